@@ -0,0 +1,120 @@
+//! Re-validating an existing corpus against a schema.
+
+use crate::corpus;
+use crate::input;
+use failure::Error;
+use jddf::{Schema, Validator};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many of the most common error paths a report lists.
+const TOP_ERROR_PATHS: usize = 10;
+
+/// The results of auditing a corpus.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Files which satisfy the schema.
+    pub valid: Vec<PathBuf>,
+
+    /// Files which are JSON, but which contain a document which does not
+    /// satisfy the schema.
+    pub invalid: Vec<PathBuf>,
+
+    /// Files which are not JSON at all.
+    pub unparseable: Vec<PathBuf>,
+
+    /// How many times each schema path rejected some file.
+    pub error_paths: HashMap<String, usize>,
+}
+
+impl Report {
+    /// The report as JSON, listing only the most common error paths.
+    pub fn to_json(&self) -> Value {
+        let mut error_paths: Vec<_> = self.error_paths.iter().collect();
+        error_paths.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        json!({
+            "valid": self.valid.len(),
+            "invalid": self.invalid.len(),
+            "unparseable": self.unparseable.len(),
+            "commonErrorPaths": error_paths
+                .into_iter()
+                .take(TOP_ERROR_PATHS)
+                .map(|(path, count)| json!({ "schemaPath": path, "count": count }))
+                .collect::<Vec<_>>(),
+            "invalidFiles": self
+                .invalid
+                .iter()
+                .chain(&self.unparseable)
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Validates every file directly within a directory against a schema, other
+/// than those describing a content-addressed corpus.
+///
+/// A file holding a JSON text sequence is valid only if every document in the
+/// sequence is.
+pub fn audit(schema: &Schema, dir: &Path) -> Result<Report, Error> {
+    let validator = Validator::new();
+    let mut report = Report::default();
+
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    for path in paths {
+        if !path.is_file() || path.file_name().is_some_and(corpus::is_metadata) {
+            continue;
+        }
+
+        let instances = match input::parse_documents(&fs::read(&path)?) {
+            Ok(instances) => instances,
+            Err(_) => {
+                report.unparseable.push(path);
+                continue;
+            }
+        };
+
+        let mut valid = true;
+        for instance in &instances {
+            for error in validator.validate(schema, instance)? {
+                *report
+                    .error_paths
+                    .entry(error.schema_path().to_string())
+                    .or_insert(0) += 1;
+                valid = false;
+            }
+        }
+
+        if valid {
+            report.valid.push(path);
+        } else {
+            report.invalid.push(path);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Moves every invalid or unparseable file in a report into a directory.
+pub fn quarantine(report: &Report, dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+
+    for path in report.invalid.iter().chain(&report.unparseable) {
+        let dest = dir.join(path.file_name().unwrap());
+        if fs::rename(path, &dest).is_err() {
+            // Renaming fails across filesystems, in which case fall back to
+            // copying.
+            fs::copy(path, &dest)?;
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
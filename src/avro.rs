@@ -0,0 +1,775 @@
+//! Avro encoding of generated documents.
+//!
+//! Only a restricted set of JDDF schemas can be mapped to Avro. The mapping
+//! is:
+//!
+//! * The empty form becomes a union of `null`, `boolean`, `long`, `double`,
+//!   and `string`.
+//! * `boolean` becomes `boolean`; `int8`, `uint8`, `int16`, `uint16`, and
+//!   `int32` become `int`; `uint32` becomes `long`; `float32` and `float64`
+//!   become `float` and `double`. `string` and `timestamp` both become
+//!   `string`, so timestamps keep their RFC 3339 representation.
+//! * The enum form becomes an Avro `enum`, so its values must be valid Avro
+//!   names.
+//! * The elements and values forms become Avro `array` and `map`.
+//! * The properties form becomes a `record`, with optional properties as
+//!   unions of `null` and their type. Property names must be valid Avro names,
+//!   and additional properties are not supported. An absent optional property
+//!   in the empty form cannot be told apart from one which is `null`.
+//! * The discriminator form becomes a union of records, one per mapping. Each
+//!   record carries the tag as a `string` field.
+//! * The ref form refers to a named record if the definition is in the
+//!   properties form. Other definitions are inlined, and so may not be
+//!   recursive.
+
+use crate::binary;
+use failure::{bail, Error};
+use jddf::schema::{Form, Type};
+use jddf::Schema;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// How many records an object container file holds per block.
+const BLOCK_SIZE: usize = 100;
+
+/// A JDDF schema, mapped to Avro.
+#[derive(Debug)]
+pub struct AvroSchema {
+    /// The Avro schema, in its JSON representation.
+    pub json: Value,
+
+    root: AvroType,
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Clone)]
+enum AvroType {
+    Any,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    String,
+    Enum(Vec<String>),
+    Array(Box<AvroType>),
+    Map(Box<AvroType>),
+
+    /// An index into the schema's records.
+    Record(usize),
+
+    /// The name of the tag, and the index of each mapping's record.
+    Discriminator(String, Vec<(String, usize)>),
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    optional: bool,
+    typ: AvroType,
+}
+
+impl AvroSchema {
+    /// Maps a JDDF schema to Avro.
+    pub fn from_schema(schema: &Schema) -> Result<Self, Error> {
+        let mut converter = Converter {
+            root: schema,
+            records: Vec::new(),
+            names: HashSet::new(),
+            defs: HashMap::new(),
+            inlining: HashSet::new(),
+        };
+
+        let (root, json) = converter.convert("Root", schema)?;
+        Ok(AvroSchema {
+            json,
+            root,
+            records: converter.records,
+        })
+    }
+
+    /// Encodes a document using Avro's binary encoding.
+    pub fn encode(&self, val: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.encode_as(&self.root, val, out)
+    }
+
+    fn encode_as(&self, typ: &AvroType, val: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+        match (typ, val) {
+            (AvroType::Any, Value::Null) => write_long(out, 0),
+            (AvroType::Any, Value::Bool(b)) => {
+                write_long(out, 1);
+                out.push(*b as u8);
+            }
+            (AvroType::Any, Value::Number(n)) if n.is_i64() => {
+                write_long(out, 2);
+                write_long(out, n.as_i64().unwrap());
+            }
+            (AvroType::Any, Value::Number(n)) => {
+                write_long(out, 3);
+                out.extend_from_slice(&binary::float(n).to_le_bytes());
+            }
+            (AvroType::Any, Value::String(s)) => {
+                write_long(out, 4);
+                write_str(out, s);
+            }
+            (AvroType::Boolean, Value::Bool(b)) => out.push(*b as u8),
+            (AvroType::Int, Value::Number(n)) | (AvroType::Long, Value::Number(n))
+                if n.is_i64() =>
+            {
+                write_long(out, n.as_i64().unwrap())
+            }
+            (AvroType::Float, Value::Number(n)) => {
+                out.extend_from_slice(&(binary::float(n) as f32).to_le_bytes())
+            }
+            (AvroType::Double, Value::Number(n)) => {
+                out.extend_from_slice(&binary::float(n).to_le_bytes())
+            }
+            (AvroType::String, Value::String(s)) => write_str(out, s),
+            (AvroType::Enum(symbols), Value::String(s)) => {
+                match symbols.iter().position(|symbol| symbol == s) {
+                    Some(i) => write_long(out, i as i64),
+                    None => bail!("not an Avro enum symbol: {}", s),
+                }
+            }
+            (AvroType::Array(items), Value::Array(vals)) => {
+                if !vals.is_empty() {
+                    write_long(out, vals.len() as i64);
+                    for val in vals {
+                        self.encode_as(items, val, out)?;
+                    }
+                }
+
+                write_long(out, 0);
+            }
+            (AvroType::Map(values), Value::Object(vals)) => {
+                if !vals.is_empty() {
+                    write_long(out, vals.len() as i64);
+                    for (k, v) in vals {
+                        write_str(out, k);
+                        self.encode_as(values, v, out)?;
+                    }
+                }
+
+                write_long(out, 0);
+            }
+            (AvroType::Record(i), Value::Object(_)) => self.encode_record(*i, val, out)?,
+            (AvroType::Discriminator(tag, mapping), Value::Object(_)) => {
+                self.encode_discriminator(tag, mapping, 0, val, out)?
+            }
+            _ => bail!("value does not match Avro schema: {}", val),
+        }
+
+        Ok(())
+    }
+
+    /// Encodes an object as a member of a union of records. `offset` is the
+    /// index of the first record in the union.
+    fn encode_discriminator(
+        &self,
+        tag: &str,
+        mapping: &[(String, usize)],
+        offset: usize,
+        val: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let tag_val = val.get(tag).and_then(Value::as_str);
+        match mapping
+            .iter()
+            .position(|(k, _)| Some(k.as_str()) == tag_val)
+        {
+            Some(i) => {
+                write_long(out, (offset + i) as i64);
+                self.encode_record(mapping[i].1, val, out)
+            }
+            None => bail!("no Avro union member for discriminator tag: {}", tag),
+        }
+    }
+
+    fn encode_record(&self, i: usize, val: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+        for field in &self.records[i].fields {
+            match (field.optional, val.get(&field.name)) {
+                (true, None) => write_long(out, 0),
+
+                // Unions cannot contain unions, so these types were merged into
+                // a single union with null, rather than nested in one.
+                (true, Some(v)) if matches!(field.typ, AvroType::Any) => {
+                    self.encode_as(&field.typ, v, out)?
+                }
+                (true, Some(v)) => match field.typ {
+                    AvroType::Discriminator(ref tag, ref mapping) => {
+                        self.encode_discriminator(tag, mapping, 1, v, out)?
+                    }
+                    _ => {
+                        write_long(out, 1);
+                        self.encode_as(&field.typ, v, out)?;
+                    }
+                },
+                (false, Some(v)) => self.encode_as(&field.typ, v, out)?,
+                (false, None) => bail!("missing property for Avro record: {}", field.name),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Converter<'a> {
+    root: &'a Schema,
+    records: Vec<Record>,
+
+    /// The names of all records so far, which must be unique.
+    names: HashSet<String>,
+
+    /// The record index and name of each definition mapped to a record.
+    defs: HashMap<String, (usize, String)>,
+
+    /// The definitions currently being inlined, to detect recursion.
+    inlining: HashSet<String>,
+}
+
+impl<'a> Converter<'a> {
+    /// Maps a schema to its Avro type and the JSON representation of that
+    /// type. `name` is used for any records the schema becomes.
+    fn convert(&mut self, name: &str, schema: &Schema) -> Result<(AvroType, Value), Error> {
+        Ok(match schema.form() {
+            Form::Empty => (
+                AvroType::Any,
+                json!(["null", "boolean", "long", "double", "string"]),
+            ),
+            Form::Type(Type::Boolean) => (AvroType::Boolean, json!("boolean")),
+            Form::Type(Type::Int8)
+            | Form::Type(Type::Uint8)
+            | Form::Type(Type::Int16)
+            | Form::Type(Type::Uint16)
+            | Form::Type(Type::Int32) => (AvroType::Int, json!("int")),
+            Form::Type(Type::Uint32) => (AvroType::Long, json!("long")),
+            Form::Type(Type::Float32) => (AvroType::Float, json!("float")),
+            Form::Type(Type::Float64) => (AvroType::Double, json!("double")),
+            Form::Type(Type::String) | Form::Type(Type::Timestamp) => {
+                (AvroType::String, json!("string"))
+            }
+            Form::Enum(ref vals) => {
+                let mut symbols: Vec<_> = vals.iter().cloned().collect();
+                symbols.sort();
+                for symbol in &symbols {
+                    check_name(symbol)?;
+                }
+
+                let name = self.name(name);
+                let json = json!({ "type": "enum", "name": name, "symbols": symbols });
+                (AvroType::Enum(symbols), json)
+            }
+            Form::Elements(ref sub_schema) => {
+                let (items, json) = self.convert(name, sub_schema)?;
+                (
+                    AvroType::Array(Box::new(items)),
+                    json!({ "type": "array", "items": json }),
+                )
+            }
+            Form::Values(ref sub_schema) => {
+                let (values, json) = self.convert(name, sub_schema)?;
+                (
+                    AvroType::Map(Box::new(values)),
+                    json!({ "type": "map", "values": json }),
+                )
+            }
+            Form::Properties { .. } => {
+                let i = self.records.len();
+                self.records.push(Record::default());
+                let name = self.name(name);
+                let json = self.convert_record(i, &name, None, schema)?;
+                (AvroType::Record(i), json)
+            }
+            Form::Discriminator(ref tag, ref mapping) => {
+                let mut keys: Vec<_> = mapping.keys().collect();
+                keys.sort();
+
+                let mut arms = Vec::new();
+                let mut jsons = Vec::new();
+                for k in keys {
+                    let i = self.records.len();
+                    self.records.push(Record::default());
+                    let name = self.name(&format!("{}_{}", name, k));
+                    jsons.push(self.convert_record(i, &name, Some(tag), &mapping[k])?);
+                    arms.push((k.clone(), i));
+                }
+
+                (
+                    AvroType::Discriminator(tag.clone(), arms),
+                    Value::Array(jsons),
+                )
+            }
+            Form::Ref(ref def) => {
+                if let Some((i, name)) = self.defs.get(def) {
+                    return Ok((AvroType::Record(*i), json!(name)));
+                }
+
+                let sub_schema = &self.root.definitions().as_ref().unwrap()[def];
+                if let Form::Properties { .. } = sub_schema.form() {
+                    let i = self.records.len();
+                    self.records.push(Record::default());
+                    let name = self.name(def);
+                    self.defs.insert(def.clone(), (i, name.clone()));
+                    let json = self.convert_record(i, &name, None, sub_schema)?;
+                    (AvroType::Record(i), json)
+                } else {
+                    if !self.inlining.insert(def.clone()) {
+                        bail!("recursive definition is not a record in Avro: {}", def);
+                    }
+
+                    let converted = self.convert(def, sub_schema)?;
+                    self.inlining.remove(def);
+                    converted
+                }
+            }
+        })
+    }
+
+    /// Maps a schema in the properties form to the record at index `i`. If
+    /// the record is part of a discriminator, `tag` is the discriminator's
+    /// tag.
+    fn convert_record(
+        &mut self,
+        i: usize,
+        name: &str,
+        tag: Option<&String>,
+        schema: &Schema,
+    ) -> Result<Value, Error> {
+        let (required, optional, allow_additional) = match schema.form() {
+            Form::Properties {
+                required,
+                optional,
+                allow_additional,
+                ..
+            } => (required, optional, *allow_additional),
+            _ => unreachable!("discriminator mappings are always properties"),
+        };
+
+        if allow_additional {
+            bail!("additionalProperties is not supported in Avro: {}", name);
+        }
+
+        let mut fields = Vec::new();
+        let mut jsons = Vec::new();
+
+        if let Some(tag) = tag {
+            check_name(tag)?;
+            fields.push(Field {
+                name: tag.clone(),
+                optional: false,
+                typ: AvroType::String,
+            });
+            jsons.push(json!({ "name": tag, "type": "string" }));
+        }
+
+        let mut props: Vec<_> = required
+            .iter()
+            .map(|(k, v)| (k, v, false))
+            .chain(optional.iter().map(|(k, v)| (k, v, true)))
+            .collect();
+        props.sort_by_key(|(k, _, _)| *k);
+
+        for (k, v, is_optional) in props {
+            check_name(k)?;
+            let (typ, json) = self.convert(&format!("{}_{}", name, k), v)?;
+            if is_optional {
+                // Unions cannot directly contain other unions, so types which
+                // are already unions have null merged into them instead.
+                let json = match json {
+                    Value::Array(members) if members[0] == "null" => Value::Array(members),
+                    Value::Array(members) => {
+                        Value::Array(std::iter::once(json!("null")).chain(members).collect())
+                    }
+                    json => json!(["null", json]),
+                };
+
+                jsons.push(json!({ "name": k, "type": json, "default": null }));
+            } else {
+                jsons.push(json!({ "name": k, "type": json }));
+            }
+
+            fields.push(Field {
+                name: k.clone(),
+                optional: is_optional,
+                typ,
+            });
+        }
+
+        self.records[i].fields = fields;
+        Ok(json!({ "type": "record", "name": name, "fields": jsons }))
+    }
+
+    /// Returns a unique Avro name based on `base`.
+    fn name(&mut self, base: &str) -> String {
+        let base: String = base
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        let mut name = base.clone();
+        let mut n = 1;
+        while !self.names.insert(name.clone()) {
+            n += 1;
+            name = format!("{}{}", base, n);
+        }
+
+        name
+    }
+}
+
+/// Checks that a string is a valid Avro name.
+fn check_name(name: &str) -> Result<(), Error> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) => {
+            (c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+
+    if !valid {
+        bail!("not a valid Avro name: {:?}", name);
+    }
+
+    Ok(())
+}
+
+/// Writes a zig-zag encoded variable-length integer.
+fn write_long(out: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+
+    out.push(n as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_long(out, s.len() as i64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes Avro object container files, one block of records at a time.
+#[derive(Debug)]
+pub struct ContainerWriter {
+    schema: AvroSchema,
+    sync: [u8; 16],
+    started: bool,
+    count: usize,
+    block: Vec<u8>,
+}
+
+impl ContainerWriter {
+    pub fn new<R: rand::Rng + ?Sized>(rng: &mut R, schema: AvroSchema) -> Self {
+        ContainerWriter {
+            schema,
+            sync: rng.gen(),
+            started: false,
+            count: 0,
+            block: Vec::new(),
+        }
+    }
+
+    pub fn write<W: Write>(&mut self, out: &mut W, val: &Value) -> Result<(), Error> {
+        if !self.started {
+            let mut header = b"Obj\x01".to_vec();
+            write_long(&mut header, 2);
+            write_str(&mut header, "avro.schema");
+            write_str(&mut header, &self.schema.json.to_string());
+            write_str(&mut header, "avro.codec");
+            write_str(&mut header, "null");
+            write_long(&mut header, 0);
+            header.extend_from_slice(&self.sync);
+
+            out.write_all(&header)?;
+            self.started = true;
+        }
+
+        self.schema.encode(val, &mut self.block)?;
+        self.count += 1;
+        if self.count == BLOCK_SIZE {
+            self.flush(out)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes out any records not yet written as part of a block, and begins
+    /// the next record written with a new header, as the start of a new file.
+    pub fn end_file<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.flush(out)?;
+        self.started = false;
+        Ok(())
+    }
+
+    /// Writes out any records not yet written as part of a block.
+    pub fn flush<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.count == 0 {
+            return Ok(());
+        }
+
+        let mut header = Vec::new();
+        write_long(&mut header, self.count as i64);
+        write_long(&mut header, self.block.len() as i64);
+
+        out.write_all(&header)?;
+        out.write_all(&self.block)?;
+        out.write_all(&self.sync)?;
+
+        self.count = 0;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check, fuzz, seeded_rng};
+    use serde_json::Number;
+    use std::convert::TryFrom;
+
+    fn avro_schema(schema: Value) -> AvroSchema {
+        AvroSchema::from_schema(&check::parse(schema).unwrap()).unwrap()
+    }
+
+    fn encode(schema: &AvroSchema, val: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        schema.encode(&val, &mut out).unwrap();
+        out
+    }
+
+    /// Leaves out null members, since an absent optional property is decoded
+    /// as null, and writes every number as a float, as it would be compared.
+    fn normalize(val: Value) -> Value {
+        match val {
+            Value::Number(n) => Value::Number(Number::from_f64(binary::float(&n)).unwrap()),
+            Value::Array(vals) => Value::Array(vals.into_iter().map(normalize).collect()),
+            Value::Object(obj) => Value::Object(
+                obj.into_iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| (k, normalize(v)))
+                    .collect(),
+            ),
+            val => val,
+        }
+    }
+
+    /// Checks that documents of a schema, encoded as Avro, are decoded as
+    /// the same documents by apache-avro.
+    fn assert_round_trips(schema: Value) {
+        let jddf = check::parse(schema).unwrap();
+        let schema = AvroSchema::from_schema(&jddf).unwrap();
+        let parsed = apache_avro::Schema::parse(&schema.json)
+            .unwrap_or_else(|err| panic!("{}: {}", err, schema.json));
+
+        let mut rng = seeded_rng(&[0]);
+        for _ in 0..100 {
+            let doc = fuzz(&mut rng, &jddf);
+            let datum = encode(&schema, doc.clone());
+            let mut reader = &datum[..];
+            let decoded = apache_avro::from_avro_datum(&parsed, &mut reader, None).unwrap();
+            assert!(reader.is_empty(), "trailing bytes after {}", doc);
+            assert_eq!(
+                normalize(Value::try_from(decoded).unwrap()),
+                normalize(doc.clone()),
+                "{}",
+                doc
+            );
+        }
+    }
+
+    #[test]
+    fn record_bytes() {
+        let schema = avro_schema(json!({
+            "properties": { "a": { "type": "int32" }, "b": { "type": "string" } },
+            "optionalProperties": { "c": { "type": "boolean" } },
+        }));
+
+        assert_eq!(
+            encode(&schema, json!({ "a": -1, "b": "hi" })),
+            [0x01, 0x04, b'h', b'i', 0x00]
+        );
+        assert_eq!(
+            encode(&schema, json!({ "a": 64, "b": "", "c": true })),
+            [0x80, 0x01, 0x00, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    fn float_bytes() {
+        // A float32 is written as the nearest f32, which a round trip through
+        // apache-avro would widen to a different f64 than the one generated.
+        let schema = avro_schema(json!({ "type": "float32" }));
+        assert_eq!(encode(&schema, json!(1.5)), [0x00, 0x00, 0xc0, 0x3f]);
+        assert_eq!(encode(&schema, json!(0.1)), 0.1f32.to_le_bytes());
+
+        let schema = avro_schema(json!({ "type": "float64" }));
+        let infinity: Value = serde_json::from_str("-1e999").unwrap();
+        assert_eq!(encode(&schema, infinity), f64::NEG_INFINITY.to_le_bytes());
+    }
+
+    #[test]
+    fn map_bytes() {
+        let schema = avro_schema(json!({ "values": { "type": "boolean" } }));
+        assert_eq!(encode(&schema, json!({})), [0x00]);
+        assert_eq!(
+            encode(&schema, json!({ "x": true, "y": false })),
+            [0x04, 0x02, b'x', 0x01, 0x02, b'y', 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn union_bytes() {
+        let schema = avro_schema(json!({
+            "discriminator": {
+                "tag": "kind",
+                "mapping": {
+                    "b": { "properties": { "n": { "type": "uint8" } } },
+                    "a": { "properties": {} },
+                },
+            },
+        }));
+
+        // Variants are in order of their tags, and carry the tag.
+        assert_eq!(encode(&schema, json!({ "kind": "a" })), [0x00, 0x02, b'a']);
+        assert_eq!(
+            encode(&schema, json!({ "kind": "b", "n": 2 })),
+            [0x02, 0x02, b'b', 0x04]
+        );
+
+        let schema = avro_schema(json!({}));
+        assert_eq!(encode(&schema, json!(null)), [0x00]);
+        assert_eq!(encode(&schema, json!(true)), [0x02, 0x01]);
+        assert_eq!(encode(&schema, json!(-2)), [0x04, 0x03]);
+        assert_eq!(encode(&schema, json!("")), [0x08, 0x00]);
+    }
+
+    #[test]
+    fn round_trip_records() {
+        assert_round_trips(json!({
+            "properties": {
+                "id": { "type": "uint32" },
+                "weight": { "type": "float64" },
+                "name": { "type": "string" },
+                "at": { "type": "timestamp" },
+                "color": { "enum": ["RED", "GREEN"] },
+                "tags": { "elements": { "type": "string" } },
+                "counts": { "values": { "type": "int16" } },
+            },
+            "optionalProperties": {
+                "extra": {},
+                "owner": { "properties": { "ok": { "type": "boolean" } } },
+            },
+        }));
+    }
+
+    #[test]
+    fn round_trip_unions() {
+        let event = json!({
+            "discriminator": {
+                "tag": "type",
+                "mapping": {
+                    "click": { "properties": { "x": { "type": "int32" } } },
+                    "key": { "optionalProperties": { "code": { "type": "string" } } },
+                },
+            },
+        });
+
+        assert_round_trips(event.clone());
+        assert_round_trips(json!({
+            "properties": { "events": { "elements": event.clone() } },
+            "optionalProperties": { "last": event, "anything": {} },
+        }));
+    }
+
+    #[test]
+    fn round_trip_recursive_records() {
+        assert_round_trips(json!({
+            "definitions": {
+                "node": {
+                    "properties": { "value": { "type": "int8" } },
+                    "optionalProperties": {
+                        "children": { "elements": { "ref": "node" } },
+                        "next": { "ref": "node" },
+                    },
+                },
+            },
+            "ref": "node",
+        }));
+    }
+
+    #[test]
+    fn recursive_non_records() {
+        let schema = check::parse(json!({
+            "definitions": { "list": { "elements": { "ref": "list" } } },
+            "ref": "list",
+        }))
+        .unwrap();
+
+        assert!(AvroSchema::from_schema(&schema).is_err());
+    }
+
+    #[test]
+    fn unique_names() {
+        let root = check::parse(json!({})).unwrap();
+        let mut converter = Converter {
+            root: &root,
+            records: Vec::new(),
+            names: HashSet::new(),
+            defs: HashMap::new(),
+            inlining: HashSet::new(),
+        };
+
+        assert_eq!(converter.name("a-b"), "a_b");
+        assert_eq!(converter.name("a_b"), "a_b2");
+        assert_eq!(converter.name("a.b"), "a_b3");
+        assert_eq!(converter.name("c"), "c");
+
+        // A definition named like a record nested in the root must still get
+        // a name of its own.
+        assert_round_trips(json!({
+            "definitions": { "Root_x": { "properties": { "y": { "type": "string" } } } },
+            "properties": {
+                "x": { "properties": { "z": { "type": "boolean" } } },
+                "r": { "ref": "Root_x" },
+            },
+        }));
+    }
+
+    #[test]
+    fn container_files() {
+        let jddf = check::parse(json!({
+            "properties": { "n": { "type": "uint8" } },
+            "optionalProperties": { "s": { "type": "string" } },
+        }))
+        .unwrap();
+
+        let mut rng = seeded_rng(&[0]);
+        let docs: Vec<_> = (0..BLOCK_SIZE + 50)
+            .map(|_| fuzz(&mut rng, &jddf))
+            .collect();
+
+        let mut writer = ContainerWriter::new(&mut rng, AvroSchema::from_schema(&jddf).unwrap());
+        let mut out = Vec::new();
+        for doc in &docs {
+            writer.write(&mut out, doc).unwrap();
+        }
+        writer.end_file(&mut out).unwrap();
+
+        let decoded: Vec<_> = apache_avro::Reader::new(&out[..])
+            .unwrap()
+            .map(|val| normalize(Value::try_from(val.unwrap()).unwrap()))
+            .collect();
+        let docs: Vec<_> = docs.into_iter().map(normalize).collect();
+        assert_eq!(decoded, docs);
+    }
+}
@@ -0,0 +1,142 @@
+//! Encoding documents as CBOR (RFC 8949) or MessagePack, for consumers which
+//! take those rather than JSON, with `serde_cbor` and `rmp-serde`.
+//!
+//! Integers are encoded as integers, and every other number as a float. Maps
+//! keep the order of the document's members.
+
+use failure::Error;
+use serde::{Serialize, Serializer};
+use serde_json::{Number, Value};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// Encodes a value as CBOR.
+pub fn cbor(val: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    serde_cbor::to_writer(&mut out, &Plain(val))?;
+    Ok(out)
+}
+
+/// Encodes a value as MessagePack.
+pub fn msgpack(val: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    rmp_serde::encode::write(&mut out, &Plain(val))?;
+    Ok(out)
+}
+
+/// Writes an encoded value as a record, preceded by its length in bytes as a
+/// big-endian 32-bit integer.
+pub fn write_record<W: Write>(out: &mut W, datum: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(datum.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "a record of {} bytes is too long for its length to be written",
+                datum.len()
+            ),
+        )
+    })?;
+
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(datum)
+}
+
+/// A value, serialized with its numbers as integers or floats.
+///
+/// With serde_json's `arbitrary_precision` feature, a `Number` serializes as a
+/// map holding its digits, which only serde_json knows to read back as a
+/// number.
+struct Plain<'a>(&'a Value);
+
+impl Serialize for Plain<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(n), _) => serializer.serialize_u64(n),
+                // Negative zero, as --int-floats writes it, is zero.
+                (None, Some(n)) => serializer.serialize_i64(n),
+                (None, None) => serializer.serialize_f64(float(n)),
+            },
+            Value::Array(vals) => serializer.collect_seq(vals.iter().map(Plain)),
+            Value::Object(obj) => {
+                serializer.collect_map(obj.iter().map(|(k, val)| (k, Plain(val))))
+            }
+            val => val.serialize(serializer),
+        }
+    }
+}
+
+/// A number which is not an integer in range of `i64` or `u64`, as a float.
+/// Numbers too large for an `f64` become infinite.
+pub(crate) fn float(n: &Number) -> f64 {
+    n.to_string().parse().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(f: fn(&Value) -> Result<Vec<u8>, Error>, json: &str) -> Vec<u8> {
+        f(&serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn cbor_integers() {
+        assert_eq!(encode(cbor, "0"), [0x00]);
+        assert_eq!(encode(cbor, "-0"), [0x00]);
+        assert_eq!(encode(cbor, "23"), [0x17]);
+        assert_eq!(encode(cbor, "24"), [0x18, 0x18]);
+        assert_eq!(encode(cbor, "-1"), [0x20]);
+        assert_eq!(encode(cbor, "-500"), [0x39, 0x01, 0xf3]);
+        assert_eq!(
+            encode(cbor, "-9223372036854775808"),
+            [0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn cbor_containers() {
+        assert_eq!(
+            encode(cbor, r#"{"a":[null,true,"b"]}"#),
+            [0xa1, 0x61, b'a', 0x83, 0xf6, 0xf5, 0x61, b'b']
+        );
+    }
+
+    #[test]
+    fn msgpack_integers() {
+        assert_eq!(encode(msgpack, "0"), [0x00]);
+        assert_eq!(encode(msgpack, "-0"), [0x00]);
+        assert_eq!(encode(msgpack, "200"), [0xcc, 0xc8]);
+        assert_eq!(encode(msgpack, "-1"), [0xff]);
+        assert_eq!(encode(msgpack, "-33"), [0xd0, 0xdf]);
+        assert_eq!(encode(msgpack, "-500"), [0xd1, 0xfe, 0x0c]);
+    }
+
+    #[test]
+    fn msgpack_containers() {
+        assert_eq!(
+            encode(msgpack, r#"{"a":[null,false,"b"]}"#),
+            [0x81, 0xa1, b'a', 0x93, 0xc0, 0xc2, 0xa1, b'b']
+        );
+    }
+
+    #[test]
+    fn floats() {
+        for json in &["1.5", "-0.1", "3.4028234663852886e38", "1e999", "-1e999"] {
+            let n: f64 = json.parse().unwrap();
+
+            let decoded: serde_cbor::Value = serde_cbor::from_slice(&encode(cbor, json)).unwrap();
+            assert_eq!(decoded, serde_cbor::Value::Float(n), "{}", json);
+
+            let decoded: f64 = rmp_serde::from_slice(&encode(msgpack, json)).unwrap();
+            assert_eq!(decoded, n, "{}", json);
+        }
+    }
+
+    #[test]
+    fn records() {
+        let mut out = Vec::new();
+        write_record(&mut out, &[0xf6]).unwrap();
+        assert_eq!(out, [0, 0, 0, 1, 0xf6]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Weighing the variants of nested discriminators, under `branch_decay`, so
+//! that deeply nested ones favor variants with small documents, and finding
+//! the variant with the smallest documents of all.
+
+use jddf::schema::Form;
+use jddf::Schema;
+use std::collections::HashMap;
+
+/// How many values the smallest document satisfying each definition has.
+///
+/// Definitions can refer to one another, so this starts out knowing none of
+/// their sizes, and works them out again until none get any smaller.
+pub fn def_sizes(root: &Schema) -> HashMap<String, usize> {
+    let mut sizes = HashMap::new();
+    let defs = match root.definitions() {
+        Some(defs) => defs,
+        None => return sizes,
+    };
+
+    loop {
+        let mut changed = false;
+        for (def, sub_schema) in defs {
+            if let Some(n) = size(sub_schema, &sizes) {
+                if sizes.get(def).is_none_or(|&m| n < m) {
+                    sizes.insert(def.clone(), n);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            return sizes;
+        }
+    }
+}
+
+/// The weight of each of a discriminator's variants, given in `tag_vals`, when
+/// nested within `depth` other discriminators.
+///
+/// A variant whose smallest document has `extra` more values than the
+/// smallest variant's weighs `(1 + extra) ^ -(decay * depth)`, so that no
+/// variant is ever ruled out entirely.
+pub fn weights(
+    sizes: &HashMap<String, usize>,
+    mapping: &HashMap<String, Schema>,
+    tag_vals: &[&String],
+    decay: f64,
+    depth: usize,
+) -> Vec<f64> {
+    let costs: Vec<_> = tag_vals
+        .iter()
+        .map(|tag_val| size(&mapping[*tag_val], sizes).unwrap_or(usize::MAX))
+        .collect();
+    let min = costs.iter().copied().min().unwrap_or(0);
+
+    costs
+        .into_iter()
+        .map(|cost| (1.0 + (cost - min) as f64).powf(-decay * depth as f64))
+        .collect()
+}
+
+/// The variant of a discriminator whose smallest document has the fewest
+/// values, or the first such variant in sorted order if several tie.
+///
+/// Some variants may only have infinitely deep documents, such as one which
+/// refers back to the discriminator itself. The smallest variant never does.
+pub fn smallest_variant<'a>(
+    sizes: &HashMap<String, usize>,
+    mapping: &'a HashMap<String, Schema>,
+) -> &'a String {
+    crate::sorted_keys(mapping)
+        .into_iter()
+        .min_by_key(|tag_val| size(&mapping[*tag_val], sizes).unwrap_or(usize::MAX))
+        .unwrap()
+}
+
+/// How many values the smallest value satisfying a schema has, counting the
+/// value itself and everything within it, given the sizes of definitions
+/// known so far.
+fn size(schema: &Schema, sizes: &HashMap<String, usize>) -> Option<usize> {
+    match schema.form() {
+        Form::Ref(def) => sizes.get(def).copied(),
+        Form::Properties { required, .. } => required
+            .values()
+            .map(|sub_schema| size(sub_schema, sizes))
+            .sum::<Option<usize>>()
+            .map(|n| n + 1),
+
+        // The tag is a value of its own, alongside the variant's properties.
+        Form::Discriminator(_, mapping) => mapping
+            .values()
+            .filter_map(|sub_schema| size(sub_schema, sizes))
+            .min()
+            .map(|n| n + 1),
+        _ => Some(1),
+    }
+}
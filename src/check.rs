@@ -0,0 +1,375 @@
+//! Checking schemas before generating anything for them, so that a schema
+//! which can't be generated for is reported along with where the problem is,
+//! rather than found partway through a run.
+//!
+//! [`crate::fuzz`] panics on schemas which [`schema`] rejects.
+
+use crate::config::{Freeze, FuzzConfig};
+use crate::hints;
+use crate::pattern::Pattern;
+use crate::pointer::Pointer;
+use crate::weights;
+use failure::{bail, format_err, Error};
+use jddf::schema::{Form, Type};
+use jddf::{Schema, SerdeSchema, Validator};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// Parses a schema from JSON, and checks it as [`schema`] does.
+///
+/// When the JSON is not a valid schema, the error names the schema path of the
+/// innermost schema which is not valid on its own.
+pub fn parse(json: Value) -> Result<Schema, Error> {
+    let root = convert_located(json)?;
+    schema(&root)?;
+    Ok(root)
+}
+
+/// Parses a schema from JSON, and checks it as [`schema_with_unsupported`]
+/// does.
+pub fn parse_with_unsupported(json: Value) -> Result<(Schema, Vec<(String, Error)>), Error> {
+    let root = convert_located(json)?;
+    let unsupported = schema_with_unsupported(&root)?;
+    Ok((root, unsupported))
+}
+
+fn convert_located(json: Value) -> Result<Schema, Error> {
+    let root = match convert(json.clone()) {
+        Ok(root) => root,
+        Err(err) => {
+            let defs: Vec<_> = match json.get("definitions") {
+                Some(Value::Object(defs)) => defs.keys().cloned().collect(),
+                _ => Vec::new(),
+            };
+
+            return Err(match locate(&json, &defs, &mut Vec::new()) {
+                Some(path) => format_err!("{} at schema path: {}", err, Pointer(path)),
+                None => err,
+            });
+        }
+    };
+
+    Ok(root)
+}
+
+/// Checks that there are values satisfying every part of a schema, and that
+/// they can be generated in finitely many steps.
+///
+/// jddf accepts discriminators with no variants, which nothing satisfies, and
+/// definitions which can only be satisfied by infinitely deep documents, such
+/// as one whose only property is required and refers back to the definition.
+/// It also keeps any metadata, so this checks the format hints and weights in
+/// it.
+pub fn schema(root: &Schema) -> Result<(), Error> {
+    check(root, &mut None)
+}
+
+/// Checks a schema as [`schema`] does, except that format hints it would
+/// reject are given back, with the schema paths they're at, rather than
+/// rejected.
+///
+/// Generating for such a schema is only meant for configs whose
+/// `on_unsupported` is not `Error`.
+pub fn schema_with_unsupported(root: &Schema) -> Result<Vec<(String, Error)>, Error> {
+    let mut unsupported = Some(Vec::new());
+    check(root, &mut unsupported)?;
+    Ok(unsupported.unwrap())
+}
+
+/// Checks that the values a config freezes schema paths to satisfy the
+/// schemas at those paths, since they're generated as they are.
+pub fn frozen(root: &Schema, config: &FuzzConfig) -> Result<(), Error> {
+    let validator = Validator::new();
+    for path in crate::sorted_keys(&config.freeze) {
+        let val = match &config.freeze[path] {
+            Freeze::Value(val) => val,
+            _ => continue,
+        };
+
+        // Paths which aren't in the schema are never generated, so whatever
+        // they're frozen to doesn't matter.
+        let sub_schema = match at(root, &Pointer::parse(path)?.0) {
+            Some(sub_schema) => sub_schema,
+            None => continue,
+        };
+
+        // The schema at the path is validated against on its own, with the
+        // root's definitions for any refs within it.
+        let alone = Schema::from_parts(
+            root.definitions().clone(),
+            Box::new(sub_schema.form().clone()),
+            sub_schema.extra().clone(),
+        );
+        if let Some(error) = validator.validate(&alone, val)?.first() {
+            bail!(
+                "frozen value {} does not satisfy the schema at schema path: {} (instance path: {})",
+                val,
+                path,
+                error.instance_path()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The schema at a schema path, if there is one.
+fn at<'a>(root: &'a Schema, path: &[String]) -> Option<&'a Schema> {
+    let mut schema = root;
+    let mut tokens = path.iter().map(String::as_str);
+    while let Some(token) = tokens.next() {
+        schema = match (token, schema.form()) {
+            ("definitions", _) => schema.definitions().as_ref()?.get(tokens.next()?)?,
+            ("elements", Form::Elements(sub_schema)) | ("values", Form::Values(sub_schema)) => {
+                sub_schema
+            }
+            ("properties", Form::Properties { required, .. }) => required.get(tokens.next()?)?,
+            ("optionalProperties", Form::Properties { optional, .. }) => {
+                optional.get(tokens.next()?)?
+            }
+            ("discriminator", Form::Discriminator(_, mapping)) => match tokens.next()? {
+                "mapping" => mapping.get(tokens.next()?)?,
+                _ => return None,
+            },
+            _ => return None,
+        };
+    }
+
+    Some(schema)
+}
+
+fn check(root: &Schema, unsupported: &mut Option<Vec<(String, Error)>>) -> Result<(), Error> {
+    let mut path = Vec::new();
+    parts(root, &mut path, false, unsupported)?;
+
+    let defs = match root.definitions() {
+        Some(defs) => defs,
+        None => return Ok(()),
+    };
+
+    for (def, sub_schema) in crate::sorted_keys(defs)
+        .into_iter()
+        .map(|def| (def, &defs[def]))
+    {
+        path.extend(vec!["definitions".to_owned(), def.clone()]);
+        parts(sub_schema, &mut path, false, unsupported)?;
+        path.clear();
+    }
+
+    // The definitions with finite values, found by adding those which only
+    // need definitions already found, until there are no more to add.
+    let mut finite = HashSet::new();
+    loop {
+        let before = finite.len();
+        for (def, sub_schema) in defs {
+            if !finite.contains(def.as_str()) && has_finite(sub_schema, &finite) {
+                finite.insert(def.as_str());
+            }
+        }
+
+        if finite.len() == before {
+            break;
+        }
+    }
+
+    if let Some(def) = crate::sorted_keys(defs)
+        .into_iter()
+        .find(|def| !finite.contains(def.as_str()))
+    {
+        bail!(
+            "only infinitely deep documents satisfy the schema at schema path: {}",
+            Pointer(vec!["definitions".to_owned(), def.clone()])
+        );
+    }
+
+    Ok(())
+}
+
+fn convert(json: Value) -> Result<Schema, Error> {
+    let serde_schema: SerdeSchema = serde_json::from_value(json)?;
+    Schema::from_serde(serde_schema)
+}
+
+/// Finds the innermost schema within `json` which fails to convert on its own,
+/// with the root's definitions standing in as empty schemas, so that its
+/// references still resolve.
+fn locate(json: &Value, defs: &[String], path: &mut Vec<String>) -> Option<Vec<String>> {
+    let obj = json.as_object()?;
+
+    let mut children = Vec::new();
+    if path.is_empty() {
+        children.push("definitions");
+    }
+    children.extend(&["properties", "optionalProperties"]);
+    for &token in &children {
+        if let Some(Value::Object(sub_schemas)) = obj.get(token) {
+            for (k, sub_schema) in sub_schemas {
+                path.extend(vec![token.to_owned(), k.clone()]);
+                let found = locate(sub_schema, defs, path);
+                path.truncate(path.len() - 2);
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+    }
+
+    for &token in &["elements", "values"] {
+        if let Some(sub_schema) = obj.get(token) {
+            path.push(token.to_owned());
+            let found = locate(sub_schema, defs, path);
+            path.pop();
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+
+    if let Some(Value::Object(mapping)) = obj
+        .get("discriminator")
+        .and_then(|discriminator| discriminator.get("mapping"))
+    {
+        for (tag_val, sub_schema) in mapping {
+            path.extend(vec![
+                "discriminator".to_owned(),
+                "mapping".to_owned(),
+                tag_val.clone(),
+            ]);
+            let found = locate(sub_schema, defs, path);
+            path.truncate(path.len() - 3);
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+
+    let mut alone = obj.clone();
+    alone.insert(
+        "definitions".to_owned(),
+        defs.iter()
+            .map(|def| (def.clone(), Value::Object(Map::new())))
+            .collect::<Map<_, _>>()
+            .into(),
+    );
+
+    if convert(alone.into()).is_err() {
+        Some(path.clone())
+    } else {
+        None
+    }
+}
+
+/// Rejects discriminators with no variants, weights which [`weights::check`]
+/// rejects, and format hints which jddf-fuzz doesn't know, which aren't on
+/// strings, or whose patterns it can't parse. `weighable` is whether the
+/// schema is a discriminator variant or an optional property.
+///
+/// Rejected format hints are instead added to `unsupported`, if it is given.
+fn parts(
+    schema: &Schema,
+    path: &mut Vec<String>,
+    weighable: bool,
+    unsupported: &mut Option<Vec<(String, Error)>>,
+) -> Result<(), Error> {
+    weights::check(schema, path, weighable)?;
+
+    if let Err(err) = hint(schema, path) {
+        match unsupported {
+            Some(unsupported) => unsupported.push((Pointer(path.clone()).to_string(), err)),
+            None => return Err(err),
+        }
+    }
+
+    match schema.form() {
+        Form::Elements(sub_schema) => {
+            path.push("elements".to_owned());
+            parts(sub_schema, path, false, unsupported)?;
+            path.pop();
+        }
+        Form::Values(sub_schema) => {
+            path.push("values".to_owned());
+            parts(sub_schema, path, false, unsupported)?;
+            path.pop();
+        }
+        Form::Properties {
+            required, optional, ..
+        } => {
+            for &(token, props) in &[("properties", required), ("optionalProperties", optional)] {
+                for k in crate::sorted_keys(props) {
+                    path.extend(vec![token.to_owned(), k.clone()]);
+                    parts(&props[k], path, token == "optionalProperties", unsupported)?;
+                    path.truncate(path.len() - 2);
+                }
+            }
+        }
+        Form::Discriminator(_, mapping) => {
+            if mapping.is_empty() {
+                bail!(
+                    "discriminator has no variants at schema path: {}",
+                    Pointer(path.clone())
+                );
+            }
+
+            for tag_val in crate::sorted_keys(mapping) {
+                path.extend(vec![
+                    "discriminator".to_owned(),
+                    "mapping".to_owned(),
+                    tag_val.clone(),
+                ]);
+                parts(&mapping[tag_val], path, true, unsupported)?;
+                path.truncate(path.len() - 3);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Rejects format hints which jddf-fuzz doesn't know, which aren't on strings,
+/// or whose patterns it can't parse.
+fn hint(schema: &Schema, path: &[String]) -> Result<(), Error> {
+    match hints::of(schema.extra()) {
+        Some(Err(hint)) => bail!(
+            "unknown {} {} (expected one of: {}, or {{\"pattern\": ...}}) at schema path: {}",
+            hints::KEY,
+            hint,
+            hints::Hint::names().join(", "),
+            Pointer(path.to_vec())
+        ),
+        Some(Ok(_)) if *schema.form() != Form::Type(Type::String) => bail!(
+            "{} is only for schemas of type string at schema path: {}",
+            hints::KEY,
+            Pointer(path.to_vec())
+        ),
+        Some(Ok(hints::Hint::Pattern(source))) => {
+            if let Err(err) = Pattern::parse(source) {
+                bail!(
+                    "invalid {} pattern {:?}: {} at schema path: {}",
+                    hints::KEY,
+                    source,
+                    err,
+                    Pointer(path.to_vec())
+                );
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Whether a schema has finite values, given the definitions already known
+/// to have them.
+fn has_finite(schema: &Schema, finite: &HashSet<&str>) -> bool {
+    match schema.form() {
+        Form::Ref(def) => finite.contains(def.as_str()),
+        Form::Properties { required, .. } => required
+            .values()
+            .all(|sub_schema| has_finite(sub_schema, finite)),
+        Form::Discriminator(_, mapping) => mapping
+            .values()
+            .any(|sub_schema| has_finite(sub_schema, finite)),
+        _ => true,
+    }
+}
@@ -0,0 +1,286 @@
+//! Rust source files of fixed documents for a schema, to `include!` into test
+//! suites, so that they don't have to generate documents as they run.
+//!
+//! There is a fixture for the smallest document the schema allows, one for a
+//! document with as much in it as possible, and one for each variant of each
+//! discriminator.
+
+use crate::config::FuzzConfig;
+use crate::Context;
+use failure::Error;
+use jddf::schema::Form;
+use jddf::Schema;
+use rand_chacha::ChaChaRng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// A document, and the name and description of the constant to write it to.
+#[derive(Debug)]
+pub struct Fixture {
+    pub name: String,
+    pub description: String,
+    pub value: Value,
+}
+
+/// Generates the fixtures for a schema. Leaf values are derived from the seed,
+/// and from where they are in the schema and document, as under `--seed`.
+pub fn fixtures(root: &Schema, seed: u64) -> Result<Vec<Fixture>, Error> {
+    let config = FuzzConfig {
+        seed: Some(seed),
+        ..FuzzConfig::default()
+    };
+
+    let mut walker = Walker {
+        ctx: Context::new(root, &config)?,
+        rng: crate::seeded_rng(&[seed, crate::fnv1a("codegen-fixtures")]),
+        branch: None,
+        refs: Vec::new(),
+        discriminators: Vec::new(),
+    };
+
+    let mut fixtures = vec![
+        Fixture {
+            name: "MINIMAL".to_owned(),
+            description: "The smallest document the schema allows.".to_owned(),
+            value: crate::fuzz_minimal(&mut walker.ctx, root),
+        },
+        Fixture {
+            name: "MAXIMAL".to_owned(),
+            description: "A document with every optional property present, and an element or value in every array and map.".to_owned(),
+            value: walker.maximal(root),
+        },
+    ];
+
+    // Each variant's fixture is like the maximal one, but with that variant
+    // chosen. Variants can have discriminators of their own, which are only
+    // found once a fixture uses them.
+    let mut seen = HashSet::new();
+    let mut names = HashSet::new();
+    while let Some((path, tag_vals)) = walker.discriminators.pop() {
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+
+        for tag_val in tag_vals {
+            walker.branch = Some((path.clone(), tag_val.clone()));
+            let value = walker.maximal(root);
+
+            let mut name = branch_name(&path, &tag_val);
+            if !names.insert(name.clone()) {
+                name = (2..)
+                    .map(|i| format!("{}_{}", name, i))
+                    .find(|name| names.insert(name.clone()))
+                    .unwrap();
+            }
+
+            fixtures.push(Fixture {
+                name,
+                description: format!(
+                    "Like `MAXIMAL`, but with the discriminator at schema path `{}` taking the `{}` variant.",
+                    pointer(&path),
+                    tag_val
+                ),
+                value,
+            });
+        }
+    }
+
+    Ok(fixtures)
+}
+
+/// Writes fixtures as Rust source, each as a `pub const` string of JSON.
+pub fn write<W: Write>(out: &mut W, header: &str, fixtures: &[Fixture]) -> io::Result<()> {
+    writeln!(out, "// {}", header)?;
+    for fixture in fixtures {
+        let json = serde_json::to_string_pretty(&fixture.value)?;
+
+        // A raw string ends at a quote followed by as many hashes as it began
+        // with, so it needs more hashes than follow any quote in the JSON.
+        let hashes = "#".repeat(
+            json.split('"')
+                .skip(1)
+                .map(|s| s.len() - s.trim_start_matches('#').len())
+                .max()
+                .unwrap_or(0)
+                + 1,
+        );
+
+        writeln!(out)?;
+        writeln!(out, "/// {}", fixture.description)?;
+        writeln!(
+            out,
+            "pub const {}: &str = r{}\"{}\"{};",
+            fixture.name, hashes, json, hashes
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The name of a variant's fixture, made from the names of the properties and
+/// definitions leading to the discriminator, and the variant.
+fn branch_name(path: &[String], tag_val: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "definitions",
+        "discriminator",
+        "elements",
+        "mapping",
+        "optionalProperties",
+        "properties",
+        "values",
+    ];
+
+    let mut name = "BRANCH".to_owned();
+    for token in path
+        .iter()
+        .map(String::as_str)
+        .filter(|token| !KEYWORDS.contains(token))
+        .chain(std::iter::once(tag_val))
+    {
+        name.push('_');
+        name.extend(token.chars().map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        }));
+    }
+
+    name
+}
+
+/// Formats the tokens of a schema path as a JSON Pointer.
+fn pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+struct Walker<'a> {
+    ctx: Context<'a>,
+    rng: ChaChaRng,
+
+    /// The schema path of a discriminator, and the variant it should take.
+    /// Other discriminators take their first variant.
+    branch: Option<(Vec<String>, String)>,
+
+    /// The definitions the value currently being generated is nested within.
+    refs: Vec<String>,
+
+    /// The schema paths of the discriminators found so far, with their
+    /// variants.
+    discriminators: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl<'a> Walker<'a> {
+    /// Generates a value with as much in it as possible, except that a
+    /// definition nested within itself is as small as possible, so that
+    /// recursive schemas still make finite documents.
+    fn maximal(&mut self, schema: &Schema) -> Value {
+        match schema.form() {
+            Form::Elements(sub_schema) => {
+                self.ctx.path.push("elements".to_owned());
+                self.ctx.instance_path.push("0".to_owned());
+                let val = self.maximal(sub_schema);
+                self.ctx.instance_path.pop();
+                self.ctx.path.pop();
+
+                Value::Array(vec![val])
+            }
+            Form::Values(sub_schema) => {
+                let k = crate::fuzz_str(self.ctx.config, &mut self.rng);
+                self.ctx.path.push("values".to_owned());
+                self.ctx.instance_path.push(k.clone());
+                let val = self.maximal(sub_schema);
+                self.ctx.instance_path.pop();
+                self.ctx.path.pop();
+
+                vec![(k, val)]
+                    .into_iter()
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            }
+            Form::Properties {
+                required, optional, ..
+            } => {
+                let mut obj = serde_json::Map::new();
+                self.properties(&mut obj, "properties", required);
+                self.properties(&mut obj, "optionalProperties", optional);
+                obj.into()
+            }
+            Form::Discriminator(tag, mapping) => {
+                let tag_vals = crate::sorted_keys(mapping);
+                if !self
+                    .discriminators
+                    .iter()
+                    .any(|(path, _)| *path == self.ctx.path)
+                {
+                    self.discriminators.push((
+                        self.ctx.path.clone(),
+                        tag_vals.iter().map(|tag_val| (*tag_val).clone()).collect(),
+                    ));
+                }
+
+                let tag_val = match self.branch {
+                    Some((ref path, ref tag_val)) if *path == self.ctx.path => tag_val.clone(),
+                    _ => tag_vals[0].clone(),
+                };
+
+                self.ctx.path.extend(vec![
+                    "discriminator".to_owned(),
+                    "mapping".to_owned(),
+                    tag_val.clone(),
+                ]);
+                let mut obj = self.maximal(&mapping[&tag_val]);
+                self.ctx.path.truncate(self.ctx.path.len() - 3);
+
+                obj.as_object_mut()
+                    .unwrap()
+                    .insert(tag.clone(), tag_val.into());
+                obj
+            }
+            Form::Ref(def) => {
+                let root = self.ctx.root;
+                let definition = &root.definitions().as_ref().unwrap()[def];
+                let path = std::mem::replace(
+                    &mut self.ctx.path,
+                    vec!["definitions".to_owned(), def.clone()],
+                );
+
+                let val = if self.refs.contains(def) {
+                    crate::fuzz_minimal(&mut self.ctx, definition)
+                } else {
+                    self.refs.push(def.clone());
+                    let val = self.maximal(definition);
+                    self.refs.pop();
+                    val
+                };
+
+                self.ctx.path = path;
+                val
+            }
+            Form::Empty | Form::Type(_) | Form::Enum(_) => {
+                crate::fuzz_schema(&mut self.ctx, &mut self.rng, schema)
+            }
+        }
+    }
+
+    fn properties(
+        &mut self,
+        obj: &mut serde_json::Map<String, Value>,
+        token: &str,
+        props: &HashMap<String, Schema>,
+    ) {
+        self.ctx.path.push(token.to_owned());
+        for k in crate::sorted_keys(props) {
+            self.ctx.path.push(k.clone());
+            self.ctx.instance_path.push(k.clone());
+            obj.insert(k.clone(), self.maximal(&props[k]));
+            self.ctx.instance_path.pop();
+            self.ctx.path.pop();
+        }
+        self.ctx.path.pop();
+    }
+}
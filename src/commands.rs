@@ -0,0 +1,425 @@
+//! The `jddf-fuzz` command's subcommands, as functions.
+//!
+//! Each function does what the subcommand of the same name does, given its
+//! arguments already parsed, and writes what the subcommand would print to
+//! `out`, or returns it as JSON. Generating documents, as the command does
+//! without a subcommand, is in [`generate`](crate::generate).
+
+#[cfg(feature = "avro")]
+use crate::avro;
+use crate::config::FuzzConfig;
+use crate::output::{self, Encoder, RecordSeparator};
+use crate::pointer::{self, Pointer};
+use crate::{audit, check, codegen, corpus, dict, features, http, input, keys, merge};
+use crate::{fnv1a, mutations, next_document, run_context, run_rng, seeded_rng, transform};
+use crate::{Attempts, Context, ALGORITHM_VERSION};
+use failure::Error;
+use jddf::{Schema, Validator};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::TcpListener;
+use std::path::Path;
+
+/// A schema, as read from a file.
+#[derive(Debug)]
+pub struct SchemaFile {
+    pub schema: Schema,
+
+    /// A hash of the schema which ignores whitespace and the order of object
+    /// members, which a corpus records its runs with.
+    pub hash: String,
+
+    /// The parts of the schema jddf-fuzz doesn't support, by schema path, with
+    /// why, if they were allowed.
+    pub unsupported: Vec<(String, Error)>,
+}
+
+impl SchemaFile {
+    /// Reads a schema from a file, or from stdin if the path is a dash. Parts
+    /// of it jddf-fuzz doesn't support are an error, unless allowed.
+    pub fn read(path: &str, allow_unsupported: bool) -> Result<Self, Error> {
+        let reader: Box<dyn io::Read> = match path {
+            "-" => Box::new(io::stdin()),
+            file => Box::new(io::BufReader::new(File::open(file)?)),
+        };
+
+        Self::from_json(serde_json::from_reader(reader)?, allow_unsupported)
+    }
+
+    /// Parses a schema, as [`SchemaFile::read`] does.
+    pub fn from_json(json: Value, allow_unsupported: bool) -> Result<Self, Error> {
+        let hash = format!("{:016x}", fnv1a(&json.to_string()));
+        let (schema, unsupported) = if allow_unsupported {
+            check::parse_with_unsupported(json)?
+        } else {
+            (check::parse(json)?, Vec::new())
+        };
+
+        Ok(SchemaFile {
+            schema,
+            hash,
+            unsupported,
+        })
+    }
+}
+
+/// Reads a schema from a file, or from stdin if the path is a dash.
+pub fn read_schema(path: &str) -> Result<Schema, Error> {
+    Ok(SchemaFile::read(path, false)?.schema)
+}
+
+/// Reads documents from a file, or from stdin if the path is a dash, as JSON
+/// or a JSON text sequence.
+pub fn read_documents(path: &str) -> Result<Vec<Value>, Error> {
+    let mut bytes = Vec::new();
+    match path {
+        "-" => io::stdin().read_to_end(&mut bytes)?,
+        file => File::open(file)?.read_to_end(&mut bytes)?,
+    };
+
+    Ok(input::parse_documents(&bytes)?)
+}
+
+/// Gives the Avro schema documents are encoded with under `--avro`.
+#[cfg(feature = "avro")]
+pub fn avro_schema(schema: &Schema) -> Result<Value, Error> {
+    Ok(avro::AvroSchema::from_schema(schema)?.json)
+}
+
+/// Validates every file in a corpus directory, and moves those which are
+/// invalid into `quarantine`, if given. Returns a report on the results.
+pub fn audit(schema: &Schema, dir: &Path, quarantine: Option<&Path>) -> Result<Value, Error> {
+    let report = audit::audit(schema, dir)?;
+    if let Some(quarantine) = quarantine {
+        audit::quarantine(&report, quarantine)?;
+    }
+
+    Ok(report.to_json())
+}
+
+/// Generates the documents of each run recorded in a corpus again, and
+/// compares them with the corpus's files.
+///
+/// Returns how many runs there are, and a description of each difference.
+pub fn verify_corpus(schema: &SchemaFile, dir: &Path) -> Result<(usize, Vec<String>), Error> {
+    let runs = corpus::read_runs(dir)?;
+    let validator = Validator::new();
+    let mut differences = Vec::new();
+
+    for (i, run) in runs.iter().enumerate() {
+        if run["version"] != ALGORITHM_VERSION {
+            differences.push(format!(
+                "run {}: generated by version {} of the algorithm, not version {}",
+                i, run["version"], ALGORITHM_VERSION
+            ));
+            continue;
+        }
+
+        if run["schemaHash"].as_str() != Some(&schema.hash) {
+            differences.push(format!("run {}: the schema has changed since", i));
+            continue;
+        }
+
+        let config: FuzzConfig = serde_json::from_value(run["config"].clone())?;
+        config.validate()?;
+        let files: Vec<String> = serde_json::from_value(run["files"].clone())?;
+
+        let mut ctx = run_context(&schema.schema, &config, &[])?;
+
+        // With a seed, every value comes from an RNG seeded by its path, and
+        // not from this one.
+        let mut rng = rand::thread_rng();
+        let mut attempts = Attempts::default();
+        let mut stats = crate::stats::Stats::start();
+
+        for (j, name) in files.iter().enumerate() {
+            let mut val = match next_document(&mut ctx, &mut rng, &mut attempts, &mut stats)? {
+                Some(val) => val,
+                None => {
+                    differences.push(format!(
+                        "run {}: generates {} documents, not {}",
+                        i,
+                        j,
+                        files.len()
+                    ));
+                    break;
+                }
+            };
+
+            // Keys are renamed just as the run renamed them, from its seed.
+            if config.key_variants > 0.0 {
+                if let Some(seed) = config.seed {
+                    let mut key_rng = seeded_rng(&[seed, ctx.index, fnv1a("key-variants")]);
+                    if key_rng.gen_bool(config.key_variants) {
+                        keys::rename_one(&schema.schema, &mut val, &mut key_rng);
+                    }
+                }
+            }
+
+            if config.tag_validity {
+                let valid = validator.validate(&schema.schema, &val)?.is_empty();
+                val = json!({ "instance": val, "valid": valid });
+            }
+
+            let stored = match std::fs::read(dir.join(name)) {
+                Ok(bytes) => input::parse_documents(&bytes).ok(),
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                    differences.push(format!("run {}: document {} is missing: {}", i, j, name));
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let generated = corpus::canonical(std::slice::from_ref(&val));
+            if stored.map(|stored| corpus::canonical(&stored)) != Some(generated) {
+                differences.push(format!("run {}: document {} differs: {}", i, j, name));
+            }
+        }
+    }
+
+    Ok((runs.len(), differences))
+}
+
+/// Merges corpus directories into `out`, leaving out duplicate documents, and
+/// those which don't satisfy `schema`, if given. Returns a report on what was
+/// merged.
+pub fn merge(schema: Option<&Schema>, dirs: &[&Path], out: &Path) -> Result<Value, Error> {
+    Ok(merge::merge(schema, dirs, out)?.to_json())
+}
+
+/// How [`mutate`] mutates documents.
+#[derive(Debug, Clone)]
+pub struct MutateOptions {
+    /// How many mutations to apply to each document.
+    pub rounds: usize,
+
+    /// The names of the mutations to apply, or every mutation if empty.
+    pub mutations: Vec<String>,
+
+    /// The parts of documents to mutate more than the rest.
+    pub focus: Vec<Pointer>,
+
+    /// The seed every mutation is derived from, so that the same documents
+    /// are always mutated the same way.
+    pub seed: Option<u64>,
+}
+
+impl Default for MutateOptions {
+    fn default() -> Self {
+        MutateOptions {
+            rounds: 1,
+            mutations: Vec::new(),
+            focus: Vec::new(),
+            seed: None,
+        }
+    }
+}
+
+/// Applies random mutations to documents, in ways that keep them valid, and
+/// writes each out as a line of JSON. Stops early, without failing, if `out`
+/// is a pipe whose reader has gone.
+pub fn mutate<W: Write>(
+    schema: &Schema,
+    docs: Vec<Value>,
+    options: &MutateOptions,
+    out: W,
+) -> Result<(), Error> {
+    let mutations = if options.mutations.is_empty() {
+        mutations::all()
+    } else {
+        options
+            .mutations
+            .iter()
+            .map(|name| mutations::by_name(name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let config = FuzzConfig::default();
+    let mut ctx = Context::new(schema, &config)?;
+    ctx.focus = options.focus.clone();
+    let mut rng = run_rng(options.seed)?;
+    let mut encoder = Encoder::Json(RecordSeparator::Lf);
+    let mut out = io::BufWriter::new(out);
+    for mut doc in docs {
+        for _ in 0..options.rounds {
+            mutations::mutate(
+                schema,
+                &mut doc,
+                &mutations,
+                &options.focus,
+                &mut rng,
+                &mut ctx,
+            );
+        }
+
+        match encoder.write(&mut out, &doc) {
+            Err(ref err) if output::is_broken_pipe(err) => return Ok(()),
+            result => result?,
+        }
+    }
+
+    match encoder.finish(&mut out) {
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Writes a fuzzer dictionary of the strings a schema's instances hold.
+pub fn dict<W: Write>(schema: &Schema, mut out: W) -> Result<(), Error> {
+    Ok(dict::write(&mut out, &dict::extract(schema))?)
+}
+
+/// Reports on which of the features of JDDF a schema uses, and whether
+/// documents generated with the config exercise them.
+///
+/// Returns the report, and whether every feature the schema uses is
+/// exercised.
+pub fn features(schema: &Schema, config: &FuzzConfig) -> (Value, bool) {
+    let features = features::features(schema, config);
+    let exercised = features.values().all(features::Feature::exercised);
+    (features::to_json(&features), exercised)
+}
+
+/// Writes the JSON Pointers of a schema's instances, up to `max_len` tokens
+/// long, one per line: all of them, or `sample` of them at random. Stops
+/// early, without failing, if `out` is a pipe whose reader has gone.
+pub fn pointers<W: Write>(
+    schema: &Schema,
+    max_len: usize,
+    sample: Option<usize>,
+    seed: Option<u64>,
+    out: W,
+) -> Result<(), Error> {
+    let pointers = match sample {
+        Some(n) => {
+            let mut rng = run_rng(seed)?;
+            (0..n)
+                .map(|_| pointer::sample(schema, max_len, &mut rng))
+                .collect()
+        }
+        None => pointer::enumerate(schema, max_len),
+    };
+
+    let mut out = io::BufWriter::new(out);
+    let result = pointers
+        .iter()
+        .try_for_each(|pointer| writeln!(out, "{}", pointer))
+        .and_then(|_| out.flush());
+
+    match result {
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Writes `count` pairs of a document of `input_schema`, and the document of
+/// `output_schema` which `mapping` maps it to, as lines of JSON, or pairs
+/// forever if `count` is zero. Stops early, without failing, if `out` is a
+/// pipe whose reader has gone.
+pub fn transform<W: Write>(
+    input_schema: &Schema,
+    output_schema: &Schema,
+    mapping: &Value,
+    count: usize,
+    seed: Option<u64>,
+    out: W,
+) -> Result<(), Error> {
+    let mapping = transform::Mapping::parse(mapping)?;
+    let mut rng = run_rng(seed)?;
+    let mut encoder = Encoder::Json(RecordSeparator::Lf);
+    let mut out = io::BufWriter::new(out);
+    let mut i = 0;
+    while count == 0 || i < count {
+        let (input, expected) = transform::pair(&mut rng, input_schema, output_schema, &mapping)?;
+        match encoder.write(&mut out, &json!({ "input": input, "expected": expected })) {
+            Err(ref err) if output::is_broken_pipe(err) => return Ok(()),
+            result => result?,
+        }
+
+        i += 1;
+    }
+
+    match encoder.finish(&mut out) {
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Serves a new document in response to each GET request, for as long as the
+/// listener accepts them. A request with `?seed=N` gets the first document a
+/// run with that seed would generate.
+pub fn serve(schema: &Schema, config: &FuzzConfig, listener: TcpListener) -> Result<(), Error> {
+    let mut ctx = run_context(schema, config, &[])?;
+    let mut rng = run_rng(config.seed)?;
+    let mut attempts = Attempts::default();
+    let mut stats = crate::stats::Stats::start();
+
+    http::serve(listener, |method, target| {
+        if method != "GET" {
+            return (405, "text/plain", b"only GET is supported\n".to_vec());
+        }
+
+        // A request with a seed of its own gets the first document of a run
+        // with that seed, from a context of its own, so that it's the same
+        // however many requests came before it. Pools are only read from, so
+        // it borrows those already read.
+        let doc = match http::query_param(target, "seed").as_deref() {
+            Some(seed) => match seed.parse::<u64>() {
+                Ok(seed) => {
+                    let config = FuzzConfig {
+                        seed: Some(seed),
+                        ..config.clone()
+                    };
+                    Context::new(schema, &config).and_then(|mut seeded| {
+                        seeded.pools = mem::take(&mut ctx.pools);
+                        let doc = run_rng(Some(seed)).and_then(|mut rng| {
+                            next_document(
+                                &mut seeded,
+                                &mut rng,
+                                &mut Attempts::default(),
+                                &mut crate::stats::Stats::start(),
+                            )
+                        });
+                        ctx.pools = seeded.pools;
+                        doc
+                    })
+                }
+                Err(_) => {
+                    let body = format!("seed must be a non-negative integer: {}\n", seed);
+                    return (400, "text/plain", body.into_bytes());
+                }
+            },
+            None => next_document(&mut ctx, &mut rng, &mut attempts, &mut stats),
+        };
+
+        match doc {
+            Ok(Some(doc)) => (200, "application/json", doc.to_string().into_bytes()),
+            Ok(None) => (404, "text/plain", b"no more documents\n".to_vec()),
+            Err(err) => (500, "text/plain", format!("{}\n", err).into_bytes()),
+        }
+    })
+}
+
+/// Writes a Rust source file of fixed documents for a schema, as string
+/// constants to `include!` into tests. `input` is where the schema was read
+/// from, for the file's header, and leaf values are derived from `seed`.
+pub fn codegen_fixtures<W: Write>(
+    schema: &Schema,
+    input: &str,
+    seed: u64,
+    mut out: W,
+) -> Result<(), Error> {
+    let header = format!(
+        "Generated by `jddf-fuzz codegen-fixtures --seed {} {}`. Do not edit.",
+        seed, input
+    );
+
+    Ok(codegen::write(
+        &mut out,
+        &header,
+        &codegen::fixtures(schema, seed)?,
+    )?)
+}
@@ -0,0 +1,999 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use failure::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How many seconds there are in a day.
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// How deeply values may be nested, unless the config says otherwise.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Configuration for how values are generated.
+///
+/// Options which apply to only part of a schema are keyed by schema path, a
+/// JSON Pointer into the schema such as `/properties/id` or
+/// `/definitions/user/elements`. These are the same paths `jddf` reports in
+/// validation errors.
+///
+/// A configuration can be read from or written as JSON, or read from TOML. Its
+/// properties are the camel-cased names of these fields, and any which are
+/// missing take their default values.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FuzzConfig {
+    /// Whether to favor values which are valid, but likely to trip up
+    /// consumers of the generated data.
+    pub adversarial: bool,
+
+    /// Whether to generate plausible-looking numbers, strings and timestamps,
+    /// rather than ones from across their types' whole ranges: integers from 0
+    /// to 10,000, floats from 0 to 10,000 with two decimal places, strings of
+    /// 3 to 12 letters and digits unless `string_len` or `charset` say
+    /// otherwise, and timestamps within the year before `now`.
+    pub sane: bool,
+
+    /// The time which `sane` timestamps are within a year before, as a Unix
+    /// timestamp in seconds, or the current time if not given. Giving it keeps
+    /// runs repeatable.
+    pub now: Option<i64>,
+
+    /// String-typed schema paths which should be generated as integers
+    /// serialized as strings, and how many digits those integers should have.
+    pub int_as_string: HashMap<String, DigitRange>,
+
+    /// The probability that a string leaf is an edge case, such as an empty,
+    /// whitespace-only, or very long string. Zero disables edge cases.
+    pub string_edge_cases: f64,
+
+    /// How many characters strings have, unless something else decides, such
+    /// as a per-path option or a format hint. Keys of maps and additional
+    /// properties are strings too. See [`FuzzConfig::string_len`] for the
+    /// default.
+    pub string_len: Option<StringLen>,
+
+    /// Which characters those strings are made of. See
+    /// [`FuzzConfig::charset`] for the default.
+    pub charset: Option<Charset>,
+
+    /// The probability that an integer leaf is written as a float instead,
+    /// such as `1.0`, `-0`, or `1e2`. Some of these floats are deliberately
+    /// not integers, or are out of range. Zero disables such floats.
+    pub int_floats: f64,
+
+    /// The probability that a numeric leaf is an edge case of its type, such
+    /// as zero, one of its bounds, or a subnormal float. These are always in
+    /// range. Zero disables edge cases.
+    pub number_edge_cases: f64,
+
+    /// How floats are distributed, and whether they may be infinite.
+    pub float_policy: FloatPolicy,
+
+    /// The probability that a document has one of its declared property
+    /// names changed in case, padded with whitespace, or given a lookalike
+    /// character. Zero disables such names.
+    pub key_variants: f64,
+
+    /// String-typed schema paths which should be generated as lookalike
+    /// strings, which differ only in script or Unicode normalization form.
+    pub homoglyphs: HashSet<String>,
+
+    /// String-typed schema paths which should be generated as JSON Web
+    /// Tokens, and the key to sign them with using HS256, if any. Tokens
+    /// without a key have a random signature.
+    pub jwt: HashMap<String, Option<String>>,
+
+    /// String-typed schema paths whose values should be drawn from a file,
+    /// and the path to that file. Each line of the file is a value, optionally
+    /// followed by a tab and a weight.
+    pub pool: HashMap<String, String>,
+
+    /// How deeply values may be nested within the generated document. The
+    /// root value is at depth zero. See [`FuzzConfig::max_depth`] for the
+    /// default.
+    pub max_depth: Option<usize>,
+
+    /// What to do when generating a value beyond `max_depth`.
+    pub on_depth_limit: DepthLimitBehavior,
+
+    /// What to do with the parts of a schema jddf-fuzz doesn't support, such
+    /// as format hints it doesn't know.
+    pub on_unsupported: UnsupportedBehavior,
+
+    /// Definitions, by name rather than schema path, and how deeply values of
+    /// each may be nested within one another. Beyond that, references to the
+    /// definition generate its smallest valid value. Definitions not given
+    /// here may be nested four deep, or one deep under the maximal strategy.
+    pub ref_depth: HashMap<String, usize>,
+
+    /// Whether documents should be as small or as large as the rest of the
+    /// config allows, or anywhere in between.
+    pub strategy: Strategy,
+
+    /// Whether arrays and maps, for the elements and values forms, should
+    /// always have at least one element or value, except where truncated at
+    /// `max_depth`.
+    pub never_empty: bool,
+
+    /// How many elements or values arrays and maps have, for the elements and
+    /// values forms, and how many additional properties objects have where
+    /// they're allowed. The maximum grows within focused parts of documents,
+    /// and with `--mean-size`.
+    pub container_size: ContainerSize,
+
+    /// Schema paths of the elements or values form, and the probability that
+    /// arrays or maps there are empty. These take precedence over
+    /// `never_empty`.
+    pub empty_prob: HashMap<String, f64>,
+
+    /// Optional properties, by their schema path such as
+    /// `/optionalProperties/nickname`, which should be absent, `null`, or
+    /// present with a value in other than equal measure.
+    pub optional: HashMap<String, OptionalWeights>,
+
+    /// The probability that an optional property is present, unless it has
+    /// weights in `optional`, or one half if not given.
+    pub optional_prob: Option<f64>,
+
+    /// The probability that an object which allows additional properties is
+    /// given any, or always if not given. How many it is given is as
+    /// `container_size` says.
+    pub additional_prob: Option<f64>,
+
+    /// Schema paths whose values should not be random, and what they should
+    /// be instead.
+    pub freeze: HashMap<String, Freeze>,
+
+    /// JSON Pointers into documents, which may use `*`, and the values which
+    /// are put there once a document is generated, whatever the schema says.
+    pub set: HashMap<String, Value>,
+
+    /// If present, structural choices, such as how many elements an array has
+    /// or which optional properties are present, are derived from this seed
+    /// and the index of the document being generated. Leaf values remain
+    /// random.
+    pub structure_seed: Option<u64>,
+
+    /// If present, every value is derived from this seed, the index of the
+    /// document being generated, and where in the schema and document the
+    /// value is. Values at one path do not depend on what is generated
+    /// elsewhere, so adding to a schema does not change existing values.
+    pub seed: Option<u64>,
+
+    /// The range timestamps are drawn from, if not that of 32-bit Unix
+    /// timestamps, from 1901 to 2038.
+    pub timestamp_range: Option<TimestampRange>,
+
+    /// How many digits of fractional seconds timestamps have.
+    pub timestamp_precision: TimestampPrecision,
+
+    /// Whether timestamps are written at random offsets from UTC, rather than
+    /// always in UTC.
+    pub timestamp_offsets: bool,
+
+    /// Whether to follow the conventions of the proto3 JSON mapping, such as
+    /// writing timestamps in UTC with a `Z` suffix, and with 0, 3, 6, or 9
+    /// digits of fractional seconds.
+    pub proto_json: bool,
+
+    /// Whether to check that every generated document satisfies the schema,
+    /// and that its `float32` values read back as the same `f32`, before
+    /// writing it out.
+    pub verify: bool,
+
+    /// Whether to write each document as `{ "instance": ..., "valid": ... }`,
+    /// where `valid` is whether the document satisfies the schema.
+    pub tag_validity: bool,
+
+    /// Whether to leave out documents with the same shape as one already
+    /// generated. Documents have the same shape if they differ only in their
+    /// leaf values, and in how many elements or values their arrays and maps
+    /// have of each shape.
+    pub novel_only: bool,
+
+    /// Whether enums and discriminators should take each of their values in
+    /// turn, in sorted order, before taking them at random.
+    pub enum_round_robin: bool,
+
+    /// Whether to take every choice the schema offers in turn, before making
+    /// it at random: each enum value and discriminator variant, as under
+    /// `enum_round_robin`, each optional property both present and absent,
+    /// and each array and map both empty and not, where the rest of the
+    /// config allows.
+    pub coverage: bool,
+
+    /// How strongly discriminators nested within others favor their variants
+    /// with the smallest documents. Each level of nesting favors them more,
+    /// but never rules out other variants entirely. Zero chooses variants
+    /// uniformly at every level.
+    pub branch_decay: f64,
+
+    /// Business rules which every document should follow, such as that refunds
+    /// have a negative amount.
+    pub rules: Vec<Rule>,
+
+    /// Whether to break each document in one place, so that it does not
+    /// satisfy the schema.
+    pub invalid: bool,
+
+    /// The ways documents may be broken under `invalid`. If empty, they may be
+    /// broken in any way.
+    pub invalid_kinds: Vec<InvalidKind>,
+}
+
+impl FuzzConfig {
+    /// Reads a config from a file, and checks it as [`validate`] does. Files
+    /// ending in `.toml` are TOML, with the same properties as JSON, and any
+    /// others are JSON.
+    ///
+    /// [`validate`]: FuzzConfig::validate
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let config: FuzzConfig = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&fs::read_to_string(path)?)?
+        } else {
+            serde_json::from_reader(io::BufReader::new(File::open(path)?))?
+        };
+        config
+            .validate()
+            .map_err(|err| format_err!("in {}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    /// Pins the time `sane` timestamps are relative to, if they are generated
+    /// and the config doesn't give it, so that the config repeats the run.
+    pub fn pin_now(&mut self) {
+        if self.sane && self.now.is_none() {
+            self.now = Some(Utc::now().timestamp());
+        }
+    }
+
+    /// Checks the options which the command line checks as it parses them,
+    /// for configs read from JSON instead: that probabilities are between 0
+    /// and 1, that ranges don't end before they start, that timestamps can be
+    /// written at all, and that options which contradict each other aren't
+    /// both given.
+    pub fn validate(&self) -> Result<(), Error> {
+        let probs = vec![
+            ("stringEdgeCases", self.string_edge_cases),
+            ("intFloats", self.int_floats),
+            ("numberEdgeCases", self.number_edge_cases),
+            ("keyVariants", self.key_variants),
+        ];
+        let probs = probs
+            .into_iter()
+            .chain(self.optional_prob.map(|prob| ("optionalProb", prob)))
+            .chain(self.additional_prob.map(|prob| ("additionalProb", prob)));
+        for (name, prob) in probs {
+            if !(0.0..=1.0).contains(&prob) {
+                return Err(format_err!("{} must be between 0 and 1: {}", name, prob));
+            }
+        }
+
+        for (path, prob) in &self.empty_prob {
+            if !(0.0..=1.0).contains(prob) {
+                return Err(format_err!(
+                    "emptyProb must be between 0 and 1: {} at schema path: {}",
+                    prob,
+                    path
+                ));
+            }
+        }
+
+        for (path, weights) in &self.optional {
+            let weights = [weights.absent, weights.null, weights.present];
+            if !weights.iter().all(|w| *w >= 0.0 && w.is_finite())
+                || !weights.iter().any(|w| *w > 0.0)
+            {
+                return Err(format_err!(
+                    "optional weights must be non-negative numbers, not all zero, at schema path: {}",
+                    path
+                ));
+            }
+        }
+
+        for (path, digits) in &self.int_as_string {
+            if digits.min == 0 || digits.min > digits.max {
+                return Err(format_err!(
+                    "invalid digit range: {}..{} at schema path: {}",
+                    digits.min,
+                    digits.max,
+                    path
+                ));
+            }
+        }
+
+        let string_len = self.string_len();
+        if string_len.min > string_len.max {
+            return Err(format_err!(
+                "invalid string length range: {}..{}",
+                string_len.min,
+                string_len.max
+            ));
+        }
+
+        if self.container_size.min > self.container_size.max {
+            return Err(format_err!(
+                "invalid container size range: {}..{}",
+                self.container_size.min,
+                self.container_size.max
+            ));
+        }
+
+        if self.distinct_keys() < self.container_size.min {
+            return Err(format_err!(
+                "strings of length {}..{} make only {} distinct keys, but containers must have at least {}",
+                string_len.min,
+                string_len.max,
+                self.distinct_keys(),
+                self.container_size.min
+            ));
+        }
+
+        let TimestampPrecision { min, max } = self.timestamp_precision;
+        if min > max || max > 9 {
+            return Err(format_err!("invalid timestamp precision: {}..{}", min, max));
+        }
+
+        // Timestamps are written at offsets from UTC of up to a day, so a day
+        // either side of them must be within the years chrono can write too.
+        let writable = |secs: i64| match (secs.checked_sub(DAY_SECS), secs.checked_add(DAY_SECS)) {
+            (Some(before), Some(after)) => {
+                NaiveDateTime::from_timestamp_opt(before, 0).is_some()
+                    && NaiveDateTime::from_timestamp_opt(after, 0).is_some()
+            }
+            _ => false,
+        };
+
+        if let Some(range) = self.timestamp_range {
+            if range.min > range.max || !writable(range.min) || !writable(range.max) {
+                return Err(format_err!(
+                    "invalid timestamp range: {}..{}",
+                    range.min,
+                    range.max
+                ));
+            }
+        }
+
+        // Sane timestamps go back as far as a year before now.
+        if let Some(now) = self.now {
+            if !writable(now) || !now.checked_sub(366 * DAY_SECS).is_some_and(writable) {
+                return Err(format_err!(
+                    "now is not a timestamp which can be written: {}",
+                    now
+                ));
+            }
+        }
+
+        if self.int_floats > 0.0 && self.verify {
+            return Err(format_err!(
+                "intFloats writes some integers as invalid floats on purpose, so it can't be combined with verify"
+            ));
+        }
+
+        if self.branch_decay < 0.0 || !self.branch_decay.is_finite() {
+            return Err(format_err!(
+                "branch decay must be a non-negative number: {}",
+                self.branch_decay
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// How deeply values may be nested: `max_depth` if given, and otherwise
+    /// 128, so that generation never recurses without end.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// How many characters strings have: `string_len` if given, and otherwise
+    /// 3 to 12 under `sane`, or 0 to 7.
+    pub fn string_len(&self) -> StringLen {
+        match self.string_len {
+            Some(string_len) => string_len,
+            None if self.sane => StringLen { min: 3, max: 12 },
+            None => StringLen::default(),
+        }
+    }
+
+    /// Which characters strings are made of: `charset` if given, and otherwise
+    /// alphanumeric under `sane`, or printable.
+    pub fn charset(&self) -> Charset {
+        match self.charset {
+            Some(charset) => charset,
+            None if self.sane => Charset::Alphanumeric,
+            None => Charset::default(),
+        }
+    }
+
+    /// How many distinct keys maps and additional properties can be given, as
+    /// strings of the lengths strings have, spelled out of the charset's
+    /// [`Charset::key_chars`], or `usize::MAX` if that many or more.
+    pub fn distinct_keys(&self) -> usize {
+        let chars = self.charset().key_chars().len();
+        let StringLen { min, max } = self.string_len();
+        let mut keys: usize = 0;
+        for len in min..=max {
+            let of_len = u32::try_from(len)
+                .ok()
+                .and_then(|len| chars.checked_pow(len))
+                .unwrap_or(usize::MAX);
+            keys = keys.saturating_add(of_len);
+            if keys == usize::MAX {
+                break;
+            }
+        }
+
+        keys
+    }
+
+    /// The config as JSON, with every option given, and object members and
+    /// sets sorted, so that the same config is always written the same way.
+    pub fn to_canonical_json(&self) -> Value {
+        let mut json = serde_json::to_value(self).unwrap();
+        if let Value::Array(ref mut homoglyphs) = json["homoglyphs"] {
+            homoglyphs.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        }
+
+        json
+    }
+}
+
+/// A way to generate a value without any randomness.
+///
+/// As JSON, this is `"minimal"`, `"sequence"`, or `{ "value": ... }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Freeze {
+    /// Always generate the smallest valid value, such as zero, an empty
+    /// string, or the Unix epoch.
+    Minimal,
+
+    /// Generate successive values, such as `0`, `1`, `2`, and so on. Each
+    /// schema path counts independently.
+    Sequence,
+
+    /// Always generate the same, given value.
+    Value(Value),
+}
+
+impl FromStr for Freeze {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "min" => Ok(Freeze::Minimal),
+            "seq" => Ok(Freeze::Sequence),
+            _ => serde_json::from_str(s)
+                .map(Freeze::Value)
+                .map_err(|_| format_err!("freeze must be min, seq, or a JSON value: {}", s)),
+        }
+    }
+}
+
+/// How often an optional property is absent, present as `null`, or present
+/// with any other value, as relative weights.
+///
+/// Only properties whose schema accepts `null` can be present as `null`. For
+/// other properties, the `null` weight is ignored.
+///
+/// As JSON, this is `{ "absent": 1, "null": 1, "present": 2 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OptionalWeights {
+    pub absent: f64,
+    pub null: f64,
+    pub present: f64,
+}
+
+impl Default for OptionalWeights {
+    fn default() -> Self {
+        // The same odds as for any other optional property.
+        OptionalWeights {
+            absent: 1.0,
+            null: 0.0,
+            present: 1.0,
+        }
+    }
+}
+
+impl OptionalWeights {
+    /// Parses comma-separated weights, in the order `ABSENT,NULL,PRESENT`.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let weights = s
+            .split(',')
+            .map(|w| w.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match weights[..] {
+            [absent, null, present]
+                if weights.iter().all(|w| *w >= 0.0) && weights.iter().any(|w| *w > 0.0) =>
+            {
+                Ok(OptionalWeights {
+                    absent,
+                    null,
+                    present,
+                })
+            }
+            _ => Err(format_err!(
+                "weights must be three non-negative numbers, not all zero: {}",
+                s
+            )),
+        }
+    }
+}
+
+/// A rule that, if one condition holds of a document, another must too.
+///
+/// As JSON, this is like:
+///
+/// ```json
+/// {
+///   "if": { "path": "/type", "op": "==", "value": "refund" },
+///   "then": { "path": "/amount", "op": "<", "value": 0 }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(rename = "if")]
+    pub when: Condition,
+    pub then: Condition,
+}
+
+/// A comparison between the value at a JSON Pointer into a document, and a
+/// given value. It does not hold if there is no value at the pointer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    pub path: String,
+    pub op: Comparison,
+    pub value: Value,
+}
+
+/// How a condition compares values.
+///
+/// Numbers are compared by their numeric value, and strings in lexicographic
+/// order. Values of any other types, or of different types, are only equal or
+/// not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+}
+
+/// What to do when generating a value beyond the maximum depth.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DepthLimitBehavior {
+    /// Generate the smallest valid value instead.
+    #[default]
+    Truncate,
+
+    /// Generate `null` where the schema accepts it, and otherwise behave like
+    /// `Truncate`.
+    Null,
+
+    /// Discard the document, and generate another one in its place.
+    Skip,
+
+    /// Abort generation with an error.
+    Error,
+}
+
+impl FromStr for DepthLimitBehavior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "truncate" => Ok(DepthLimitBehavior::Truncate),
+            "null" => Ok(DepthLimitBehavior::Null),
+            "skip" => Ok(DepthLimitBehavior::Skip),
+            "error" => Ok(DepthLimitBehavior::Error),
+            _ => Err(format_err!("unknown depth limit behavior: {}", s)),
+        }
+    }
+}
+
+/// How large documents are, within the bounds the rest of the config sets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Strategy {
+    /// Choose sizes at random.
+    #[default]
+    Random,
+
+    /// Leave out optional and additional properties, and make arrays, maps
+    /// and strings as short as allowed.
+    Minimal,
+
+    /// Include every optional property, as many additional properties as
+    /// allowed, and make arrays, maps and strings as long as allowed.
+    /// Definitions nested within themselves are as small as possible, unless
+    /// they have a `ref_depth`.
+    Maximal,
+}
+
+impl FromStr for Strategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "random" => Ok(Strategy::Random),
+            "minimal" => Ok(Strategy::Minimal),
+            "maximal" => Ok(Strategy::Maximal),
+            _ => Err(format_err!("unknown strategy: {}", s)),
+        }
+    }
+}
+
+/// What to do with the parts of a schema jddf-fuzz doesn't support.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnsupportedBehavior {
+    /// Reject the schema before generating anything.
+    #[default]
+    Error,
+
+    /// Generate values as though the unsupported parts weren't there.
+    Skip,
+
+    /// Generate `null` in place of the unsupported parts, or skip them if they
+    /// are objects, arrays, or discriminators.
+    Null,
+
+    /// Generate a placeholder string in place of the unsupported parts, or skip
+    /// them if they are objects, arrays, or discriminators.
+    Placeholder,
+}
+
+impl FromStr for UnsupportedBehavior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "error" => Ok(UnsupportedBehavior::Error),
+            "skip" => Ok(UnsupportedBehavior::Skip),
+            "null" => Ok(UnsupportedBehavior::Null),
+            "placeholder" => Ok(UnsupportedBehavior::Placeholder),
+            _ => Err(format_err!("unknown unsupported behavior: {}", s)),
+        }
+    }
+}
+
+/// Which characters random strings are made of.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Charset {
+    /// Any ASCII character, including control characters, which JSON escapes.
+    Ascii,
+
+    /// ASCII letters and digits.
+    Alphanumeric,
+
+    /// ASCII characters other than control characters.
+    #[default]
+    Printable,
+
+    /// Mostly characters beyond ASCII, which take two, three, or four bytes in
+    /// UTF-8, including emoji, combining characters, and the characters on
+    /// either side of the surrogates.
+    Unicode,
+}
+
+impl Charset {
+    /// The characters of the charset which keys can be spelled out of, in
+    /// order, once random keys keep clashing with those already taken. The
+    /// unicode charset gives the printable ASCII characters it includes.
+    pub fn key_chars(self) -> Vec<char> {
+        match self {
+            Charset::Ascii => (0u8..128).map(char::from).collect(),
+            Charset::Alphanumeric => ('A'..='Z').chain('a'..='z').chain('0'..='9').collect(),
+            Charset::Printable | Charset::Unicode => (32u8..127).map(char::from).collect(),
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "ascii" => Ok(Charset::Ascii),
+            "alphanumeric" => Ok(Charset::Alphanumeric),
+            "printable" => Ok(Charset::Printable),
+            "unicode" => Ok(Charset::Unicode),
+            _ => Err(format_err!("unknown charset: {}", s)),
+        }
+    }
+}
+
+/// How floats are distributed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FloatPolicy {
+    /// Any finite float of the type, across its whole range of magnitudes and
+    /// of either sign.
+    #[default]
+    Full,
+
+    /// Floats from zero up to, but not including, one.
+    Unit,
+
+    /// Like `Full`, but sometimes positive or negative infinity, written as
+    /// `1e999` or `-1e999`, since JSON has no way to write infinity. NaN has
+    /// no way to be written at all, so it is never generated, except under
+    /// `proto_json`, which writes all three as the strings `"Infinity"`,
+    /// `"-Infinity"`, and `"NaN"`.
+    Special,
+}
+
+impl FromStr for FloatPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "full" => Ok(FloatPolicy::Full),
+            "unit" => Ok(FloatPolicy::Unit),
+            "special" => Ok(FloatPolicy::Special),
+            _ => Err(format_err!("unknown float policy: {}", s)),
+        }
+    }
+}
+
+/// A way to break a document, so that it does not satisfy its schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvalidKind {
+    /// A value of the wrong JSON type, such as a string where an array goes.
+    WrongType,
+
+    /// A required property, or a discriminator's tag, removed.
+    MissingRequired,
+
+    /// A property added to an object which does not allow additional ones.
+    ExtraProperty,
+
+    /// A timestamp which is not in RFC 3339 format.
+    BadTimestamp,
+
+    /// An integer just outside the range of its type.
+    OutOfRange,
+
+    /// A string which is not one of its enum's values.
+    BadEnum,
+
+    /// A discriminator tag which is not one of the mapping's values.
+    BadDiscriminator,
+}
+
+impl InvalidKind {
+    /// Every kind, in the order they are listed in `--help`.
+    pub fn all() -> Vec<Self> {
+        vec![
+            InvalidKind::WrongType,
+            InvalidKind::MissingRequired,
+            InvalidKind::ExtraProperty,
+            InvalidKind::BadTimestamp,
+            InvalidKind::OutOfRange,
+            InvalidKind::BadEnum,
+            InvalidKind::BadDiscriminator,
+        ]
+    }
+
+    /// The name of the kind, as given to `--invalid-kind`.
+    pub fn name(self) -> &'static str {
+        match self {
+            InvalidKind::WrongType => "wrong-type",
+            InvalidKind::MissingRequired => "missing-required",
+            InvalidKind::ExtraProperty => "extra-property",
+            InvalidKind::BadTimestamp => "bad-timestamp",
+            InvalidKind::OutOfRange => "out-of-range",
+            InvalidKind::BadEnum => "bad-enum",
+            InvalidKind::BadDiscriminator => "bad-discriminator",
+        }
+    }
+}
+
+impl FromStr for InvalidKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::all()
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format_err!("unknown kind of invalid document: {}", s))
+    }
+}
+
+/// An inclusive range of digit counts for integers serialized as strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigitRange {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for DigitRange {
+    fn default() -> Self {
+        // Enough digits to hold any 64-bit integer, which is what such strings
+        // usually exist to represent.
+        DigitRange { min: 1, max: 19 }
+    }
+}
+
+impl DigitRange {
+    /// Parses either a single digit count (`10`) or an inclusive range of
+    /// digit counts (`1..19`), as [`parse_range`] does. A bound left out is
+    /// the default's.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (min, max) = parse_range(s)?;
+        let default = Self::default();
+        let (min, max) = (min.unwrap_or(default.min), max.unwrap_or(default.max));
+
+        if min == 0 || min > max {
+            return Err(format_err!("invalid digit range: {}", s));
+        }
+
+        Ok(DigitRange { min, max })
+    }
+}
+
+/// An inclusive range of how many elements, values, or additional properties
+/// containers have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerSize {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for ContainerSize {
+    fn default() -> Self {
+        ContainerSize { min: 0, max: 7 }
+    }
+}
+
+impl ContainerSize {
+    /// Parses either a single size (`4`) or an inclusive range of sizes
+    /// (`0..100`), as [`parse_range`] does. A bound left out is the
+    /// default's.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (min, max) = parse_range(s)?;
+        let default = Self::default();
+        let (min, max) = (min.unwrap_or(default.min), max.unwrap_or(default.max));
+
+        if min > max {
+            return Err(format_err!("invalid container size range: {}", s));
+        }
+
+        Ok(ContainerSize { min, max })
+    }
+}
+
+/// An inclusive range of how many characters random strings have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StringLen {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for StringLen {
+    fn default() -> Self {
+        StringLen { min: 0, max: 7 }
+    }
+}
+
+impl StringLen {
+    /// Parses either a single length (`8`) or an inclusive range of lengths
+    /// (`0..256`), as [`parse_range`] does. A bound left out is the
+    /// default's.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (min, max) = parse_range(s)?;
+        let default = Self::default();
+        let (min, max) = (min.unwrap_or(default.min), max.unwrap_or(default.max));
+
+        if min > max {
+            return Err(format_err!("invalid string length range: {}", s));
+        }
+
+        Ok(StringLen { min, max })
+    }
+}
+
+/// An inclusive range of timestamps, as Unix timestamps in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampRange {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl TimestampRange {
+    /// Parses an inclusive range of RFC 3339 timestamps, like
+    /// `2020-01-01T00:00:00Z..2021-01-01T00:00:00Z`, as [`parse_range`] does.
+    /// A bound left out is that of 32-bit Unix timestamps.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (min, max) = parse_range::<DateTime<FixedOffset>>(s)?;
+        let min = min.map_or(i64::from(i32::MIN), |min| min.timestamp());
+        let max = max.map_or(i64::from(i32::MAX), |max| max.timestamp());
+
+        if min > max {
+            return Err(format_err!("invalid timestamp range: {}", s));
+        }
+
+        Ok(TimestampRange { min, max })
+    }
+}
+
+/// An inclusive range of how many digits of fractional seconds timestamps
+/// have, at most nine, for nanoseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampPrecision {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl TimestampPrecision {
+    /// Parses either a single number of digits (`3`) or an inclusive range of
+    /// them (`0..9`), as [`parse_range`] does. A bound left out is zero or
+    /// nine.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let (min, max) = parse_range(s)?;
+        let (min, max) = (min.unwrap_or(0), max.unwrap_or(9));
+
+        if min > max || max > 9 {
+            return Err(format_err!("invalid timestamp precision: {}", s));
+        }
+
+        Ok(TimestampPrecision { min, max })
+    }
+}
+
+/// Parses either a single value (`4`), which is both bounds of the range, or
+/// an inclusive range (`0..100`). Either bound of a range may be left out, as
+/// in `..100` or `4..`, for the caller to fill in.
+pub fn parse_range<T: FromStr>(s: &str) -> Result<(Option<T>, Option<T>), Error>
+where
+    T::Err: fmt::Display,
+{
+    let bound = |bound: &str| -> Result<Option<T>, Error> {
+        if bound.is_empty() {
+            return Ok(None);
+        }
+
+        bound
+            .parse()
+            .map(Some)
+            .map_err(|err| format_err!("invalid bound {:?} of range {}: {}", bound, s, err))
+    };
+
+    match s.find("..") {
+        Some(i) => Ok((bound(&s[..i])?, bound(&s[i + 2..])?)),
+        None if s.is_empty() => Err(format_err!("range must be N or MIN..MAX, not empty")),
+        None => Ok((bound(s)?, bound(s)?)),
+    }
+}
+
+/// Splits a `PATH=VALUE` command-line option into its path and value.
+///
+/// The value is optional, and everything after the first `=` is considered
+/// part of the value.
+pub fn split_path_option(s: &str) -> (&str, Option<&str>) {
+    match s.find('=') {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    }
+}
@@ -0,0 +1,131 @@
+//! Corpora laid out by content: each file is named after a hash of its
+//! documents, so the same documents are never stored twice.
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// The name of the file, within a corpus, listing its files in the order they
+/// were added.
+pub const INDEX: &str = "index.jsonl";
+
+/// The name of the file, within a corpus, recording how to generate its
+/// documents again, for each run which can be repeated exactly.
+pub const RUNS: &str = "runs.jsonl";
+
+/// Whether a file within a corpus describes the corpus, rather than holding
+/// documents.
+pub fn is_metadata(name: &OsStr) -> bool {
+    name == INDEX || name == RUNS
+}
+
+/// The canonical form of the documents in a file.
+///
+/// Object members are sorted, and insignificant whitespace removed, so files
+/// which differ only in those respects have the same canonical form.
+pub fn canonical(instances: &[Value]) -> String {
+    instances
+        .iter()
+        .map(|instance| instance.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The name of the file holding documents with the given canonical form.
+pub fn file_name(canonical: &str) -> String {
+    format!("{:016x}.json", crate::fnv1a(canonical))
+}
+
+/// A corpus directory which documents are added to, one per file.
+#[derive(Debug)]
+pub struct Corpus {
+    dir: PathBuf,
+    index: File,
+
+    /// How many documents have been added.
+    pub added: u64,
+
+    /// How many documents were not added, because the corpus already had
+    /// them.
+    pub duplicates: u64,
+
+    /// The names of the files of every document, whether added or not, in the
+    /// order they were generated.
+    pub files: Vec<String>,
+}
+
+impl Corpus {
+    /// Opens a corpus directory, creating it if need be. Documents already in
+    /// it are kept.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(INDEX))?;
+
+        Ok(Corpus {
+            dir: dir.to_owned(),
+            index,
+            added: 0,
+            duplicates: 0,
+            files: Vec::new(),
+        })
+    }
+
+    /// Adds a document, unless the corpus already has it. Returns whether it
+    /// was added.
+    pub fn add(&mut self, instance: &Value) -> io::Result<bool> {
+        let canonical = canonical(std::slice::from_ref(instance));
+        let name = file_name(&canonical);
+        let path = self.dir.join(&name);
+        self.files.push(name.clone());
+        if path.exists() {
+            self.duplicates += 1;
+            return Ok(false);
+        }
+
+        fs::write(&path, format!("{}\n", canonical))?;
+        writeln!(
+            self.index,
+            "{}",
+            json!({
+                "file": name,
+                "added": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            })
+        )?;
+
+        self.added += 1;
+        Ok(true)
+    }
+
+    /// Records how to generate the documents of this run again, along with
+    /// the files they were in.
+    pub fn record_run(&mut self, mut run: Value) -> io::Result<()> {
+        run["files"] = self.files.clone().into();
+        let mut runs = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(RUNS))?;
+        writeln!(runs, "{}", run)
+    }
+
+    /// A report of what was added, as JSON.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "added": self.added,
+            "duplicates": self.duplicates,
+        })
+    }
+}
+
+/// Reads the runs recorded in a corpus, as written by `Corpus::record_run`.
+pub fn read_runs(dir: &Path) -> io::Result<Vec<Value>> {
+    let file = io::BufReader::new(File::open(dir.join(RUNS))?);
+    file.lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
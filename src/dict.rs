@@ -0,0 +1,66 @@
+//! AFL-style dictionaries of the literal strings a schema's instances contain.
+//!
+//! Byte-level fuzzers splice dictionary entries into their inputs. Since those
+//! inputs are JSON text, each entry is a JSON-encoded string, quotes included.
+
+use jddf::schema::Form;
+use jddf::Schema;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+/// Collects the property names, enum values, and discriminator tags and
+/// values used anywhere in a schema, including its definitions.
+pub fn extract(schema: &Schema) -> BTreeSet<String> {
+    let mut out = BTreeSet::new();
+
+    extract_into(&mut out, schema);
+    for sub_schema in schema.definitions().iter().flat_map(|defs| defs.values()) {
+        extract_into(&mut out, sub_schema);
+    }
+
+    out
+}
+
+fn extract_into(out: &mut BTreeSet<String>, schema: &Schema) {
+    match schema.form() {
+        Form::Enum(ref vals) => out.extend(vals.iter().cloned()),
+        Form::Elements(ref sub_schema) | Form::Values(ref sub_schema) => {
+            extract_into(out, sub_schema)
+        }
+        Form::Properties {
+            required, optional, ..
+        } => {
+            for (k, v) in required.iter().chain(optional) {
+                out.insert(k.clone());
+                extract_into(out, v);
+            }
+        }
+        Form::Discriminator(ref tag, ref mapping) => {
+            out.insert(tag.clone());
+            for (k, v) in mapping {
+                out.insert(k.clone());
+                extract_into(out, v);
+            }
+        }
+        Form::Empty | Form::Type(_) | Form::Ref(_) => {}
+    }
+}
+
+/// Writes strings as an AFL dictionary, one entry per line.
+pub fn write<W: Write>(out: &mut W, strings: &BTreeSet<String>) -> io::Result<()> {
+    for s in strings {
+        let token = serde_json::to_string(s)?;
+
+        write!(out, "\"")?;
+        for b in token.bytes() {
+            match b {
+                b'"' | b'\\' => write!(out, "\\{}", b as char)?,
+                0x20..=0x7e => write!(out, "{}", b as char)?,
+                _ => write!(out, "\\x{:02x}", b)?,
+            }
+        }
+        writeln!(out, "\"")?;
+    }
+
+    Ok(())
+}
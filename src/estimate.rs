@@ -0,0 +1,70 @@
+//! Predicting how big a run will be, and how long it will take, from a sample
+//! of its documents.
+
+use serde_json::{json, Value};
+use std::time::Instant;
+
+/// The sizes of a sample of documents, and how long they took to generate.
+#[derive(Debug)]
+pub struct Sample {
+    started: Instant,
+
+    /// The size of each document, in bytes, as written out.
+    sizes: Vec<u64>,
+}
+
+impl Sample {
+    /// Starts sampling, from now.
+    pub fn start() -> Self {
+        Sample {
+            started: Instant::now(),
+            sizes: Vec::new(),
+        }
+    }
+
+    /// Records a document written out in the given number of bytes.
+    pub fn record(&mut self, bytes: u64) {
+        self.sizes.push(bytes);
+    }
+
+    /// Predicts the size and duration of a run of `n` documents, as JSON,
+    /// given how many bytes the whole sample took up.
+    ///
+    /// The total can differ from the sum of the documents' sizes, for encodings
+    /// which write headers or buffer documents into blocks. The predicted time
+    /// only counts generating and encoding documents, and not waiting for
+    /// whatever reads them to keep up.
+    pub fn predict(&self, n: usize, total_bytes: u64) -> Value {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let per_document = |total: f64| {
+            if self.sizes.is_empty() {
+                0.0
+            } else {
+                total / self.sizes.len() as f64
+            }
+        };
+
+        let mut sizes = self.sizes.clone();
+        sizes.sort();
+        let percentile = |p: f64| {
+            let i = (sizes.len().saturating_sub(1) as f64 * p).round() as usize;
+            sizes.get(i).copied().unwrap_or(0)
+        };
+
+        json!({
+            "samples": sizes.len(),
+            "sampleSecs": elapsed,
+            "documentBytes": {
+                "min": percentile(0.0),
+                "p50": percentile(0.5),
+                "p90": percentile(0.9),
+                "p99": percentile(0.99),
+                "max": percentile(1.0),
+                "mean": per_document(total_bytes as f64),
+            },
+            "documents": n,
+            "estimatedBytes": (per_document(total_bytes as f64) * n as f64).round(),
+            "estimatedSecs": per_document(elapsed) * n as f64,
+        })
+    }
+}
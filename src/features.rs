@@ -0,0 +1,279 @@
+//! Reporting which parts of JDDF a schema uses, and whether a configuration
+//! lets each of them be exercised, so that CI can fail when a schema starts
+//! using something the fuzzing setup never generates.
+
+use crate::config::FuzzConfig;
+use crate::pointer::Pointer;
+use crate::reach;
+use jddf::schema::{Form, Type};
+use jddf::Schema;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Keywords jddf keeps, but which don't affect what satisfies a schema, so
+/// that ignoring them doesn't leave anything unexercised.
+const ANNOTATIONS: &[&str] = &["metadata"];
+
+/// The per-path options which can apply to strings.
+const STRING_OPTIONS: &[&str] = &[
+    "--freeze",
+    "--homoglyphs",
+    "--int-as-string",
+    "--jwt",
+    "--pool",
+];
+
+/// A part of JDDF, and where a schema uses it.
+#[derive(Debug)]
+pub struct Feature {
+    /// Whether documents are generated according to it. Keywords jddf-fuzz
+    /// doesn't know are kept by jddf, but ignored.
+    pub supported: bool,
+
+    /// The per-path options which can apply to it.
+    pub options: &'static [&'static str],
+
+    /// The schema paths using it, and why the configuration keeps each from
+    /// ever being generated, if it does.
+    pub paths: BTreeMap<String, Option<String>>,
+
+    /// The schema paths using it which each of its options is set for.
+    pub configured: BTreeMap<&'static str, Vec<String>>,
+}
+
+impl Feature {
+    /// Whether any of the schema paths using it are generated.
+    pub fn exercised(&self) -> bool {
+        self.supported && self.paths.values().any(Option::is_none)
+    }
+}
+
+/// Finds the features a schema uses, by name, such as `elements`,
+/// `type:timestamp`, or `keyword:nullable` for keywords jddf-fuzz ignores.
+pub fn features(schema: &Schema, config: &FuzzConfig) -> BTreeMap<String, Feature> {
+    let mut walk = Walk {
+        config,
+        generated: reach::paths(schema, config),
+        features: BTreeMap::new(),
+    };
+
+    walk.walk(schema, &mut Vec::new());
+    if let Some(defs) = schema.definitions() {
+        for def in crate::sorted_keys(defs) {
+            let mut path = vec!["definitions".to_owned(), def.clone()];
+            walk.walk(&defs[def], &mut path);
+        }
+    }
+
+    walk.features
+}
+
+/// Describes features as JSON, listing by name those which are unsupported,
+/// or never exercised.
+pub fn to_json(features: &BTreeMap<String, Feature>) -> Value {
+    let names = |f: &dyn Fn(&Feature) -> bool| -> Vec<&String> {
+        features
+            .iter()
+            .filter(|(_, feature)| f(feature))
+            .map(|(name, _)| name)
+            .collect()
+    };
+
+    json!({
+        "features": features
+            .iter()
+            .map(|(name, feature)| json!({
+                "feature": name,
+                "supported": feature.supported,
+                "exercised": feature.exercised(),
+                "options": feature.options,
+                "configured": feature.configured,
+                "paths": feature
+                    .paths
+                    .iter()
+                    .map(|(path, reason)| json!({ "schemaPath": path, "neverGenerated": reason }))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>(),
+        "unsupported": names(&|feature| !feature.supported),
+        "unexercised": names(&|feature| feature.supported && !feature.exercised()),
+    })
+}
+
+struct Walk<'a> {
+    config: &'a FuzzConfig,
+    generated: HashMap<String, Option<String>>,
+    features: BTreeMap<String, Feature>,
+}
+
+impl<'a> Walk<'a> {
+    fn walk(&mut self, schema: &Schema, path: &mut Vec<String>) {
+        let pointer = Pointer(path.clone()).to_string();
+
+        let mut extra: Vec<_> = schema
+            .extra()
+            .keys()
+            .filter(|k| !ANNOTATIONS.contains(&k.as_str()))
+            .collect();
+        extra.sort();
+        for k in extra {
+            self.add(&format!("keyword:{}", k), false, &[], &pointer);
+        }
+
+        match schema.form() {
+            Form::Empty => self.add("empty", true, &["--freeze"], &pointer),
+            Form::Ref(def) => {
+                self.add("ref", true, &["--freeze", "--ref-depth"], &pointer);
+                if self.config.ref_depth.contains_key(def) {
+                    self.configure("ref", "--ref-depth", &pointer);
+                }
+            }
+            Form::Type(typ) => {
+                let name = format!(
+                    "type:{}",
+                    match typ {
+                        Type::Boolean => "boolean",
+                        Type::Float32 => "float32",
+                        Type::Float64 => "float64",
+                        Type::Int8 => "int8",
+                        Type::Uint8 => "uint8",
+                        Type::Int16 => "int16",
+                        Type::Uint16 => "uint16",
+                        Type::Int32 => "int32",
+                        Type::Uint32 => "uint32",
+                        Type::String => "string",
+                        Type::Timestamp => "timestamp",
+                    }
+                );
+
+                if *typ != Type::String {
+                    self.add(&name, true, &["--freeze"], &pointer);
+                    return;
+                }
+
+                self.add(&name, true, STRING_OPTIONS, &pointer);
+                if self.config.homoglyphs.contains(&pointer) {
+                    self.configure(&name, "--homoglyphs", &pointer);
+                }
+                if self.config.int_as_string.contains_key(&pointer) {
+                    self.configure(&name, "--int-as-string", &pointer);
+                }
+                if self.config.jwt.contains_key(&pointer) {
+                    self.configure(&name, "--jwt", &pointer);
+                }
+                if self.config.pool.contains_key(&pointer) {
+                    self.configure(&name, "--pool", &pointer);
+                }
+            }
+            Form::Enum(_) => self.add("enum", true, &["--freeze"], &pointer),
+            Form::Elements(sub_schema) | Form::Values(sub_schema) => {
+                let token = match schema.form() {
+                    Form::Elements(_) => "elements",
+                    _ => "values",
+                };
+
+                self.add(token, true, &["--empty-prob", "--freeze"], &pointer);
+                if self.config.empty_prob.contains_key(&pointer) {
+                    self.configure(token, "--empty-prob", &pointer);
+                }
+
+                path.push(token.to_owned());
+                self.walk(sub_schema, path);
+                path.pop();
+            }
+            Form::Properties {
+                required,
+                optional,
+                has_required,
+                allow_additional,
+            } => {
+                if *has_required {
+                    self.add("properties", true, &["--freeze"], &pointer);
+                }
+                if *allow_additional {
+                    self.add("additionalProperties", true, &["--freeze"], &pointer);
+
+                    // A reason they're never generated, such as being frozen,
+                    // is kept if there already is one.
+                    if self.config.additional_prob == Some(0.0) {
+                        self.features
+                            .get_mut("additionalProperties")
+                            .unwrap()
+                            .paths
+                            .get_mut(&pointer)
+                            .unwrap()
+                            .get_or_insert_with(|| "--additional-prob is zero".to_owned());
+                    }
+                }
+
+                for k in crate::sorted_keys(required) {
+                    path.extend(vec!["properties".to_owned(), k.clone()]);
+                    self.walk(&required[k], path);
+                    path.truncate(path.len() - 2);
+                }
+
+                for k in crate::sorted_keys(optional) {
+                    path.extend(vec!["optionalProperties".to_owned(), k.clone()]);
+                    let sub_pointer = Pointer(path.clone()).to_string();
+                    self.add("optionalProperties", true, &["--optional"], &sub_pointer);
+                    if self.config.optional.contains_key(&sub_pointer) {
+                        self.configure("optionalProperties", "--optional", &sub_pointer);
+                    }
+
+                    self.walk(&optional[k], path);
+                    path.truncate(path.len() - 2);
+                }
+            }
+            Form::Discriminator(_, mapping) => {
+                self.add("discriminator", true, &["--freeze"], &pointer);
+                for tag_val in crate::sorted_keys(mapping) {
+                    path.extend(vec![
+                        "discriminator".to_owned(),
+                        "mapping".to_owned(),
+                        tag_val.clone(),
+                    ]);
+                    self.walk(&mapping[tag_val], path);
+                    path.truncate(path.len() - 3);
+                }
+            }
+        }
+    }
+
+    /// Records that a feature is used at a schema path.
+    fn add(&mut self, name: &str, supported: bool, options: &'static [&'static str], path: &str) {
+        // A frozen path is generated, but always as the same value.
+        let reason = match self.generated.get(path) {
+            None => Some("it is within a definition which is never referred to".to_owned()),
+            Some(None) if self.config.freeze.contains_key(path) => {
+                Some(format!("{} is frozen", path))
+            }
+            Some(reason) => reason.clone(),
+        };
+
+        self.features
+            .entry(name.to_owned())
+            .or_insert_with(|| Feature {
+                supported,
+                options,
+                paths: BTreeMap::new(),
+                configured: BTreeMap::new(),
+            })
+            .paths
+            .insert(path.to_owned(), reason);
+
+        if options.contains(&"--freeze") && self.config.freeze.contains_key(path) {
+            self.configure(name, "--freeze", path);
+        }
+    }
+
+    /// Records that an option is set for a feature at a schema path.
+    fn configure(&mut self, name: &str, option: &'static str, path: &str) {
+        self.features
+            .get_mut(name)
+            .unwrap()
+            .configured
+            .entry(option)
+            .or_default()
+            .push(path.to_owned());
+    }
+}
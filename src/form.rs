@@ -0,0 +1,82 @@
+//! Encoding documents as HTML form data (`application/x-www-form-urlencoded`)
+//! or as URL query strings, for endpoints which take those rather than JSON.
+//!
+//! Documents must be objects. Each of their members becomes a `name=value`
+//! pair, with the bracket conventions most form parsers understand for values
+//! which aren't scalars: the elements of an array of scalars are each named
+//! `name[]`, and members of objects, and elements of arrays of anything else,
+//! are named `name[key]` or `name[index]`. `null` is an empty value, and
+//! empty arrays and objects leave out their member entirely.
+
+use failure::{bail, Error};
+use serde_json::Value;
+
+/// Encodes a document as form data, or as a query string, with a leading `?`
+/// if `query`.
+///
+/// Form data escapes spaces as `+`, as HTML forms do, and query strings as
+/// `%20`, since not every consumer of URLs takes `+` to be a space.
+pub fn encode(val: &Value, query: bool) -> Result<String, Error> {
+    let obj = match val {
+        Value::Object(obj) => obj,
+        _ => bail!("only objects can be form-encoded, not: {}", val),
+    };
+
+    let mut pairs = Vec::new();
+    for (k, val) in obj {
+        flatten(k.clone(), val, &mut pairs);
+    }
+
+    let encoded: Vec<_> = pairs
+        .iter()
+        .map(|(name, val)| format!("{}={}", escape(name, query), escape(val, query)))
+        .collect();
+
+    let prefix = if query { "?" } else { "" };
+    Ok(format!("{}{}", prefix, encoded.join("&")))
+}
+
+/// Appends the name-value pairs for a value, under a name.
+fn flatten(name: String, val: &Value, pairs: &mut Vec<(String, String)>) {
+    match val {
+        Value::Null => pairs.push((name, String::new())),
+        Value::String(s) => pairs.push((name, s.clone())),
+        Value::Bool(_) | Value::Number(_) => pairs.push((name, val.to_string())),
+        Value::Array(vals) if vals.iter().all(is_scalar) => {
+            for val in vals {
+                flatten(format!("{}[]", name), val, pairs);
+            }
+        }
+        Value::Array(vals) => {
+            for (i, val) in vals.iter().enumerate() {
+                flatten(format!("{}[{}]", name, i), val, pairs);
+            }
+        }
+        Value::Object(obj) => {
+            for (k, val) in obj {
+                flatten(format!("{}[{}]", name, k), val, pairs);
+            }
+        }
+    }
+}
+
+fn is_scalar(val: &Value) -> bool {
+    !matches!(val, Value::Array(_) | Value::Object(_))
+}
+
+/// Percent-encodes a name or value, leaving only the characters unescaped
+/// which needn't ever be.
+fn escape(s: &str, query: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' => out.push(b as char),
+            b'*' if !query => out.push('*'),
+            b'~' if query => out.push('~'),
+            b' ' if !query => out.push('+'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    out
+}
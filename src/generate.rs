@@ -0,0 +1,1033 @@
+//! Generating documents and writing them out, as the `jddf-fuzz` command does
+//! without a subcommand.
+//!
+//! [`run`] generates documents for a schema and config, and [`Options`] says
+//! everything else about the run: how many documents there are, how they are
+//! encoded, what is done to them after they're generated, and where they go.
+
+#[cfg(feature = "avro")]
+use crate::avro;
+use crate::commands::SchemaFile;
+use crate::config::{FuzzConfig, InvalidKind, UnsupportedBehavior};
+use crate::output::{DocumentFiles, Encoder, RotatingFiles, Sink, Tee};
+use crate::pointer::{self, Pointer};
+use crate::{corpus, estimate, features, giant, http, invalid, keys, mutations, output, patch};
+use crate::{fnv1a, next_document, run_context, run_rng, seeded_rng, Attempts, ALGORITHM_VERSION};
+use crate::{reach, rules, size, stats, stream, tui, verify};
+use failure::{bail, format_err, Error};
+use jddf::schema::Form;
+use jddf::{Schema, Validator};
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub use crate::output::RecordSeparator;
+
+/// How many documents generation may get ahead of output by, before waiting
+/// for output to catch up.
+const PIPELINE_DEPTH: usize = 64;
+
+/// How many documents in a row may have nowhere they can be broken, under
+/// `--invalid`, before giving up.
+const MAX_UNBREAKABLE: usize = 1000;
+
+/// How many documents `--estimate` generates, at most, to base its prediction
+/// on.
+const ESTIMATE_SAMPLES: usize = 1000;
+
+/// How large the stack documents are generated on is, before making room for
+/// how deeply they may be nested.
+const BASE_STACK_BYTES: usize = 2 * 1024 * 1024;
+
+/// How much more stack documents are generated on for each level they may be
+/// nested, with room to spare for checking and fingerprinting them too.
+const STACK_BYTES_PER_DEPTH: usize = 16 * 1024;
+
+/// Everything about a run besides the schema and config, as given on the
+/// command line.
+#[derive(Debug)]
+pub struct Options {
+    /// How many documents to generate, or zero to go on forever, as with `-n`.
+    pub count: usize,
+
+    /// How documents are encoded.
+    pub format: Format,
+
+    /// Where documents are written to.
+    pub output: Output,
+
+    /// Only keep the parts of documents these point to, as with `--project`.
+    pub project: Vec<Pointer>,
+
+    /// Vary the parts of documents these point to more than the rest, as with
+    /// `--focus`.
+    pub focus: Vec<Pointer>,
+
+    /// Replace the parts of documents these point to, as with `--redact`.
+    pub redact: Vec<Pointer>,
+
+    /// How many tenants to interleave the documents of, as with `--tenants`.
+    pub tenants: usize,
+
+    /// How often each tenant's documents come up, as with `--tenant-mix`, or
+    /// equally often if not given.
+    pub tenant_mix: Option<Vec<f64>>,
+
+    /// Predict how large the output would be, rather than writing it, as with
+    /// `--estimate`.
+    pub estimate: bool,
+
+    /// Send each document in a POST request, rather than writing it out, as
+    /// with `--post`.
+    pub post: Option<Post>,
+
+    /// Also copy documents to these, as given with `--sink`.
+    pub sinks: Vec<String>,
+
+    /// Add documents to the corpus in this directory, rather than writing them
+    /// out, as with `--corpus`.
+    pub corpus: Option<PathBuf>,
+
+    /// Steer the size of documents towards this many bytes on average, as
+    /// with `--mean-size`.
+    pub mean_size: Option<u64>,
+
+    /// Write stats to this file every `stats_interval`, as with `--stats`.
+    pub stats: Option<PathBuf>,
+    pub stats_interval: Duration,
+
+    /// Write progress events to this file, or file descriptor if of the form
+    /// `fd:N`, every `progress_interval`, as with `--progress-json`.
+    pub progress: Option<String>,
+    pub progress_interval: Duration,
+
+    /// Draw a dashboard of the run on the terminal, as with `--tui`.
+    pub tui: bool,
+
+    /// Stop once this many bytes have been written, as with
+    /// `--stop-after-bytes`.
+    pub stop_after_bytes: Option<u64>,
+
+    /// Stop after this long, as with `--stop-after-duration`.
+    pub stop_after: Option<Duration>,
+
+    /// Stop once every enum value and discriminator variant has been
+    /// generated, as with `--stop-after-coverage`.
+    pub stop_after_coverage: bool,
+
+    /// Report how often each enum value and discriminator variant came up, as
+    /// with `--report`.
+    pub report: bool,
+
+    /// Write a single document of about this many bytes, as it's generated,
+    /// as with `--giant` and `--target-bytes`.
+    pub giant: Option<u64>,
+
+    /// Write a single document whose root array or map has this many elements
+    /// or members, as it's generated, as with `--stream-root`.
+    pub stream_root: Option<u64>,
+
+    /// Wrap each document as the variables of the GraphQL query in this file,
+    /// as with `--graphql-query`, with an operation name if given.
+    pub graphql_query: Option<PathBuf>,
+    pub graphql_operation: Option<String>,
+
+    /// Write patches after each document, as with `--json-patch` and
+    /// `--merge-patch`.
+    pub patches: Option<Patches>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            count: 0,
+            format: Format::Json(RecordSeparator::Lf),
+            output: Output::Stdout,
+            project: Vec::new(),
+            focus: Vec::new(),
+            redact: Vec::new(),
+            tenants: 1,
+            tenant_mix: None,
+            estimate: false,
+            post: None,
+            sinks: Vec::new(),
+            corpus: None,
+            mean_size: None,
+            stats: None,
+            stats_interval: Duration::from_secs(60),
+            progress: None,
+            progress_interval: Duration::from_secs(1),
+            tui: false,
+            stop_after_bytes: None,
+            stop_after: None,
+            stop_after_coverage: false,
+            report: false,
+            giant: None,
+            stream_root: None,
+            graphql_query: None,
+            graphql_operation: None,
+            patches: None,
+        }
+    }
+}
+
+/// How documents are encoded, as given with `--format`, `--encoding`, and
+/// `--avro`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    /// JSON, delimited by a record separator.
+    Json(RecordSeparator),
+
+    /// A single JSON array in each file, whose elements are the documents.
+    Array,
+
+    /// Indented JSON, with a line holding a delimiter between documents.
+    Pretty { delimiter: String },
+
+    /// Form data, or a query string if `query`, on a line of its own.
+    Form { query: bool },
+
+    /// CBOR, each preceded by its length in bytes.
+    Cbor,
+
+    /// MessagePack, each preceded by its length in bytes.
+    MessagePack,
+
+    /// Avro, each preceded by its length in bytes.
+    #[cfg(feature = "avro")]
+    AvroRecords,
+
+    /// An Avro object container file.
+    #[cfg(feature = "avro")]
+    AvroContainer,
+}
+
+/// Where documents are written to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// Standard output.
+    Stdout,
+
+    /// A file, as with `--output`.
+    File(PathBuf),
+
+    /// A file of its own for each document, named after a template, as with
+    /// `--output-dir` and `--output-template`.
+    Documents { dir: PathBuf, template: String },
+
+    /// Files which are moved on from once they hold `max_bytes`, or are
+    /// `max_age` old, as with `--soak`.
+    Rotating {
+        dir: PathBuf,
+        max_bytes: Option<u64>,
+        max_age: Option<Duration>,
+    },
+}
+
+/// Where to send documents, and how, as given with `--post`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Post {
+    /// The `http://` or `https://` URL to send documents to.
+    pub url: String,
+
+    /// Headers to add to each request, given as `Name: value`.
+    pub headers: Vec<String>,
+
+    /// How many requests may be in flight at once.
+    pub concurrency: usize,
+
+    /// How many requests may be sent a second, at most.
+    pub rate: Option<f64>,
+}
+
+/// How many patches to write after each document, and in which format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Patches {
+    /// RFC 6902 JSON Patches, as with `--json-patch`.
+    JsonPatch(usize),
+
+    /// RFC 7386 JSON Merge Patches, as with `--merge-patch`.
+    MergePatch(usize),
+}
+
+/// What a run reports once it's done, as JSON, for each of the options which
+/// report anything.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Sinks which failed, and how.
+    pub warnings: Vec<String>,
+
+    /// How many responses had each status, under `post`.
+    pub posted: Option<Value>,
+
+    /// How large the output would be, under `estimate`.
+    pub estimate: Option<Value>,
+
+    /// How often each enum value and discriminator variant came up, under
+    /// `report` or the config's `coverage`.
+    pub report: Option<Value>,
+
+    /// What was added to the corpus, under `corpus`.
+    pub corpus: Option<Value>,
+}
+
+/// Warnings about a schema, and what the config will or won't generate for
+/// it, to give before a run.
+pub fn warnings(schema: &SchemaFile, config: &FuzzConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !schema.unsupported.is_empty() {
+        warnings.push(format!(
+            "{} {} parts of the schema jddf-fuzz doesn't support:",
+            match config.on_unsupported {
+                UnsupportedBehavior::Skip => "skipping",
+                UnsupportedBehavior::Null => "generating null for",
+                _ => "generating placeholders for",
+            },
+            schema.unsupported.len()
+        ));
+        for (_, err) in &schema.unsupported {
+            warnings.push(format!("  {}", err));
+        }
+    }
+
+    warnings.extend(reach::warnings(&schema.schema, config));
+    warnings
+}
+
+/// Generates documents for a schema and config, and writes them out as the
+/// options say.
+///
+/// Ends early, without failing, if output is a pipe whose reader has gone.
+pub fn run(schema: &SchemaFile, config: &FuzzConfig, options: Options) -> Result<Summary, Error> {
+    let Options {
+        count: num_values,
+        format,
+        output: output_to,
+        project,
+        focus,
+        redact,
+        tenants: num_tenants,
+        tenant_mix,
+        estimate,
+        post,
+        sinks,
+        corpus,
+        mean_size,
+        stats: stats_path,
+        stats_interval,
+        progress,
+        progress_interval,
+        tui,
+        stop_after_bytes: stop_bytes,
+        stop_after,
+        stop_after_coverage,
+        report,
+        giant,
+        stream_root,
+        graphql_query,
+        graphql_operation,
+        patches,
+    } = options;
+    let (schema_hash, schema) = (&schema.hash, &schema.schema);
+
+    let record_separator = match format {
+        Format::Json(record_separator) => record_separator,
+        _ => RecordSeparator::Lf,
+    };
+
+    let binary = matches!(format, Format::Cbor | Format::MessagePack);
+
+    let flags = [
+        ("giant", giant.is_some()),
+        ("stream-root", stream_root.is_some()),
+        ("corpus", corpus.is_some()),
+    ];
+    for (flag, given) in &flags {
+        if binary && *given {
+            bail!(
+                "--{} writes JSON, so it can't be combined with --encoding",
+                flag
+            );
+        }
+    }
+
+    #[cfg(feature = "avro")]
+    {
+        if let Format::AvroRecords | Format::AvroContainer = format {
+            if patches.is_some() {
+                bail!("patches are written as JSON, so they can't be combined with --avro");
+            }
+
+            if giant.is_some() {
+                bail!("--giant writes JSON as it goes, so it can't be combined with --avro");
+            }
+
+            if config.invalid {
+                bail!("invalid documents can't be encoded as Avro, so --invalid can't be combined with --avro");
+            }
+
+            if stream_root.is_some() {
+                bail!("--stream-root writes JSON as it goes, so it can't be combined with --avro");
+            }
+
+            if corpus.is_some() {
+                bail!(
+                    "corpus files are written as JSON, so --corpus can't be combined with --avro"
+                );
+            }
+
+            if format == Format::AvroContainer && post.is_some() {
+                bail!("--post sends each document on its own, so it can't be combined with --avro container");
+            }
+        }
+    }
+
+    let mut rng = run_rng(config.seed)?;
+    let mut encoder = match format {
+        Format::Json(record_separator) => Encoder::Json(record_separator),
+        Format::Array => Encoder::array(),
+        Format::Pretty { ref delimiter } => Encoder::pretty(delimiter),
+        Format::Form { query } => Encoder::Form { query },
+        Format::Cbor => Encoder::Cbor,
+        Format::MessagePack => Encoder::MessagePack,
+        #[cfg(feature = "avro")]
+        Format::AvroRecords => Encoder::AvroRecords(avro::AvroSchema::from_schema(schema)?),
+        #[cfg(feature = "avro")]
+        Format::AvroContainer => Encoder::AvroContainer(avro::ContainerWriter::new(
+            &mut rng,
+            avro::AvroSchema::from_schema(schema)?,
+        )),
+    };
+
+    if let Encoder::Form { .. } = encoder {
+        if !is_object_schema(schema, schema) {
+            bail!("only objects can be form-encoded, but the schema does not describe one");
+        }
+    }
+
+    let graphql_query = match graphql_query {
+        Some(path) => {
+            if !is_object_schema(schema, schema) {
+                bail!("GraphQL variables must be an object, but the schema does not describe one");
+            }
+
+            Some(std::fs::read_to_string(path)?)
+        }
+        None => None,
+    };
+
+    let (patch_format, patch_steps) = match patches {
+        Some(Patches::JsonPatch(steps)) => (patch::Format::JsonPatch, steps),
+        Some(Patches::MergePatch(steps)) => (patch::Format::MergePatch, steps),
+        None => (patch::Format::JsonPatch, 0),
+    };
+
+    // Each tenant after the first has a config of its own only so that, under a
+    // seed, its documents are derived from a seed of its own too.
+    if num_tenants == 0 {
+        bail!("--tenants must be at least one");
+    }
+
+    let tenant_configs: Vec<_> = (1..num_tenants)
+        .map(|i| FuzzConfig {
+            seed: config
+                .seed
+                .map(|seed| seeded_rng(&[seed, fnv1a("tenant"), i as u64]).gen()),
+            ..config.clone()
+        })
+        .collect();
+
+    let tenant_mix = match tenant_mix {
+        Some(weights) => {
+            if weights.len() != num_tenants
+                || weights.iter().any(|w| *w < 0.0)
+                || weights.iter().all(|w| *w == 0.0)
+            {
+                bail!(
+                    "--tenant-mix must be {} non-negative weights, not all zero",
+                    num_tenants
+                );
+            }
+
+            WeightedIndex::new(&weights)?
+        }
+        None => WeightedIndex::new(vec![1.0; num_tenants])?,
+    };
+
+    let mut attempts = Attempts::default();
+    let mut ctx = run_context(schema, config, &focus)?;
+
+    // The tenant being generated for is in ctx, rng, and attempts, and the
+    // others are set aside here, each in its own slot.
+    let mut tenant = 0;
+    let mut tenants = vec![None];
+    for (i, tenant_config) in tenant_configs.iter().enumerate() {
+        let tenant_rng = match config.seed {
+            Some(seed) => seeded_rng(&[seed, fnv1a("tenant-run"), i as u64 + 1]),
+            None => run_rng(None)?,
+        };
+
+        tenants.push(Some((
+            run_context(schema, tenant_config, &focus)?,
+            tenant_rng,
+            Attempts::default(),
+        )));
+    }
+
+    let mut mix_rng = match config.seed {
+        Some(seed) => seeded_rng(&[seed, fnv1a("tenant-mix")]),
+        None => run_rng(None)?,
+    };
+
+    let stdout = io::stdout();
+    if estimate && num_values == 0 {
+        bail!("--estimate needs a number of documents to estimate for, given with -n");
+    }
+
+    let mut sample = if estimate {
+        Some(estimate::Sample::start())
+    } else {
+        None
+    };
+
+    let target = if estimate {
+        num_values.min(ESTIMATE_SAMPLES)
+    } else {
+        num_values
+    };
+
+    let soak = matches!(output_to, Output::Rotating { .. });
+    let mut out = match output_to {
+        _ if estimate || post.is_some() => Sink::discard(),
+        Output::Rotating {
+            dir,
+            max_bytes,
+            max_age,
+        } => {
+            let extension = encoder.extension();
+            Sink::files(RotatingFiles::new(&dir, extension, max_bytes, max_age)?)
+        }
+        Output::File(path) => Sink::file(&path)?,
+        Output::Documents { dir, template } => {
+            Sink::documents(DocumentFiles::new(&dir, &template)?)
+        }
+        Output::Stdout => Sink::stdout(stdout.lock()),
+    };
+
+    let mut poster = match post {
+        Some(_) if format == (Format::Form { query: true }) => {
+            bail!("--post sends documents in request bodies, so it can't be combined with --format query-string")
+        }
+        Some(post) => Some(http::Poster::new(
+            http::Endpoint::parse(&post.url)?,
+            encoder.content_type(),
+            post.headers
+                .iter()
+                .map(|header| http::parse_header(header))
+                .collect::<Result<_, _>>()?,
+            post.concurrency,
+            post.rate,
+        )?),
+        None => None,
+    };
+
+    let mut tees = sinks
+        .iter()
+        .map(|sink| sink.parse())
+        .collect::<Result<Vec<Tee>, _>>()?;
+    let mut tee_rng = match config.seed {
+        Some(seed) => seeded_rng(&[seed, fnv1a("sink")]),
+        None => run_rng(None)?,
+    };
+
+    let mut corpus = match corpus {
+        Some(dir) => Some(corpus::Corpus::open(&dir)?),
+        None => None,
+    };
+
+    let mut size_controller = mean_size.map(size::SizeController::new);
+
+    let mut stats = stats::Stats::start();
+    stats.files = if soak { 1 } else { 0 };
+    let mut stats_file = stats_path.map(|path| stats::StatsFile::new(path, stats_interval));
+
+    let mut progress = match progress {
+        Some(target) => Some(stats::ProgressStream::new(
+            progress_writer(&target)?,
+            progress_interval,
+        )),
+        None => None,
+    };
+
+    let mut dashboard = if tui {
+        Some(tui::Dashboard::new())
+    } else {
+        None
+    };
+
+    // The enum and discriminator schema paths which are ever generated, and
+    // not frozen, so that all their values can be taken.
+    let stop_coverage: Option<BTreeSet<String>> = if stop_after_coverage {
+        let features = features::features(schema, config);
+        Some(
+            ["enum", "discriminator"]
+                .iter()
+                .filter_map(|name| features.get(*name))
+                .flat_map(|feature| &feature.paths)
+                .filter(|(_, reason)| reason.is_none())
+                .map(|(path, _)| path.clone())
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let mut report = if config.coverage || report {
+        Some(stats::Report::new())
+    } else {
+        None
+    };
+
+    let track_branches = stats_file.is_some()
+        || progress.is_some()
+        || dashboard.is_some()
+        || stop_coverage.is_some()
+        || report.is_some();
+
+    if let Some(size) = giant {
+        let result = out
+            .write_all(record_separator.prefix())
+            .and_then(|_| giant::write(&mut out, schema, size, &mut rng, &mut ctx))
+            .and_then(|_| out.write_all(record_separator.suffix()))
+            .and_then(|_| out.flush());
+
+        return match result {
+            Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(Summary::default()),
+            result => result.map(|_| Summary::default()).map_err(Error::from),
+        };
+    }
+
+    if let Some(len) = stream_root {
+        let container = match stream::Container::root(schema) {
+            Some(container) => container,
+            None => {
+                bail!("--stream-root needs a schema whose root is of the elements or values form")
+            }
+        };
+
+        let result = out
+            .write_all(record_separator.prefix())
+            .and_then(|_| container.write(&mut out, len, &mut rng, &mut ctx))
+            .and_then(|_| out.write_all(record_separator.suffix()))
+            .and_then(|_| out.flush());
+
+        return match result {
+            Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(Summary::default()),
+            result => result.map(|_| Summary::default()).map_err(Error::from),
+        };
+    }
+
+    // Documents are generated, and changed as the options say, on one thread,
+    // and written out on this one. Generation gets at most PIPELINE_DEPTH
+    // documents ahead, and then waits for output to catch up.
+    let (tx, rx) = mpsc::sync_channel::<Batch>(PIPELINE_DEPTH);
+    let graphql_operation = graphql_operation.as_deref();
+    let stack_size = config
+        .max_depth()
+        .checked_mul(STACK_BYTES_PER_DEPTH)
+        .and_then(|bytes| bytes.checked_add(BASE_STACK_BYTES))
+        .ok_or_else(|| format_err!("--max-depth is too large: {}", config.max_depth()))?;
+    let (generated, written) = thread::scope(|scope| {
+        let generation = thread::Builder::new().stack_size(stack_size).spawn_scoped(scope, || -> Result<(), Error> {
+            let tx = tx;
+            let validator = Validator::new();
+            let patch_mutations = mutations::all();
+            let mut left_out = stats::Stats::start();
+            let invalid_kinds = if config.invalid_kinds.is_empty() {
+                InvalidKind::all()
+            } else {
+                config.invalid_kinds.clone()
+            };
+            let mut unbreakable = 0;
+
+            let mut i = 0;
+            while i != target || target == 0 {
+                if num_tenants > 1 {
+                    let next_tenant = mix_rng.sample(&tenant_mix);
+
+                    if next_tenant != tenant {
+                        let (next_ctx, next_rng, next_attempts) =
+                            tenants[next_tenant].take().unwrap();
+                        tenants[tenant] = Some((
+                            mem::replace(&mut ctx, next_ctx),
+                            mem::replace(&mut rng, next_rng),
+                            mem::replace(&mut attempts, next_attempts),
+                        ));
+                        tenant = next_tenant;
+                    }
+                }
+
+                let next = next_document(&mut ctx, &mut rng, &mut attempts, &mut left_out)?;
+                let mut val = match next {
+                    Some(val) => val,
+                    None => break,
+                };
+
+                if config.verify {
+                    verify::verify(&validator, schema, &val, config.proto_json)?;
+                }
+
+                // Coverage is of the document as generated, before it's renamed,
+                // broken, or wrapped in anything.
+                let branches = if track_branches {
+                    stats::branches(schema, &val)
+                } else {
+                    Vec::new()
+                };
+
+                if config.key_variants > 0.0 {
+                    // So that repeatable runs stay repeatable, this is seeded too.
+                    let seed = config.seed.unwrap_or_else(|| rng.gen());
+                    let mut key_rng = seeded_rng(&[seed, ctx.index, fnv1a("key-variants")]);
+                    if key_rng.gen_bool(config.key_variants) {
+                        keys::rename_one(schema, &mut val, &mut key_rng);
+                    }
+                }
+
+                let mut violation = None;
+                if config.invalid {
+                    violation = invalid::break_one(schema, &mut val, &invalid_kinds, &mut rng);
+                    if violation.is_none() {
+                        unbreakable += 1;
+                        if unbreakable == MAX_UNBREAKABLE {
+                            bail!(
+                                "{} documents in a row could not be made invalid in any of the given ways",
+                                MAX_UNBREAKABLE
+                            );
+                        }
+
+                        continue;
+                    }
+
+                    unbreakable = 0;
+                }
+
+                let mut val = if project.is_empty() {
+                    val
+                } else {
+                    pointer::project(&val, &project)
+                };
+
+                pointer::redact(&mut val, &redact);
+
+                if config.tag_validity {
+                    let valid = validator.validate(schema, &val)?.is_empty();
+                    val = json!({ "instance": val, "valid": valid });
+                    if let Some(ref violation) = violation {
+                        val["violation"] = violation.to_json();
+                    }
+                }
+
+                if let Some(ref query) = graphql_query {
+                    val = match graphql_operation {
+                        Some(name) => {
+                            json!({ "query": query, "operationName": name, "variables": val })
+                        }
+                        None => json!({ "query": query, "variables": val }),
+                    };
+                }
+
+                if num_tenants > 1 {
+                    val = json!({ "tenant": tenant, "document": val });
+                }
+
+                if let Some(ref mut size_controller) = size_controller {
+                    size_controller.record(serde_json::to_string(&val)?.len());
+                    ctx.size_scale = size_controller.scale;
+                }
+
+                // Each patch is from a mutation of the document as it stands. A
+                // mutation which would break the config's rules, or which the patch
+                // format can't express, is left out.
+                let mut patches = Vec::new();
+                if patch_steps > 0 {
+                    let mut state = val.clone();
+                    for _ in 0..patch_steps {
+                        let mut next = state.clone();
+                        if mutations::mutate(
+                            schema,
+                            &mut next,
+                            &patch_mutations,
+                            &focus,
+                            &mut rng,
+                            &mut ctx,
+                        )
+                        .is_none()
+                        {
+                            break;
+                        }
+
+                        ctx.depth_limit_hit = None;
+                        if !rules::enforce(&config.rules, &mut next) {
+                            continue;
+                        }
+
+                        if let Some(patch) = patch_format.diff(&state, &next) {
+                            patches.push(patch);
+                            state = next;
+                        }
+                    }
+                }
+
+                let batch = Batch {
+                    doc: val,
+                    patches,
+                    branches,
+                    skipped: left_out.skipped,
+                    stale: left_out.stale,
+                    rejected: left_out.rejected,
+                };
+
+                // Output only stops early when it can't go on, and it reports
+                // why, or when a stop condition is met.
+                if tx.send(batch).is_err() {
+                    break;
+                }
+
+                i += 1;
+            }
+
+            Ok(())
+        });
+
+        // Generation can't start without a stack as deep as it may go, such as
+        // under a --max-depth larger than memory allows.
+        let generation = match generation {
+            Ok(generation) => generation,
+            Err(err) => {
+                let err = format_err!(
+                    "can't make a {} byte stack for --max-depth {}: {}",
+                    stack_size,
+                    config.max_depth(),
+                    err
+                );
+                return (Err(err), Ok(()));
+            }
+        };
+
+        let written = (|| -> Result<(), Error> {
+            let started = Instant::now();
+            for batch in rx {
+                stats.skipped = batch.skipped;
+                stats.stale = batch.stale;
+                stats.rejected = batch.rejected;
+                if let Some(ref mut report) = report {
+                    report.record(&batch.branches);
+                }
+
+                stats.record(batch.branches);
+
+                let bytes = out.bytes;
+                match corpus {
+                    Some(ref mut corpus) => {
+                        corpus.add(&batch.doc)?;
+                    }
+                    None => match poster {
+                        Some(ref mut poster) => {
+                            let body = encoder.body(&batch.doc)?;
+                            out.bytes += body.len() as u64;
+                            poster.send(body)?;
+                        }
+                        None => encoder.write(&mut out, &batch.doc)?,
+                    },
+                }
+
+                if let Some(ref mut sample) = sample {
+                    sample.record(out.bytes - bytes);
+                }
+
+                for patch in &batch.patches {
+                    encoder.write(&mut out, patch)?;
+                }
+
+                for tee in &mut tees {
+                    tee.write(&mut tee_rng, &batch.doc)?;
+                }
+
+                stats.documents += 1;
+
+                if out.rotation_due() {
+                    encoder.end_file(&mut out)?;
+                    out.rotate()?;
+                    stats.files += 1;
+                }
+
+                if let Some(ref mut stats_file) = stats_file {
+                    if stats_file.due() {
+                        stats_file.write(&stats.snapshot(out.bytes, out.blocked()))?;
+                    }
+                }
+
+                if let Some(ref mut progress) = progress {
+                    if progress.due() {
+                        progress.write("progress", &stats.snapshot(out.bytes, out.blocked()))?;
+                    }
+                }
+
+                if let Some(ref mut dashboard) = dashboard {
+                    if dashboard.due() {
+                        dashboard.draw(&stats.snapshot(out.bytes, out.blocked()))?;
+                    }
+                }
+
+                if stop_bytes.is_some_and(|bytes| out.bytes >= bytes)
+                    || stop_after.is_some_and(|after| started.elapsed() >= after)
+                    || stop_coverage
+                        .as_ref()
+                        .is_some_and(|paths| stats.covers(paths))
+                {
+                    break;
+                }
+            }
+
+            Ok(())
+        })();
+
+        let generated = generation
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        (generated, written)
+    });
+
+    match written {
+        Err(ref err) if output::is_broken_pipe(err) => return Ok(Summary::default()),
+        result => result?,
+    }
+    generated?;
+
+    let result = match encoder.finish(&mut out) {
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    };
+
+    let mut summary = Summary::default();
+    for tee in &mut tees {
+        tee.finish()?;
+        summary.warnings.extend(tee.warning());
+    }
+
+    summary.posted = poster.map(|poster| poster.finish().to_json());
+
+    if let Some(ref mut stats_file) = stats_file {
+        stats_file.write(&stats.snapshot(out.bytes, out.blocked()))?;
+    }
+
+    if let Some(ref mut progress) = progress {
+        progress.write("done", &stats.snapshot(out.bytes, out.blocked()))?;
+    }
+
+    if let Some(ref mut dashboard) = dashboard {
+        dashboard.draw(&stats.snapshot(out.bytes, out.blocked()))?;
+    }
+
+    summary.estimate = sample.map(|sample| sample.predict(num_values, out.bytes));
+    summary.report = report.map(|report| report.to_json());
+
+    if let Some(mut corpus) = corpus {
+        // Runs can only be repeated if every option which affects documents
+        // is in the config, and nothing is left to chance.
+        let repeatable = config.seed.is_some()
+            && project.is_empty()
+            && redact.is_empty()
+            && focus.is_empty()
+            && graphql_query.is_none()
+            && !config.invalid
+            && num_tenants == 1
+            && mean_size.is_none();
+
+        if repeatable {
+            corpus.record_run(json!({
+                "version": ALGORITHM_VERSION,
+                "schemaHash": schema_hash,
+                "config": config.to_canonical_json(),
+            }))?;
+        }
+
+        summary.corpus = Some(corpus.to_json());
+    }
+
+    result?;
+    Ok(summary)
+}
+
+/// A document, with the patches which follow it, passed from generation to
+/// output.
+struct Batch {
+    doc: Value,
+    patches: Vec<Value>,
+
+    /// The choices the document took, such as its enum values and
+    /// discriminator variants, when they're being counted.
+    branches: Vec<stats::Branch>,
+
+    /// How many documents generation has left out so far, for the stats.
+    skipped: u64,
+    stale: u64,
+    rejected: u64,
+}
+
+/// Opens where progress events go: a file descriptor the caller has opened for
+/// them, like `fd:3`, or otherwise a file.
+fn progress_writer(target: &str) -> Result<Box<dyn Write>, Error> {
+    if !target.starts_with("fd:") {
+        return Ok(Box::new(File::create(target)?));
+    }
+
+    let fd: i32 = target[3..]
+        .parse()
+        .map_err(|_| format_err!("invalid file descriptor: {}", target))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::FromRawFd;
+
+        // Standard input, output and error are already in use, and the File
+        // below would close them when dropped.
+        if fd <= 2 {
+            bail!("file descriptor is not free for progress: {}", fd);
+        }
+
+        // SAFETY: F_GETFD only reads the descriptor's flags, and fails for a
+        // descriptor that isn't open.
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            bail!("file descriptor is not open: {}", fd);
+        }
+
+        // SAFETY: the descriptor is open, isn't one of the standard streams,
+        // and was inherited for this alone, so nothing else in the process
+        // uses or closes it, and the File may own it.
+        Ok(Box::new(unsafe { File::from_raw_fd(fd) }))
+    }
+
+    #[cfg(not(unix))]
+    {
+        bail!("file descriptors are only supported on Unix: {}", fd)
+    }
+}
+
+/// Whether every instance of a schema is an object.
+fn is_object_schema(root: &Schema, schema: &Schema) -> bool {
+    match schema.form() {
+        Form::Properties { .. } | Form::Values(_) | Form::Discriminator(..) => true,
+        Form::Ref(def) => is_object_schema(root, &root.definitions().as_ref().unwrap()[def]),
+        _ => false,
+    }
+}
@@ -0,0 +1,279 @@
+//! Single, enormous documents, written out as they are generated, so that
+//! the whole document is never held in memory.
+//!
+//! A document grows wherever its schema allows arrays or maps, or any value
+//! at all. Those are filled with ordinary, small values until they take up
+//! their share of the target size, and some of those values are themselves
+//! grown, so that documents are deep as well as wide.
+
+use crate::mutations::Generator;
+use jddf::schema::Form;
+use jddf::Schema;
+use rand::{Rng, RngCore};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Values with a smaller share of the target size than this are generated
+/// as usual, rather than grown.
+const MIN_BUDGET: u64 = 4096;
+
+/// The probability that an element or value of a growing array or map is
+/// itself grown.
+const DEEP_PROB: f64 = 0.01;
+
+/// Writes a document satisfying a schema, of at least roughly `target` bytes.
+/// Returns how many bytes were written.
+pub fn write<W: Write>(
+    out: &mut W,
+    root: &Schema,
+    target: u64,
+    rng: &mut dyn RngCore,
+    gen: &mut dyn Generator,
+) -> io::Result<u64> {
+    let mut giant = Giant {
+        root,
+        out: Counting { out, bytes: 0 },
+        rng,
+        gen,
+        schema_path: Vec::new(),
+        instance_path: Vec::new(),
+    };
+
+    giant.value(root, target)?;
+    Ok(giant.out.bytes)
+}
+
+/// A writer which counts the bytes written to it.
+struct Counting<W> {
+    out: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.out.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+struct Giant<'a, W> {
+    root: &'a Schema,
+    out: Counting<W>,
+    rng: &'a mut dyn RngCore,
+    gen: &'a mut dyn Generator,
+    schema_path: Vec<String>,
+    instance_path: Vec<String>,
+}
+
+impl<'a, W: Write> Giant<'a, W> {
+    /// Writes a value satisfying a schema, which takes up about `budget`
+    /// bytes if the schema lets it grow.
+    fn value(&mut self, schema: &'a Schema, budget: u64) -> io::Result<()> {
+        if budget < MIN_BUDGET || !growable(self.root, schema, &mut HashSet::new()) {
+            return self.small(schema);
+        }
+
+        match schema.form() {
+            Form::Ref(def) => {
+                let definition = &self.root.definitions().as_ref().unwrap()[def];
+                let path = std::mem::replace(
+                    &mut self.schema_path,
+                    vec!["definitions".to_owned(), def.clone()],
+                );
+                self.value(definition, budget)?;
+                self.schema_path = path;
+                Ok(())
+            }
+
+            // Any value at all is allowed, so this is an array of anything.
+            Form::Empty => self.array(schema, None, budget),
+            Form::Elements(sub_schema) => self.array(sub_schema, Some("elements"), budget),
+            Form::Values(sub_schema) => self.values(sub_schema, budget),
+            Form::Properties {
+                required, optional, ..
+            } => self.properties(required, optional, None, budget),
+            Form::Discriminator(tag, mapping) => {
+                let mut tag_vals: Vec<_> = mapping
+                    .keys()
+                    .filter(|tag_val| growable(self.root, &mapping[*tag_val], &mut HashSet::new()))
+                    .collect();
+                tag_vals.sort();
+
+                let tag_val = tag_vals[self.rng.gen_range(0, tag_vals.len())];
+                self.schema_path.extend(vec![
+                    "discriminator".to_owned(),
+                    "mapping".to_owned(),
+                    tag_val.clone(),
+                ]);
+
+                let result = match mapping[tag_val].form() {
+                    Form::Properties {
+                        required, optional, ..
+                    } => self.properties(
+                        required,
+                        optional,
+                        Some((tag.as_str(), tag_val.as_str())),
+                        budget,
+                    ),
+                    _ => unreachable!("discriminator mapping is not of the properties form"),
+                };
+
+                self.schema_path.truncate(self.schema_path.len() - 3);
+                result
+            }
+            _ => self.small(schema),
+        }
+    }
+
+    /// Writes a value generated as usual.
+    fn small(&mut self, schema: &'a Schema) -> io::Result<()> {
+        let val = self
+            .gen
+            .generate(self.rng, &self.schema_path, &self.instance_path, schema);
+        serde_json::to_writer(&mut self.out, &val)?;
+        Ok(())
+    }
+
+    /// The share of what's left of a budget that the next element or value
+    /// of a growing array or map gets.
+    fn child_budget(&mut self, end: u64) -> u64 {
+        if self.rng.gen_bool(DEEP_PROB) {
+            end.saturating_sub(self.out.bytes) / self.rng.gen_range(2, 9)
+        } else {
+            0
+        }
+    }
+
+    fn array(
+        &mut self,
+        sub_schema: &'a Schema,
+        token: Option<&str>,
+        budget: u64,
+    ) -> io::Result<()> {
+        let end = self.out.bytes + budget;
+        self.schema_path.extend(token.map(String::from));
+
+        self.out.write_all(b"[")?;
+        let mut i = 0;
+        while self.out.bytes < end {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+
+            let child_budget = self.child_budget(end);
+            self.instance_path.push(i.to_string());
+            self.value(sub_schema, child_budget)?;
+            self.instance_path.pop();
+            i += 1;
+        }
+        self.out.write_all(b"]")?;
+
+        if token.is_some() {
+            self.schema_path.pop();
+        }
+
+        Ok(())
+    }
+
+    fn values(&mut self, sub_schema: &'a Schema, budget: u64) -> io::Result<()> {
+        let end = self.out.bytes + budget;
+        self.schema_path.push("values".to_owned());
+
+        // Keys are numbered, so that none of them repeat.
+        self.out.write_all(b"{")?;
+        let mut i = 0;
+        while self.out.bytes < end {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+
+            let k = format!("k{}", i);
+            write!(self.out, "\"{}\":", k)?;
+
+            let child_budget = self.child_budget(end);
+            self.instance_path.push(k);
+            self.value(sub_schema, child_budget)?;
+            self.instance_path.pop();
+            i += 1;
+        }
+        self.out.write_all(b"}")?;
+
+        self.schema_path.pop();
+        Ok(())
+    }
+
+    /// Writes an object with the given properties, and a discriminator tag if
+    /// given. The budget is shared among the properties which can grow, and
+    /// those are always present.
+    fn properties(
+        &mut self,
+        required: &'a HashMap<String, Schema>,
+        optional: &'a HashMap<String, Schema>,
+        tag: Option<(&str, &str)>,
+        budget: u64,
+    ) -> io::Result<()> {
+        let mut members = Vec::new();
+        for &(token, props) in &[("properties", required), ("optionalProperties", optional)] {
+            for (k, sub_schema) in props {
+                let grows = growable(self.root, sub_schema, &mut HashSet::new());
+                if grows || token == "properties" || self.rng.gen() {
+                    members.push((k, token, sub_schema, grows));
+                }
+            }
+        }
+
+        members.sort_by_key(|(k, _, _, _)| *k);
+        let growing = members.iter().filter(|(_, _, _, grows)| *grows).count() as u64;
+
+        self.out.write_all(b"{")?;
+        if let Some((tag, tag_val)) = tag {
+            serde_json::to_writer(&mut self.out, tag)?;
+            self.out.write_all(b":")?;
+            serde_json::to_writer(&mut self.out, tag_val)?;
+        }
+
+        for (i, (k, token, sub_schema, grows)) in members.into_iter().enumerate() {
+            if i > 0 || tag.is_some() {
+                self.out.write_all(b",")?;
+            }
+
+            serde_json::to_writer(&mut self.out, k)?;
+            self.out.write_all(b":")?;
+
+            self.schema_path.extend(vec![token.to_owned(), k.clone()]);
+            self.instance_path.push(k.clone());
+            self.value(sub_schema, if grows { budget / growing } else { 0 })?;
+            self.instance_path.pop();
+            self.schema_path.truncate(self.schema_path.len() - 2);
+        }
+        self.out.write_all(b"}")?;
+
+        Ok(())
+    }
+}
+
+/// Whether values satisfying a schema can be arbitrarily large.
+fn growable(root: &Schema, schema: &Schema, visiting: &mut HashSet<String>) -> bool {
+    match schema.form() {
+        Form::Empty | Form::Elements(_) | Form::Values(_) => true,
+        Form::Ref(def) => {
+            visiting.insert(def.clone())
+                && growable(root, &root.definitions().as_ref().unwrap()[def], visiting)
+        }
+        Form::Properties {
+            required, optional, ..
+        } => required
+            .values()
+            .chain(optional.values())
+            .any(|sub_schema| growable(root, sub_schema, visiting)),
+        Form::Discriminator(_, mapping) => mapping
+            .values()
+            .any(|sub_schema| growable(root, sub_schema, visiting)),
+        _ => false,
+    }
+}
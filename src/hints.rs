@@ -0,0 +1,243 @@
+//! Plausible strings of a given format, for string schemas whose metadata
+//! hints at what they hold, like `{ "metadata": { "fuzzHint": "email" } }`, or
+//! strings matching a regular expression, with `{ "pattern": "..." }` as the
+//! hint.
+//!
+//! Random strings are rejected by anything which parses what it's given, so
+//! hinted strings are generated the way a real value of their format would
+//! be written, rather than with every edge case the format allows.
+
+use crate::pattern::Pattern;
+use crate::pointer::Pointer;
+use jddf::schema::Form;
+use jddf::Schema;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The metadata member which hints at a string's format.
+pub const KEY: &str = "fuzzHint";
+
+/// The formats which can be hinted at, by name.
+const REGISTRY: &[(&str, Hint<'static>)] = &[
+    ("email", Hint::Email),
+    ("hostname", Hint::Hostname),
+    ("ipv4", Hint::Ipv4),
+    ("ipv6", Hint::Ipv6),
+    ("uri", Hint::Uri),
+    ("uuid", Hint::Uuid),
+];
+
+const LOWERCASE_ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+const TLDS: &[&str] = &["com", "net", "org", "io", "dev", "example", "test"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hint<'a> {
+    Email,
+    Hostname,
+    Ipv4,
+    Ipv6,
+    Uri,
+    Uuid,
+
+    /// A regular expression, as given.
+    Pattern(&'a str),
+}
+
+impl<'a> Hint<'a> {
+    /// Finds a hint by name.
+    pub fn from_name(name: &str) -> Option<Hint<'static>> {
+        REGISTRY
+            .iter()
+            .find(|(hint_name, _)| *hint_name == name)
+            .map(|(_, hint)| *hint)
+    }
+
+    /// The names of all the hints, as given in metadata.
+    pub fn names() -> Vec<&'static str> {
+        REGISTRY.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Generates a string of the hinted format. A pattern is parsed anew each
+    /// time, so that callers generating many strings should parse it once,
+    /// with [`Pattern::parse`], instead.
+    ///
+    /// Panics if the hint is a pattern which isn't valid.
+    pub fn fuzz<R: Rng + ?Sized>(self, rng: &mut R) -> String {
+        match self {
+            Hint::Email => format!("{}@{}", fuzz_label(rng), fuzz_hostname(rng)),
+            Hint::Hostname => fuzz_hostname(rng),
+            Hint::Ipv4 => format!(
+                "{}.{}.{}.{}",
+                rng.gen::<u8>(),
+                rng.gen::<u8>(),
+                rng.gen::<u8>(),
+                rng.gen::<u8>()
+            ),
+            Hint::Ipv6 => fuzz_ipv6(rng),
+            Hint::Uri => fuzz_uri(rng),
+            Hint::Uuid => fuzz_uuid(rng),
+            Hint::Pattern(source) => Pattern::parse(source).unwrap().fuzz(rng),
+        }
+    }
+}
+
+/// The hint in a schema's metadata, if it has one: `Some(Err(..))` with the
+/// hint as given when it isn't a known one.
+pub fn of(extra: &HashMap<String, Value>) -> Option<Result<Hint<'_>, &Value>> {
+    let hint = extra.get("metadata")?.get(KEY)?;
+    Some(match hint {
+        Value::String(name) => Hint::from_name(name).ok_or(hint),
+        Value::Object(obj) if obj.len() == 1 => match obj.get("pattern") {
+            Some(Value::String(source)) => Ok(Hint::Pattern(source)),
+            _ => Err(hint),
+        },
+        _ => Err(hint),
+    })
+}
+
+/// The patterns hinted at throughout a schema, including its definitions,
+/// each with the schema path it's at.
+pub fn patterns(root: &Schema) -> Vec<(String, &str)> {
+    let mut out = Vec::new();
+    patterns_at(root, &mut Vec::new(), &mut out);
+    for (def, sub_schema) in root.definitions().iter().flatten() {
+        let mut path = vec!["definitions".to_owned(), def.clone()];
+        patterns_at(sub_schema, &mut path, &mut out);
+    }
+
+    out
+}
+
+fn patterns_at<'s>(schema: &'s Schema, path: &mut Vec<String>, out: &mut Vec<(String, &'s str)>) {
+    if let Some(Ok(Hint::Pattern(source))) = of(schema.extra()) {
+        out.push((Pointer(path.clone()).to_string(), source));
+    }
+
+    match schema.form() {
+        Form::Elements(sub_schema) => {
+            path.push("elements".to_owned());
+            patterns_at(sub_schema, path, out);
+            path.pop();
+        }
+        Form::Values(sub_schema) => {
+            path.push("values".to_owned());
+            patterns_at(sub_schema, path, out);
+            path.pop();
+        }
+        Form::Properties {
+            required, optional, ..
+        } => {
+            for &(token, props) in &[("properties", required), ("optionalProperties", optional)] {
+                for (k, sub_schema) in props {
+                    path.extend(vec![token.to_owned(), k.clone()]);
+                    patterns_at(sub_schema, path, out);
+                    path.truncate(path.len() - 2);
+                }
+            }
+        }
+        Form::Discriminator(_, mapping) => {
+            for (tag_val, sub_schema) in mapping {
+                path.extend(vec![
+                    "discriminator".to_owned(),
+                    "mapping".to_owned(),
+                    tag_val.clone(),
+                ]);
+                patterns_at(sub_schema, path, out);
+                path.truncate(path.len() - 3);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A hostname label: lowercase letters and digits, with the odd hyphen within.
+fn fuzz_label<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let len = rng.gen_range(1, 11);
+    (0..len)
+        .map(|i| {
+            if i > 0 && i < len - 1 && rng.gen_bool(0.1) {
+                '-'
+            } else {
+                char::from(*LOWERCASE_ALPHANUMERIC.choose(rng).unwrap())
+            }
+        })
+        .collect()
+}
+
+fn fuzz_hostname<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let mut labels: Vec<_> = (0..rng.gen_range(1, 4)).map(|_| fuzz_label(rng)).collect();
+    labels.push(TLDS.choose(rng).unwrap().to_string());
+    labels.join(".")
+}
+
+/// An IPv6 address, with its longest run of zero groups, if any, written as
+/// `::` as RFC 5952 recommends. Zero groups are made likelier than chance,
+/// so that runs of them come up.
+fn fuzz_ipv6<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let groups: Vec<u16> = (0..8)
+        .map(|_| if rng.gen_bool(0.3) { 0 } else { rng.gen() })
+        .collect();
+
+    // The start and length of the longest run of at least two zero groups.
+    let mut run = (0, 0);
+    let mut i = 0;
+    while i < groups.len() {
+        let len = groups[i..].iter().take_while(|&&group| group == 0).count();
+        if len >= 2 && len > run.1 {
+            run = (i, len);
+        }
+        i += len.max(1);
+    }
+
+    let hex = |groups: &[u16]| {
+        groups
+            .iter()
+            .map(|group| format!("{:x}", group))
+            .collect::<Vec<_>>()
+            .join(":")
+    };
+
+    match run {
+        (_, 0) => hex(&groups),
+        (start, len) => format!("{}::{}", hex(&groups[..start]), hex(&groups[start + len..])),
+    }
+}
+
+fn fuzz_uri<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let mut uri = format!(
+        "{}://{}",
+        ["http", "https"].choose(rng).unwrap(),
+        fuzz_hostname(rng)
+    );
+
+    for _ in 0..rng.gen_range(0, 4) {
+        uri.push('/');
+        uri.push_str(&fuzz_label(rng));
+    }
+
+    if rng.gen_bool(0.3) {
+        uri.push_str(&format!("?{}={}", fuzz_label(rng), fuzz_label(rng)));
+    }
+
+    uri
+}
+
+/// A random (version 4) UUID.
+fn fuzz_uuid<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,73 @@
+//! Strings which look identical, but which are encoded differently.
+//!
+//! Each generated string is one of a small set of words, so that the same
+//! word is generated many times over. Each time, every character of the word
+//! may be swapped for a lookalike from another script, and every accented
+//! character may be either precomposed (NFC) or decomposed (NFD).
+
+use rand::seq::SliceRandom;
+
+const WORDS: &[&str] = &[
+    "admin",
+    "apple",
+    "café",
+    "josé",
+    "naïve",
+    "paypal",
+    "résumé",
+    "secure",
+    "señor",
+    "société",
+];
+
+/// Latin characters and their Cyrillic lookalikes.
+const LOOKALIKES: &[(char, char)] = &[
+    ('a', '\u{430}'),
+    ('c', '\u{441}'),
+    ('e', '\u{435}'),
+    ('i', '\u{456}'),
+    ('j', '\u{458}'),
+    ('o', '\u{43e}'),
+    ('p', '\u{440}'),
+    ('s', '\u{455}'),
+    ('x', '\u{445}'),
+    ('y', '\u{443}'),
+];
+
+/// Precomposed characters, and their base character and combining mark.
+const DECOMPOSITIONS: &[(char, char, char)] = &[
+    ('é', 'e', '\u{301}'),
+    ('ï', 'i', '\u{308}'),
+    ('ñ', 'n', '\u{303}'),
+];
+
+/// The lookalike from another script of a character, if it has one.
+pub fn lookalike(c: char) -> Option<char> {
+    LOOKALIKES
+        .iter()
+        .find(|(latin, _)| *latin == c)
+        .map(|(_, lookalike)| *lookalike)
+}
+
+pub fn fuzz_homoglyph<R: rand::Rng + ?Sized>(rng: &mut R) -> String {
+    let mut out = String::new();
+
+    for c in WORDS.choose(rng).unwrap().chars() {
+        let decomposed = DECOMPOSITIONS
+            .iter()
+            .find(|(composed, _, _)| *composed == c);
+        let (base, mark) = match decomposed {
+            Some((_, base, mark)) if rng.gen() => (*base, Some(*mark)),
+            _ => (c, None),
+        };
+
+        match lookalike(base) {
+            Some(lookalike) if rng.gen_bool(0.3) => out.push(lookalike),
+            _ => out.push(base),
+        }
+
+        out.extend(mark);
+    }
+
+    out
+}
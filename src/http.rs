@@ -0,0 +1,587 @@
+//! Sending documents to a service under test with `ureq`, and just enough
+//! HTTP/1.1 to serve them to clients over plain TCP.
+
+use failure::{bail, format_err, Error};
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait to connect, send, or hear back, before giving up.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A URL to send requests to.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl Endpoint {
+    /// Parses an `http://` or `https://` URL.
+    pub fn parse(url: &str) -> Result<Self, Error> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            bail!("not an http:// or https:// URL: {}", url);
+        }
+
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(TIMEOUT)
+            .timeout_read(TIMEOUT)
+            .timeout_write(TIMEOUT)
+            .build();
+
+        // Requests are only built to send them, so this checks the URL once,
+        // rather than with every request.
+        if let Err(err) = agent.post(url).request_url() {
+            bail!("invalid URL {}: {}", url, err);
+        }
+
+        Ok(Endpoint {
+            url: url.to_owned(),
+            agent,
+        })
+    }
+
+    /// Sends a body with a POST request, and returns the status code of the
+    /// response. A `Content-Type` among the headers replaces `content_type`.
+    pub fn post(
+        &self,
+        content_type: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<u16, Error> {
+        let mut request = self.agent.post(&self.url);
+        if !headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        {
+            request = request.set("Content-Type", content_type);
+        }
+        for (name, val) in headers {
+            request = request.set(name, val);
+        }
+
+        // Only the status matters, so the body of the response is left unread.
+        match request.send_bytes(body) {
+            Ok(response) => Ok(response.status()),
+            Err(ureq::Error::Status(status, _)) => Ok(status),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Parses a header given as `Name: value`.
+///
+/// Names must be tokens, and values may not hold control characters other
+/// than tabs, so that a header can't end early and smuggle another header, or
+/// a request, in after it.
+pub fn parse_header(s: &str) -> Result<(String, String), Error> {
+    let (name, val) = match s.find(':') {
+        Some(i) if i > 0 => (trim_whitespace(&s[..i]), trim_whitespace(&s[i + 1..])),
+        _ => bail!("header must be given as Name: value, not: {:?}", s),
+    };
+
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        bail!("invalid header name {:?}: {:?}", name, s);
+    }
+
+    if val.chars().any(|c| c.is_control() && c != '\t') {
+        bail!("header value may not hold control characters: {:?}", s);
+    }
+
+    Ok((name.to_owned(), val.to_owned()))
+}
+
+/// Trims the spaces and tabs, but not the line breaks, around a header's
+/// name or value.
+fn trim_whitespace(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+/// Whether a byte may be part of a token (RFC 7230, section 3.2.6), as
+/// header names are.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// The status, content type, and body of a response.
+pub type Response = (u16, &'static str, Vec<u8>);
+
+/// A request's method and target, and where to send the response to it.
+type Request = (String, String, mpsc::Sender<Response>);
+
+/// How many connections are served at once. Any more wait to be accepted
+/// until one of those is done with.
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long a client has to send the request line and headers, in all.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many bytes the request line and headers may take up, in all.
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+
+/// Serves requests for as long as the listener accepts them. `respond` is
+/// given the method and target of each request, and gives the response.
+///
+/// Each connection is read from and written to on a thread of its own, so a
+/// slow client only holds up itself, but `respond` is called on this thread,
+/// for one request at a time, in the order they're read. A client which sends
+/// something other than HTTP, hangs up, or takes too long, only loses its own
+/// connection.
+pub fn serve<F>(listener: TcpListener, mut respond: F) -> Result<(), Error>
+where
+    F: FnMut(&str, &str) -> Response,
+{
+    let (tx, rx) = mpsc::channel::<Request>();
+    let slots = Arc::new(Slots::default());
+    thread::spawn(move || loop {
+        let slot = Slots::acquire(&slots);
+        if let Ok((stream, _)) = listener.accept() {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let _ = answer(stream, &tx);
+                drop(slot);
+            });
+        }
+    });
+
+    for (method, target, reply) in rx {
+        let _ = reply.send(respond(&method, &target));
+    }
+
+    Ok(())
+}
+
+/// How many connections are being served.
+#[derive(Default)]
+struct Slots {
+    open: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// A connection being served, which frees its slot when dropped.
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    /// Waits for fewer than [`MAX_CONNECTIONS`] connections to be open, and
+    /// takes a slot for another.
+    fn acquire(slots: &Arc<Slots>) -> Slot {
+        let open = slots.open.lock().unwrap();
+        let mut open = slots
+            .freed
+            .wait_while(open, |open| *open >= MAX_CONNECTIONS)
+            .unwrap();
+        *open += 1;
+        Slot(slots.clone())
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *self.0.open.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+fn answer(mut stream: TcpStream, tx: &mpsc::Sender<Request>) -> Result<(), Error> {
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    // The timeout is of each read, so it's shortened before each line, to
+    // what's left of the time the whole head may take.
+    let deadline = Instant::now() + HEAD_TIMEOUT;
+    let mut reader = io::BufReader::new(stream.try_clone()?.take(MAX_HEAD_LEN));
+    let mut read_line = || -> Result<String, Error> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left == Duration::from_secs(0) {
+            bail!("the request took too long to send");
+        }
+
+        stream.set_read_timeout(Some(left))?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        // A line cut short is either all a client sent before hanging up, or
+        // as much of the head as may be read.
+        if !line.is_empty() && !line.ends_with('\n') {
+            bail!("the request's head was cut short, or too long");
+        }
+
+        Ok(line)
+    };
+
+    let request_line = read_line()?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_owned(), target.to_owned()),
+        _ => bail!("not an HTTP request: {:?}", request_line),
+    };
+
+    // The headers don't matter, and requests are expected not to have bodies,
+    // so everything up to the blank line after the headers is skipped.
+    while !read_line()?.trim_end().is_empty() {}
+
+    let (reply_tx, reply_rx) = mpsc::channel();
+    tx.send((method, target, reply_tx))
+        .map_err(|_| format_err!("the server has stopped"))?;
+    let (status, content_type, body) = reply_rx
+        .recv()
+        .map_err(|_| format_err!("the server has stopped"))?;
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// The reason phrase for the statuses [`serve`] is expected to respond with.
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+/// The value of a parameter in a request target's query string, if it has
+/// one. Names and values are percent-decoded, with `+` as a space, as forms
+/// encode them.
+pub fn query_param(target: &str, name: &str) -> Option<String> {
+    let query = &target[target.find('?')? + 1..];
+    query.split('&').find_map(|param| {
+        let mut parts = param.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(k), val) if decode(k) == name => Some(decode(val.unwrap_or(""))),
+            _ => None,
+        }
+    })
+}
+
+/// Percent-decodes part of a query string. Bytes which aren't UTF-8 are
+/// replaced.
+fn decode(s: &str) -> String {
+    percent_decode_str(&s.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Sends documents to an endpoint from a number of threads at once, and at
+/// most a given number of them a second.
+#[derive(Debug)]
+pub struct Poster {
+    tx: Option<mpsc::SyncSender<Vec<u8>>>,
+    workers: Vec<thread::JoinHandle<Tally>>,
+
+    /// How long to leave between sending requests, if there is a rate to
+    /// keep to, and when the next one may be sent.
+    interval: Option<Duration>,
+    next: Instant,
+}
+
+impl Poster {
+    /// Starts `concurrency` threads to send requests, with `headers` on each,
+    /// at most `rate` of them a second if given. Bodies are of `content_type`,
+    /// unless the headers say otherwise.
+    pub fn new(
+        endpoint: Endpoint,
+        content_type: &'static str,
+        headers: Vec<(String, String)>,
+        concurrency: usize,
+        rate: Option<f64>,
+    ) -> Result<Self, Error> {
+        if concurrency == 0 {
+            bail!("concurrency must be at least one");
+        }
+
+        let interval = match rate {
+            Some(rate) if rate > 0.0 && rate.is_finite() => {
+                Some(Duration::from_secs_f64(1.0 / rate))
+            }
+            Some(rate) => bail!(
+                "rate must be a positive number of requests a second: {}",
+                rate
+            ),
+            None => None,
+        };
+
+        // Each thread has at most one body waiting for it, so that generation
+        // waits for requests to be sent rather than getting far ahead.
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(concurrency);
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..concurrency)
+            .map(|_| {
+                let (endpoint, headers, rx) = (endpoint.clone(), headers.clone(), rx.clone());
+                thread::spawn(move || {
+                    let mut tally = Tally::default();
+                    loop {
+                        let body = match rx.lock().unwrap().recv() {
+                            Ok(body) => body,
+                            Err(_) => return tally,
+                        };
+
+                        tally.record(endpoint.post(content_type, &headers, &body));
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Poster {
+            tx: Some(tx),
+            workers,
+            interval,
+            next: Instant::now(),
+        })
+    }
+
+    /// Sends a body, once the rate allows and a thread is free to send it.
+    pub fn send(&mut self, body: Vec<u8>) -> Result<(), Error> {
+        if let Some(interval) = self.interval {
+            let now = Instant::now();
+            if self.next > now {
+                thread::sleep(self.next - now);
+            }
+
+            self.next = self.next.max(now) + interval;
+        }
+
+        self.tx
+            .as_ref()
+            .unwrap()
+            .send(body)
+            .map_err(|_| format_err!("every thread sending requests has stopped"))
+    }
+
+    /// Waits for every request to be sent, and tallies up their responses.
+    pub fn finish(mut self) -> Tally {
+        self.tx = None;
+        let mut total = Tally::default();
+        for worker in self.workers.drain(..) {
+            let tally = worker
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (status, n) in tally.statuses {
+                *total.statuses.entry(status).or_insert(0) += n;
+            }
+
+            total.errors += tally.errors;
+            if total.first_error.is_none() {
+                total.first_error = tally.first_error;
+            }
+        }
+
+        total
+    }
+}
+
+/// How many requests got each status code in response, and how many got no
+/// response at all.
+#[derive(Debug, Default)]
+pub struct Tally {
+    statuses: BTreeMap<u16, u64>,
+    errors: u64,
+    first_error: Option<String>,
+}
+
+impl Tally {
+    fn record(&mut self, result: Result<u16, Error>) {
+        match result {
+            Ok(status) => *self.statuses.entry(status).or_insert(0) += 1,
+            Err(err) => {
+                self.errors += 1;
+                self.first_error.get_or_insert_with(|| err.to_string());
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let statuses: Map<_, _> = self
+            .statuses
+            .iter()
+            .map(|(status, n)| (status.to_string(), (*n).into()))
+            .collect();
+
+        json!({
+            "sent": self.statuses.values().sum::<u64>() + self.errors,
+            "statuses": statuses,
+            "errors": self.errors,
+            "firstError": self.first_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn parse_urls() {
+        assert!(Endpoint::parse("http://[::1]:8080/users").is_ok());
+        assert!(Endpoint::parse("https://example.com?x=1").is_ok());
+        assert!(Endpoint::parse("ftp://example.com/").is_err());
+        assert!(Endpoint::parse("http://").is_err());
+        assert!(Endpoint::parse("http://example.com:port/").is_err());
+    }
+
+    #[test]
+    fn parse_headers() {
+        assert_eq!(
+            parse_header("Authorization:  Bearer a\tb ").unwrap(),
+            ("Authorization".to_owned(), "Bearer a\tb".to_owned())
+        );
+        assert_eq!(
+            parse_header("X-Empty:").unwrap(),
+            ("X-Empty".to_owned(), "".to_owned())
+        );
+
+        for header in &[
+            ": value",
+            "no colon",
+            "X-A: b\r\nX-Injected: c",
+            "X-A: b\nc",
+            "X-A: b\u{7f}",
+            "X A: b",
+            "X-A\r\n: b",
+            "X-\u{e9}: b",
+        ] {
+            assert!(parse_header(header).is_err(), "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn post() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/users", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n{}") {
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0, "the request ended early");
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            stream
+                .write_all(b"HTTP/1.1 422 Unprocessable Entity\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let headers = [parse_header("X-Test: 1").unwrap()];
+        let status = Endpoint::parse(&url)
+            .unwrap()
+            .post("application/json", &headers, b"{}")
+            .unwrap();
+        assert_eq!(status, 422);
+
+        let request = server.join().unwrap().to_ascii_lowercase();
+        assert!(
+            request.starts_with("post /users http/1.1\r\n"),
+            "{}",
+            request
+        );
+        assert!(request.contains("\r\ncontent-type: application/json\r\n"));
+        assert!(request.contains("\r\nx-test: 1\r\n"));
+    }
+
+    #[test]
+    fn query_params() {
+        assert_eq!(query_param("/?seed=7", "seed").as_deref(), Some("7"));
+        assert_eq!(
+            query_param("/users?a=1&seed=%31", "seed").as_deref(),
+            Some("1")
+        );
+        assert_eq!(
+            query_param("/?se%65d=a+b%2B", "seed").as_deref(),
+            Some("a b+")
+        );
+        assert_eq!(query_param("/?seed", "seed").as_deref(), Some(""));
+        assert_eq!(query_param("/?seeds=1", "seed"), None);
+        assert_eq!(query_param("/seed=1", "seed"), None);
+    }
+
+    /// Sends a request to a server, and reads its whole response.
+    fn get(addr: std::net::SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serve_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            serve(listener, |method, target| {
+                let body = format!("{} {:?}", method, query_param(target, "seed"));
+                (200, "text/plain", body.into_bytes())
+            })
+        });
+
+        // A client which never finishes its request doesn't hold up others.
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+
+        let clients: Vec<_> = (0..MAX_CONNECTIONS * 2)
+            .map(|i| {
+                thread::spawn(move || {
+                    let request = format!("GET /?seed=%3{} HTTP/1.1\r\nHost: x\r\n\r\n", i % 10);
+                    (i, get(addr, request.as_bytes()))
+                })
+            })
+            .collect();
+
+        for client in clients {
+            let (i, response) = client.join().unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+            assert!(
+                response.contains("\r\nContent-Length: 13\r\n"),
+                "{}",
+                response
+            );
+            assert!(
+                response.ends_with(&format!("\r\n\r\nGET Some(\"{}\")", i % 10)),
+                "{}",
+                response
+            );
+        }
+
+        // Nor does one which sends something other than HTTP, or too much of
+        // it.
+        assert_eq!(get(addr, b"\r\n"), "");
+        let mut long = b"GET / HTTP/1.1\r\nX: ".to_vec();
+        long.resize(MAX_HEAD_LEN as usize * 2, b'a');
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let _ = stream.write_all(&long);
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert_eq!(response, "");
+
+        let response = get(addr, b"POST / HTTP/1.1\r\n\r\n");
+        assert!(response.ends_with("POST None"), "{}", response);
+        drop(idle);
+    }
+}
@@ -0,0 +1,24 @@
+//! Reading documents in.
+
+use serde_json::Value;
+
+/// The ASCII record separator, which begins each text in an RFC 7464 JSON
+/// text sequence.
+const RS: u8 = 0x1e;
+
+/// Parses the documents in a file.
+///
+/// If the file is an RFC 7464 JSON text sequence (that is, if it begins with
+/// a record separator), each text in the sequence is a document. Otherwise, the
+/// entire file is a single document.
+pub fn parse_documents(bytes: &[u8]) -> Result<Vec<Value>, serde_json::Error> {
+    if bytes.first() != Some(&RS) {
+        return Ok(vec![serde_json::from_slice(bytes)?]);
+    }
+
+    bytes
+        .split(|b| *b == RS)
+        .skip(1)
+        .map(serde_json::from_slice)
+        .collect()
+}
@@ -0,0 +1,228 @@
+//! Documents which deliberately break their schema, for testing that
+//! validators reject what they should.
+//!
+//! A valid document is broken in exactly one place, in one of the ways listed
+//! by [`InvalidKind`], so that it is certain not to satisfy the schema, and it
+//! is clear why not.
+
+use crate::config::InvalidKind;
+use crate::mutations::{self, Site};
+use crate::pointer::Pointer;
+use chrono::DateTime;
+use jddf::schema::{Form, Type};
+use jddf::Schema;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::{json, Value};
+
+/// Strings which look something like timestamps, but are not in RFC 3339
+/// format. Any which chrono turns out to accept are left out.
+const BAD_TIMESTAMPS: &[&str] = &[
+    "",
+    "not a timestamp",
+    "2019-01-01",
+    "2019-13-01T00:00:00Z",
+    "2019-02-30T00:00:00Z",
+    "2019-01-01T25:00:00Z",
+    "2019-01-01T00:00:00",
+    "2019-01-01T00:00:00+0000",
+    "01/01/2019 00:00:00",
+    "1546300800",
+];
+
+/// Where, and how, a document was broken.
+#[derive(Debug)]
+pub struct Violation {
+    pub kind: InvalidKind,
+
+    /// The tokens of the path to the broken value within the document.
+    pub instance_path: Vec<String>,
+
+    /// The tokens of the path to the part of the schema it breaks.
+    pub schema_path: Vec<String>,
+}
+
+impl Violation {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind.name(),
+            "instancePath": Pointer(self.instance_path.clone()).to_string(),
+            "schemaPath": Pointer(self.schema_path.clone()).to_string(),
+        })
+    }
+}
+
+/// Breaks a document, which must satisfy the root schema, in one of the given
+/// ways, chosen at random among every place it can be broken in those ways.
+///
+/// Returns `None`, leaving the document as it was, if it can't be broken in
+/// any of those ways. A schema of the empty form accepts anything, for
+/// instance.
+pub fn break_one<R: Rng + ?Sized>(
+    root: &Schema,
+    doc: &mut Value,
+    kinds: &[InvalidKind],
+    rng: &mut R,
+) -> Option<Violation> {
+    let sites = mutations::sites(root, doc);
+
+    let mut candidates = Vec::new();
+    for (i, site) in sites.iter().enumerate() {
+        let val = doc.pointer(&Pointer(site.instance_path.clone()).to_string())?;
+        for kind in kinds {
+            if applies(*kind, site, val) {
+                candidates.push((i, *kind));
+            }
+        }
+    }
+
+    let &(i, kind) = candidates.choose(rng)?;
+    let site = &sites[i];
+    let val = doc
+        .pointer_mut(&Pointer(site.instance_path.clone()).to_string())
+        .unwrap();
+
+    let mut violation = Violation {
+        kind,
+        instance_path: site.instance_path.clone(),
+        schema_path: site.schema_path.clone(),
+    };
+
+    match (kind, site.schema.form()) {
+        (InvalidKind::WrongType, form) => {
+            let wrong: Vec<_> = vec![
+                Value::Null,
+                Value::Bool(true),
+                json!(0),
+                json!(""),
+                json!([]),
+                json!({}),
+            ]
+            .into_iter()
+            .filter(|wrong| !same_type(wrong, val))
+            .collect();
+            *val = wrong.choose(rng).unwrap().clone();
+
+            violation.schema_path.push(
+                match form {
+                    Form::Type(_) => "type",
+                    Form::Enum(_) => "enum",
+                    Form::Elements(_) => "elements",
+                    Form::Values(_) => "values",
+                    Form::Discriminator(..) => "discriminator",
+                    _ => "properties",
+                }
+                .to_owned(),
+            );
+        }
+        (InvalidKind::MissingRequired, Form::Properties { required, .. }) => {
+            let obj = val.as_object_mut().unwrap();
+            let mut keys: Vec<_> = required.keys().filter(|k| obj.contains_key(*k)).collect();
+            keys.sort();
+
+            let k = *keys.choose(rng).unwrap();
+            obj.remove(k);
+            violation
+                .schema_path
+                .extend(vec!["properties".to_owned(), k.clone()]);
+        }
+        (InvalidKind::MissingRequired, Form::Discriminator(tag, _)) => {
+            val.as_object_mut().unwrap().remove(tag);
+            violation
+                .schema_path
+                .extend(vec!["discriminator".to_owned(), "tag".to_owned()]);
+        }
+        (
+            InvalidKind::ExtraProperty,
+            Form::Properties {
+                required, optional, ..
+            },
+        ) => {
+            let obj = val.as_object_mut().unwrap();
+            let mut k = "extra".to_owned();
+            while required.contains_key(&k) || optional.contains_key(&k) || obj.contains_key(&k) {
+                k = format!("extra{}", rng.gen::<u32>());
+            }
+
+            obj.insert(k.clone(), Value::Null);
+            violation.instance_path.push(k);
+        }
+        (InvalidKind::BadTimestamp, _) => {
+            let bad: Vec<_> = BAD_TIMESTAMPS
+                .iter()
+                .filter(|bad| DateTime::parse_from_rfc3339(bad).is_err())
+                .collect();
+            *val = (**bad.choose(rng).unwrap()).into();
+            violation.schema_path.push("type".to_owned());
+        }
+        (InvalidKind::OutOfRange, Form::Type(typ)) => {
+            let (min, max) = crate::int_range(typ).unwrap();
+            *val = if rng.gen() { min - 1 } else { max + 1 }.into();
+            violation.schema_path.push("type".to_owned());
+        }
+        (InvalidKind::BadEnum, Form::Enum(vals)) => {
+            let s = val.as_str().unwrap().to_owned();
+            let mut bad = crate::keys::variant(rng, &s);
+            while vals.contains(&bad) {
+                bad = crate::keys::variant(rng, &bad);
+            }
+
+            *val = bad.into();
+            violation.schema_path.push("enum".to_owned());
+        }
+        (InvalidKind::BadDiscriminator, Form::Discriminator(tag, mapping)) => {
+            let obj = val.as_object_mut().unwrap();
+            let s = obj[tag].as_str().unwrap().to_owned();
+            let mut bad = crate::keys::variant(rng, &s);
+            while mapping.contains_key(&bad) {
+                bad = crate::keys::variant(rng, &bad);
+            }
+
+            obj.insert(tag.clone(), bad.into());
+            violation.instance_path.push(tag.clone());
+            violation
+                .schema_path
+                .extend(vec!["discriminator".to_owned(), "mapping".to_owned()]);
+        }
+        _ => unreachable!("{} applied where it does not apply", kind.name()),
+    }
+
+    Some(violation)
+}
+
+/// Whether a document can be broken in some way at a site.
+fn applies(kind: InvalidKind, site: &Site, val: &Value) -> bool {
+    match (kind, site.schema.form(), val) {
+        (InvalidKind::WrongType, Form::Empty, _) => false,
+        (InvalidKind::WrongType, _, _) => true,
+        (InvalidKind::MissingRequired, Form::Properties { required, .. }, Value::Object(obj)) => {
+            required.keys().any(|k| obj.contains_key(k))
+        }
+        (InvalidKind::MissingRequired, Form::Discriminator(tag, _), Value::Object(obj)) => {
+            obj.contains_key(tag)
+        }
+        (
+            InvalidKind::ExtraProperty,
+            Form::Properties {
+                allow_additional, ..
+            },
+            Value::Object(_),
+        ) => !allow_additional,
+        (InvalidKind::BadTimestamp, Form::Type(Type::Timestamp), Value::String(s)) => {
+            DateTime::parse_from_rfc3339(s).is_ok()
+        }
+        (InvalidKind::OutOfRange, Form::Type(typ), Value::Number(_)) => {
+            crate::int_range(typ).is_some()
+        }
+        (InvalidKind::BadEnum, Form::Enum(_), Value::String(_)) => true,
+        (InvalidKind::BadDiscriminator, Form::Discriminator(tag, _), Value::Object(obj)) => {
+            obj.get(tag).is_some_and(Value::is_string)
+        }
+        _ => false,
+    }
+}
+
+/// Whether two values are of the same JSON type.
+fn same_type(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
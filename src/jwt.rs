@@ -0,0 +1,183 @@
+//! Structurally valid JSON Web Tokens, for string fields which hold them.
+//!
+//! APIs which authenticate requests tend to reject a malformed token before
+//! doing anything else with a request, so random strings never get past them.
+//! These tokens are three base64url-encoded segments, with a header and claims
+//! which parse as JSON. They can be signed with HS256 and a test key, so that
+//! a server configured with the same key accepts them.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::json;
+use sha2::Sha256;
+
+/// Generates a JWT, signed with HS256 under `key` if given, or with a random
+/// signature otherwise.
+pub fn fuzz_jwt<R: Rng + ?Sized>(rng: &mut R, key: Option<&str>) -> String {
+    let iat = rng.gen_range(1_500_000_000i64, 2_000_000_000);
+    let sub: String = (0..rng.gen_range(8, 17))
+        .map(|_| char::from(*crate::ALPHANUMERIC.choose(rng).unwrap()))
+        .collect();
+
+    let header = json!({ "alg": "HS256", "typ": "JWT" });
+    let claims = json!({
+        "iss": "jddf-fuzz",
+        "sub": sub,
+        "iat": iat,
+        "exp": iat + rng.gen_range(60, 86_400),
+        "jti": base64url(&rng.gen::<[u8; 16]>()),
+    });
+
+    let signing_input = format!(
+        "{}.{}",
+        base64url(header.to_string().as_bytes()),
+        base64url(claims.to_string().as_bytes())
+    );
+
+    let signature = match key {
+        Some(key) => hmac_sha256(key.as_bytes(), signing_input.as_bytes()),
+        None => rng.gen(),
+    };
+
+    format!("{}.{}", signing_input, base64url(&signature))
+}
+
+/// Encodes bytes as base64url, without padding, as JWTs are.
+fn base64url(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Computes an HMAC (RFC 2104) using SHA-256.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    // HMAC takes keys of any length, hashing those longer than a block.
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sha2::Digest;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        // FIPS 180-2, appendix B, and the empty message.
+        let vectors: &[(&[u8], &str)] = &[
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+
+        for (message, digest) in vectors {
+            assert_eq!(hex(&Sha256::digest(message)), *digest);
+        }
+
+        let million_a = vec![b'a'; 1_000_000];
+        assert_eq!(
+            hex(&Sha256::digest(&million_a)),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn hmac_sha256_vectors() {
+        // RFC 4231, section 4, test cases 1 to 4, 6 and 7. Test case 5
+        // truncates its output.
+        let vectors: &[(Vec<u8>, &[u8], &str)] = &[
+            (
+                vec![0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                vec![0xaa; 20],
+                &[0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                (1..=25).collect(),
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than \
+                  block-size data. The key needs to be hashed before being used by the \
+                  HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+
+        for (key, message, mac) in vectors {
+            assert_eq!(hex(&hmac_sha256(key, message)), *mac);
+        }
+    }
+
+    #[test]
+    fn known_token() {
+        // The example token from jwt.io.
+        let signing_input = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+                             eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ";
+
+        assert_eq!(
+            base64url(br#"{"sub":"1234567890","name":"John Doe","iat":1516239022}"#),
+            signing_input.split('.').nth(1).unwrap()
+        );
+        assert_eq!(
+            base64url(&hmac_sha256(
+                b"your-256-bit-secret",
+                signing_input.as_bytes()
+            )),
+            "SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c"
+        );
+    }
+
+    #[test]
+    fn signed_tokens() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        for _ in 0..16 {
+            let token = fuzz_jwt(&mut rng, Some("test-key"));
+            let (signing_input, signature) = token.rsplit_once('.').unwrap();
+            assert_eq!(
+                signature,
+                base64url(&hmac_sha256(b"test-key", signing_input.as_bytes()))
+            );
+
+            for segment in signing_input.split('.') {
+                let json = URL_SAFE_NO_PAD.decode(segment).unwrap();
+                assert!(serde_json::from_slice::<serde_json::Value>(&json)
+                    .unwrap()
+                    .is_object());
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Declared property names, changed in ways which consumers that match names
+//! case-insensitively, or after trimming whitespace, might not notice.
+
+use crate::homoglyph;
+use crate::mutations;
+use crate::pointer::Pointer;
+use jddf::schema::Form;
+use jddf::Schema;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::Value;
+
+/// Characters added before or after a property name.
+const PADDING: &[char] = &[' ', '\t', '\n', '\u{a0}'];
+
+/// Renames one declared property somewhere within a document, which must
+/// satisfy the root schema, to a variant of its name. A discriminator's tag
+/// counts as a declared property.
+///
+/// Returns whether there was a declared property to rename.
+pub fn rename_one<R: Rng + ?Sized>(root: &Schema, doc: &mut Value, rng: &mut R) -> bool {
+    let mut candidates = Vec::new();
+    for site in mutations::sites(root, doc) {
+        let obj = match doc.pointer(&Pointer(site.instance_path.clone()).to_string()) {
+            Some(Value::Object(obj)) => obj,
+            _ => continue,
+        };
+
+        let mut keys: Vec<_> = match site.schema.form() {
+            Form::Properties {
+                required, optional, ..
+            } => obj
+                .keys()
+                .filter(|k| required.contains_key(*k) || optional.contains_key(*k))
+                .cloned()
+                .collect(),
+            Form::Discriminator(tag, _) if obj.contains_key(tag) => vec![tag.clone()],
+            _ => continue,
+        };
+
+        keys.sort();
+        candidates.extend(keys.into_iter().map(|k| (site.instance_path.clone(), k)));
+    }
+
+    let (path, k) = match candidates.choose(rng) {
+        Some(candidate) => candidate.clone(),
+        None => return false,
+    };
+
+    let pointer = Pointer(path).to_string();
+    let obj = doc.pointer_mut(&pointer).unwrap().as_object_mut().unwrap();
+
+    let mut name = variant(rng, &k);
+    while obj.contains_key(&name) {
+        name = variant(rng, &k);
+    }
+
+    let val = obj.remove(&k).unwrap();
+    obj.insert(name, val);
+    true
+}
+
+/// Generates a variant of a name, which differs from it only in case, in
+/// surrounding whitespace, or in having a lookalike from another script in
+/// place of one of its characters.
+pub fn variant<R: Rng + ?Sized>(rng: &mut R, name: &str) -> String {
+    let variants = match rng.gen_range(0, 3) {
+        0 => {
+            let mut first_swapped = String::new();
+            for (i, c) in name.chars().enumerate() {
+                match i {
+                    0 if c.is_lowercase() => first_swapped.extend(c.to_uppercase()),
+                    0 => first_swapped.extend(c.to_lowercase()),
+                    _ => first_swapped.push(c),
+                }
+            }
+
+            vec![name.to_uppercase(), name.to_lowercase(), first_swapped]
+        }
+        1 => name
+            .char_indices()
+            .filter_map(|(i, c)| {
+                homoglyph::lookalike(c).map(|lookalike| {
+                    format!("{}{}{}", &name[..i], lookalike, &name[i + c.len_utf8()..])
+                })
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    let mut variants: Vec<_> = variants.into_iter().filter(|v| v != name).collect();
+    variants.dedup();
+    match variants.choose(rng) {
+        Some(variant) => variant.clone(),
+        None => {
+            // Padding always makes a difference.
+            let padding = PADDING.choose(rng).unwrap();
+            if rng.gen() {
+                format!("{}{}", padding, name)
+            } else {
+                format!("{}{}", name, padding)
+            }
+        }
+    }
+}
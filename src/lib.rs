@@ -0,0 +1,1996 @@
+//! Generating random data which satisfies a JDDF schema.
+//!
+//! [`fuzz`] generates a single value for a schema, the same way the
+//! `jddf-fuzz` command does with its default options. The command's other
+//! options are described by [`config::FuzzConfig`]. Schemas should first be
+//! parsed with [`check::parse`], or checked with [`check::schema`], since a
+//! schema jddf accepts may still be one nothing can be generated for.
+//! [`try_fuzz`] checks the schema itself, and fails rather than panics.
+//!
+//! [`typecheck::check`] uses it to find values a schema allows, but which a
+//! Rust type fails to deserialize.
+//!
+//! Everything else the command does is here too, so that it can be done from
+//! code: [`generate::run`] generates documents and writes them out, as the
+//! command does without a subcommand, and [`commands`] has a function for each
+//! subcommand.
+
+mod audit;
+#[cfg(feature = "avro")]
+mod avro;
+mod binary;
+mod branch;
+pub mod check;
+mod codegen;
+pub mod commands;
+pub mod config;
+mod corpus;
+mod dict;
+mod estimate;
+mod features;
+mod form;
+pub mod generate;
+mod giant;
+mod hints;
+mod homoglyph;
+mod http;
+mod input;
+mod invalid;
+mod jwt;
+mod keys;
+mod merge;
+pub mod mutations;
+mod output;
+mod patch;
+mod pattern;
+pub mod pointer;
+mod pool;
+mod reach;
+mod rules;
+mod shape;
+mod size;
+mod stats;
+mod stream;
+mod transform;
+mod tui;
+pub mod typecheck;
+mod verify;
+mod weights;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use config::{
+    Charset, ContainerSize, DepthLimitBehavior, DigitRange, FloatPolicy, Freeze, FuzzConfig,
+    OptionalWeights, Strategy, StringLen, TimestampPrecision, UnsupportedBehavior,
+};
+use failure::{bail, format_err, Error};
+use jddf::schema::{Form, Type};
+use jddf::Schema;
+use pointer::Pointer;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// How many documents in a row may be skipped for exceeding the maximum depth
+/// before giving up.
+const MAX_SKIPPED: usize = 1000;
+
+/// How many documents in a row may have shapes already generated, under
+/// `--novel-only`, before concluding there are no new shapes left to find.
+const MAX_STALE: usize = 1000;
+
+/// How many documents in a row may break the config's rules, even after trying
+/// to fix them, before giving up.
+const MAX_REJECTED: usize = 1000;
+
+/// The version of the algorithm documents are generated with. This changes
+/// whenever the same schema, config, and seed would generate different
+/// documents than before, so that `verify-corpus` can tell when a corpus was
+/// generated by a different version, rather than nondeterministically.
+pub(crate) const ALGORITHM_VERSION: u64 = 7;
+
+/// An exclusive upper bound on how many elements or members a container within
+/// a focused part of the document may have, unless `container_size` allows
+/// more.
+const FOCUSED_MAX_LEN: usize = 64;
+
+/// The probability that a scalar within a focused part of the document is one
+/// of the extremes of its type, such as its minimum or maximum.
+const FOCUSED_EXTREME_PROB: f64 = 0.5;
+
+/// How many random keys in a row may clash with those already taken before a
+/// key is spelled out instead.
+const KEY_ATTEMPTS: usize = 16;
+
+/// The largest number generated under `sane`.
+const SANE_MAX: i64 = 10_000;
+
+/// How far back timestamps generated under `sane` go, in seconds.
+const SANE_TIMESTAMP_SECS: i64 = 365 * 24 * 60 * 60;
+
+/// The probability that a float is infinite, or NaN, under the `special` float
+/// policy.
+const INFINITY_PROB: f64 = 0.125;
+
+/// How deeply values of a definition may be nested within one another under
+/// the maximal strategy, unless the config gives a `ref_depth`, so that
+/// recursive definitions don't grow forever.
+const MAXIMAL_REF_DEPTH: usize = 1;
+
+/// How deeply values of a definition may be nested within one another under
+/// any other strategy, unless the config gives a `ref_depth`. Without a bound,
+/// a definition whose arrays or maps refer back to it has no end, on average,
+/// and generating it overflows the stack.
+const DEFAULT_REF_DEPTH: usize = 4;
+
+/// What goes in place of the unsupported parts of a schema, under the
+/// `placeholder` unsupported behavior.
+const UNSUPPORTED_PLACEHOLDER: &str = "<unsupported>";
+
+/// Generates a value which satisfies a schema, as [`try_fuzz`] does.
+///
+/// # Panics
+///
+/// Panics if the schema is one [`check::schema`] rejects.
+pub fn fuzz<R: Rng + ?Sized>(rng: &mut R, schema: &Schema) -> Value {
+    try_fuzz(rng, schema).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Generates a value which satisfies a schema, or fails if the schema is one
+/// [`check::schema`] rejects.
+pub fn try_fuzz<R: Rng + ?Sized>(rng: &mut R, schema: &Schema) -> Result<Value, Error> {
+    check::schema(schema)?;
+
+    let config = FuzzConfig::default();
+    Ok(fuzz_schema(
+        &mut Context::new(schema, &config)?,
+        rng,
+        schema,
+    ))
+}
+
+/// How many documents have been generated, and how many in a row have been
+/// left out, while looking for the next one to write out.
+#[derive(Debug, Default)]
+pub(crate) struct Attempts {
+    attempts: u64,
+    skipped: usize,
+    rejected: usize,
+    stale: usize,
+
+    /// The shapes of the documents generated so far, under `--novel-only`.
+    shapes: HashSet<u64>,
+}
+
+/// Generates the next document to write out, leaving out any which exceed the
+/// maximum depth, break the config's rules, or have a shape already generated,
+/// as the config says to.
+///
+/// Returns `None` once, under `--novel-only`, there seem to be no more shapes
+/// left to find.
+pub(crate) fn next_document<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    attempts: &mut Attempts,
+    stats: &mut stats::Stats,
+) -> Result<Option<Value>, Error> {
+    let (root, config) = (ctx.root, ctx.config);
+    loop {
+        ctx.structure_rng = config
+            .structure_seed
+            .map(|seed| seeded_rng(&[seed, attempts.attempts]));
+        ctx.index = attempts.attempts;
+        attempts.attempts += 1;
+
+        let mut val = fuzz_schema(ctx, rng, root);
+
+        if let Some(path) = ctx.depth_limit_hit.take() {
+            match config.on_depth_limit {
+                DepthLimitBehavior::Skip if attempts.skipped < MAX_SKIPPED => {
+                    attempts.skipped += 1;
+                    stats.skipped += 1;
+                    continue;
+                }
+                DepthLimitBehavior::Skip => bail!(
+                    "{} documents in a row exceeded the maximum depth at schema path: {}",
+                    MAX_SKIPPED,
+                    path
+                ),
+                DepthLimitBehavior::Error => {
+                    bail!("maximum depth exceeded at schema path: {}", path)
+                }
+                _ => {}
+            }
+        }
+
+        // Shorter pointers go first, so that values set within what they set
+        // are kept.
+        let mut set: Vec<_> = config.set.iter().collect();
+        set.sort_by_key(|(a, _)| *a);
+        for (pointer, to) in set {
+            pointer::set(&mut val, &Pointer::parse(pointer)?, to);
+        }
+
+        if !rules::enforce(&config.rules, &mut val) {
+            if attempts.rejected < MAX_REJECTED {
+                attempts.rejected += 1;
+                stats.rejected += 1;
+                continue;
+            }
+
+            bail!(
+                "{} documents in a row broke the config's rules, which may contradict each other",
+                MAX_REJECTED
+            );
+        }
+
+        if config.novel_only && !attempts.shapes.insert(shape::fingerprint(root, &val)) {
+            if attempts.stale < MAX_STALE {
+                attempts.stale += 1;
+                stats.stale += 1;
+                continue;
+            }
+
+            return Ok(None);
+        }
+
+        attempts.skipped = 0;
+        attempts.rejected = 0;
+        attempts.stale = 0;
+        return Ok(Some(val));
+    }
+}
+
+/// Constructs an RNG which is the same on every platform for the given words,
+/// such as a seed and a document index. At most four words are used.
+pub(crate) fn seeded_rng(words: &[u64]) -> ChaChaRng {
+    let mut key = [0; 32];
+    for (chunk, word) in key.chunks_mut(8).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    ChaChaRng::from_seed(key)
+}
+
+/// Hashes a string with 64-bit FNV-1a, which, unlike the standard library's
+/// hashers, is stable across platforms and releases.
+pub(crate) fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Constructs the context documents of a run are generated in, with the parts
+/// of documents `focus` points to varied more than the rest, and the pools the
+/// config gives read.
+pub(crate) fn run_context<'a>(
+    schema: &'a Schema,
+    config: &'a FuzzConfig,
+    focus: &[Pointer],
+) -> Result<Context<'a>, Error> {
+    check::frozen(schema, config)?;
+
+    let mut ctx = Context::new(schema, config)?;
+    ctx.focus = focus.to_vec();
+    for (path, file) in &config.pool {
+        ctx.pools
+            .insert(path.clone(), pool::Pool::read(std::path::Path::new(file))?);
+    }
+
+    Ok(ctx)
+}
+
+/// Constructs the RNG for whatever isn't derived from where a value is in the
+/// schema and document, such as patches and Avro sync markers. Under a seed,
+/// this is seeded too, so that a whole run is reproducible.
+pub(crate) fn run_rng(seed: Option<u64>) -> Result<ChaChaRng, Error> {
+    Ok(match seed {
+        Some(seed) => seeded_rng(&[seed, fnv1a("run")]),
+        None => ChaChaRng::from_rng(rand::thread_rng())?,
+    })
+}
+
+/// State shared across the generation of values.
+pub(crate) struct Context<'a> {
+    root: &'a Schema,
+    config: &'a FuzzConfig,
+
+    /// The tokens of the schema path currently being generated.
+    path: Vec<String>,
+
+    /// The tokens of the instance path currently being generated.
+    instance_path: Vec<String>,
+
+    /// The index of the document being generated.
+    pub index: u64,
+
+    /// How deeply nested the value currently being generated is.
+    depth: usize,
+
+    /// The schema path at which the maximum depth was first exceeded while
+    /// generating the current value, if it has been.
+    pub depth_limit_hit: Option<String>,
+
+    /// How many values of each definition the value currently being generated
+    /// is nested within.
+    ref_depths: HashMap<String, usize>,
+
+    /// The next value of each frozen schema path's sequence.
+    sequences: HashMap<String, u64>,
+
+    /// How many values each enum or discriminator schema path has taken so
+    /// far, while cycling through them.
+    round_robin: HashMap<String, usize>,
+
+    /// How many times each optional property, and each array or map, has been
+    /// generated so far, while taking each of their choices in turn, by kind
+    /// and schema path.
+    turns: HashMap<(&'static str, String), usize>,
+
+    /// How many discriminator variants the value currently being generated is
+    /// nested within.
+    discriminator_depth: usize,
+
+    /// The tag of the discriminator whose variant is about to be generated,
+    /// which the variant's additional properties must not be named.
+    discriminator_tag: Option<String>,
+
+    /// How many values the smallest document satisfying each definition has,
+    /// once worked out for `branch_decay` or to generate a smallest value.
+    def_sizes: Option<HashMap<String, usize>>,
+
+    /// The RNG to make structural choices with, if they are seeded separately
+    /// from leaf values.
+    structure_rng: Option<ChaChaRng>,
+
+    /// Pointers to the parts of documents which should vary more than the
+    /// rest.
+    pub focus: Vec<Pointer>,
+
+    /// The values to draw from at schema paths with a pool, as read from the
+    /// files in `config.pool`.
+    pub pools: HashMap<String, pool::Pool>,
+
+    /// The regular expressions hinted at in the schema, parsed, by their
+    /// source.
+    patterns: HashMap<String, pattern::Pattern>,
+
+    /// The schema paths of the parts of the schema which jddf-fuzz doesn't
+    /// support, unless the config says to reject them.
+    unsupported: HashSet<String>,
+
+    /// How much larger than usual containers should be, as steered by
+    /// `--mean-size`.
+    pub size_scale: f64,
+}
+
+impl<'a> Context<'a> {
+    /// Constructs a context for generating values of a schema, or fails if a
+    /// pattern it hints at can't be generated for, unless the config allows
+    /// unsupported parts of the schema.
+    pub fn new(root: &'a Schema, config: &'a FuzzConfig) -> Result<Self, Error> {
+        let unsupported: HashSet<String> = match config.on_unsupported {
+            UnsupportedBehavior::Error => HashSet::new(),
+            _ => check::schema_with_unsupported(root)
+                .map(|found| found.into_iter().map(|(path, _)| path).collect())
+                .unwrap_or_default(),
+        };
+
+        // Patterns are parsed up front, so that one which can't be parsed is
+        // reported rather than found partway through a run.
+        let mut patterns = HashMap::new();
+        for (path, source) in hints::patterns(root) {
+            if unsupported.contains(&path) || patterns.contains_key(source) {
+                continue;
+            }
+
+            let pattern = pattern::Pattern::parse(source).map_err(|err| {
+                format_err!(
+                    "invalid {} pattern {:?}: {} at schema path: {}",
+                    hints::KEY,
+                    source,
+                    err,
+                    path
+                )
+            })?;
+            patterns.insert(source.to_owned(), pattern);
+        }
+
+        Ok(Context {
+            root,
+            config,
+            path: Vec::new(),
+            instance_path: Vec::new(),
+            index: 0,
+            depth: 0,
+            depth_limit_hit: None,
+            ref_depths: HashMap::new(),
+            sequences: HashMap::new(),
+            round_robin: HashMap::new(),
+            turns: HashMap::new(),
+            discriminator_depth: 0,
+            discriminator_tag: None,
+            def_sizes: None,
+            structure_rng: None,
+            focus: Vec::new(),
+            pools: HashMap::new(),
+            patterns,
+            unsupported,
+            size_scale: 1.0,
+        })
+    }
+
+    /// The current schema path, as a JSON Pointer.
+    fn schema_path(&self) -> String {
+        self.path
+            .iter()
+            .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+            .collect()
+    }
+
+    /// The RNG to generate the value at the current schema and instance path
+    /// with, if values are seeded.
+    fn path_rng(&self) -> Option<ChaChaRng> {
+        let seed = self.config.seed?;
+        let instance_path: String = self
+            .instance_path
+            .iter()
+            .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+            .collect();
+
+        Some(seeded_rng(&[
+            seed,
+            self.index,
+            fnv1a(&self.schema_path()),
+            fnv1a(&instance_path),
+        ]))
+    }
+
+    /// Whether the value currently being generated is within a focused part of
+    /// the document.
+    fn focused(&self) -> bool {
+        self.focus
+            .iter()
+            .any(|pointer| pointer.contains(&self.instance_path))
+    }
+
+    /// The inclusive range of how many elements or members a container at the
+    /// current instance path may have.
+    ///
+    /// The maximum is scaled by `size_scale`, but never below the minimum, and
+    /// always leaves room for at least one element or member. Neither bound is
+    /// `usize::MAX`, so that callers can add one to the maximum.
+    fn len_range(&self) -> (usize, usize) {
+        let ContainerSize { min, max } = self.config.container_size;
+        let (min, max) = (min.min(usize::MAX - 1), max.min(usize::MAX - 1));
+        let mut max_len = max + 1;
+        if self.focused() {
+            max_len = max_len.max(FOCUSED_MAX_LEN);
+        }
+
+        let max_len = ((max_len as f64 * self.size_scale).round() as usize).max(2);
+        (min, max_len.max(min + 1) - 1)
+    }
+
+    /// Whether containers at the current depth should be kept as small as
+    /// possible, because their children would exceed the maximum depth.
+    fn truncating(&self) -> bool {
+        self.depth >= self.config.max_depth()
+            && match self.config.on_depth_limit {
+                DepthLimitBehavior::Truncate | DepthLimitBehavior::Null => true,
+                DepthLimitBehavior::Skip | DepthLimitBehavior::Error => false,
+            }
+    }
+}
+
+impl<'a> mutations::Generator for Context<'a> {
+    fn generate(
+        &mut self,
+        rng: &mut dyn rand::RngCore,
+        schema_path: &[String],
+        instance_path: &[String],
+        schema: &Schema,
+    ) -> Value {
+        let path = std::mem::replace(&mut self.path, schema_path.to_vec());
+        let outer = std::mem::replace(&mut self.instance_path, instance_path.to_vec());
+        let val = fuzz_schema(self, rng, schema);
+        self.instance_path = outer;
+        self.path = path;
+        val
+    }
+}
+
+/// An RNG for making structural choices, such as how many elements an array
+/// has, or which optional properties are present.
+enum StructuralRng<'r, R: ?Sized> {
+    /// Structural choices are seeded separately from leaf values.
+    Seeded(&'r mut ChaChaRng),
+
+    /// Structural choices use the same RNG as leaf values.
+    Shared(&'r mut R),
+}
+
+impl<'r, R: rand::RngCore + ?Sized> rand::RngCore for StructuralRng<'r, R> {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            StructuralRng::Seeded(rng) => rng.next_u32(),
+            StructuralRng::Shared(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            StructuralRng::Seeded(rng) => rng.next_u64(),
+            StructuralRng::Shared(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            StructuralRng::Seeded(rng) => rng.fill_bytes(dest),
+            StructuralRng::Shared(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            StructuralRng::Seeded(rng) => rng.try_fill_bytes(dest),
+            StructuralRng::Shared(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+fn structural<'r, R: rand::Rng + ?Sized>(
+    ctx: &'r mut Context,
+    rng: &'r mut R,
+) -> StructuralRng<'r, R> {
+    match ctx.structure_rng {
+        Some(ref mut structure_rng) => StructuralRng::Seeded(structure_rng),
+        None => StructuralRng::Shared(rng),
+    }
+}
+
+/// Returns the keys of a map in sorted order.
+///
+/// Structural choices are made in this order, so that they do not depend on
+/// the order in which a `HashMap` happens to iterate.
+fn sorted_keys<V>(map: &HashMap<String, V>) -> Vec<&String> {
+    let mut keys: Vec<_> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn fuzz_schema<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R, schema: &Schema) -> Value {
+    if ctx.depth > ctx.config.max_depth() {
+        return fuzz_beyond_depth(ctx, schema);
+    }
+
+    if !ctx.config.freeze.is_empty() {
+        let path = ctx.schema_path();
+        if let Some(freeze) = ctx.config.freeze.get(&path) {
+            return fuzz_frozen(ctx, path, freeze, schema);
+        }
+    }
+
+    // Objects, arrays, and discriminators are generated as usual, whatever their
+    // hints, since the variants of a discriminator must be objects.
+    let leaf = !matches!(
+        schema.form(),
+        Form::Properties { .. } | Form::Elements(_) | Form::Values(_) | Form::Discriminator(..)
+    );
+
+    if leaf && !ctx.unsupported.is_empty() && ctx.unsupported.contains(&ctx.schema_path()) {
+        match ctx.config.on_unsupported {
+            UnsupportedBehavior::Null => return Value::Null,
+            UnsupportedBehavior::Placeholder => return UNSUPPORTED_PLACEHOLDER.into(),
+            UnsupportedBehavior::Skip | UnsupportedBehavior::Error => {}
+        }
+    }
+
+    match ctx.path_rng() {
+        Some(mut path_rng) => fuzz_form(ctx, &mut path_rng, schema),
+        None => fuzz_form(ctx, rng, schema),
+    }
+}
+
+fn fuzz_form<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R, schema: &Schema) -> Value {
+    if ctx.focused() && rng.gen_bool(FOCUSED_EXTREME_PROB) {
+        if let Some(val) = fuzz_extreme(rng, schema) {
+            return val;
+        }
+    }
+
+    if ctx.config.number_edge_cases > 0.0 {
+        if let Form::Type(ref typ) = schema.form() {
+            if let Some(vals) = edge_numbers(typ) {
+                if rng.gen_bool(ctx.config.number_edge_cases) {
+                    return vals.choose(rng).unwrap().clone();
+                }
+            }
+        }
+    }
+
+    if ctx.config.int_floats > 0.0 {
+        if let Form::Type(ref typ) = schema.form() {
+            if let Some((min, max)) = int_range(typ) {
+                if rng.gen_bool(ctx.config.int_floats) {
+                    return fuzz_int_float(rng, min, max);
+                }
+            }
+        }
+    }
+
+    if ctx.config.sane {
+        if let Form::Type(ref typ) = schema.form() {
+            if let Some((min, max)) = int_range(typ) {
+                return rng.gen_range(min.max(0), max.min(SANE_MAX) + 1).into();
+            }
+        }
+    }
+
+    match schema.form() {
+        Form::Empty => fuzz_any(ctx, rng),
+        Form::Type(Type::Boolean) => fuzz_bool(rng),
+        Form::Type(Type::Int8) => fuzz_i8(rng),
+        Form::Type(Type::Uint8) => fuzz_u8(rng),
+        Form::Type(Type::Int16) => fuzz_i16(rng),
+        Form::Type(Type::Uint16) => fuzz_u16(rng),
+        Form::Type(Type::Int32) => fuzz_i32(rng),
+        Form::Type(Type::Uint32) => fuzz_u32(rng),
+        Form::Type(Type::Float32) => fuzz_f32(ctx.config, rng),
+        Form::Type(Type::Float64) => fuzz_f64(ctx.config, rng),
+        Form::Type(Type::String) => fuzz_string_at(ctx, rng, schema),
+        Form::Type(Type::Timestamp) => fuzz_timestamp(ctx, rng),
+        Form::Enum(ref vals) => fuzz_enum(ctx, rng, schema, vals),
+        Form::Elements(ref sub_schema) => fuzz_elems(ctx, rng, sub_schema),
+        Form::Properties {
+            required,
+            optional,
+            allow_additional,
+            ..
+        } => fuzz_props(ctx, rng, required, optional, *allow_additional),
+        Form::Values(ref sub_schema) => fuzz_values(ctx, rng, sub_schema),
+        Form::Discriminator(ref tag, ref mapping) => fuzz_discr(ctx, rng, tag, mapping),
+        Form::Ref(def) => {
+            let root = ctx.root;
+            let definition = root.definitions().as_ref().unwrap().get(def).unwrap();
+
+            let max_depth = match ctx.config.ref_depth.get(def) {
+                Some(max_depth) => *max_depth,
+                None if ctx.config.strategy == Strategy::Maximal => MAXIMAL_REF_DEPTH,
+                None => DEFAULT_REF_DEPTH,
+            };
+            if ctx.ref_depths.get(def).copied().unwrap_or(0) >= max_depth {
+                let path =
+                    std::mem::replace(&mut ctx.path, vec!["definitions".to_owned(), def.clone()]);
+                let val = fuzz_minimal(ctx, definition);
+                ctx.path = path;
+                return val;
+            }
+
+            *ctx.ref_depths.entry(def.clone()).or_insert(0) += 1;
+            let path = std::mem::replace(&mut ctx.path, vec!["definitions".to_owned()]);
+            let val = fuzz_at(ctx, rng, def, definition);
+            ctx.path = path;
+            *ctx.ref_depths.get_mut(def).unwrap() -= 1;
+
+            val
+        }
+    }
+}
+
+/// Generates a value for a frozen schema path.
+fn fuzz_frozen(ctx: &mut Context, path: String, freeze: &Freeze, schema: &Schema) -> Value {
+    let n = match freeze {
+        Freeze::Minimal => return fuzz_minimal(ctx, schema),
+        Freeze::Value(val) => return val.clone(),
+        Freeze::Sequence => {
+            let next = ctx.sequences.entry(path).or_insert(0);
+            *next += 1;
+            *next - 1
+        }
+    };
+
+    // Sequences wrap around rather than leave the range of their type.
+    match schema.form() {
+        Form::Type(Type::Int8) => (n % 128).into(),
+        Form::Type(Type::Uint8) => (n % 256).into(),
+        Form::Type(Type::Int16) => (n % 32_768).into(),
+        Form::Type(Type::Uint16) => (n % 65_536).into(),
+        Form::Type(Type::Int32) => (n % 2_147_483_648).into(),
+        Form::Type(Type::Uint32) => (n % 4_294_967_296).into(),
+        // Integers beyond 2^24 are not all exactly representable as an f32.
+        Form::Type(Type::Float32) => (n % 16_777_216).into(),
+        Form::Type(Type::Float64) | Form::Empty => n.into(),
+        Form::Type(Type::String) => n.to_string().into(),
+        Form::Type(Type::Timestamp) => format_timestamp(ctx, n as i64),
+        Form::Enum(ref vals) => {
+            let mut vals: Vec<_> = vals.iter().collect();
+            vals.sort();
+            vals[n as usize % vals.len()].clone().into()
+        }
+        _ => fuzz_minimal(ctx, schema),
+    }
+}
+
+/// Generates a value for a schema which is nested beyond the maximum depth.
+fn fuzz_beyond_depth(ctx: &mut Context, schema: &Schema) -> Value {
+    match ctx.config.on_depth_limit {
+        DepthLimitBehavior::Truncate => {}
+        DepthLimitBehavior::Null => {
+            if let Form::Empty = schema.form() {
+                return Value::Null;
+            }
+        }
+        DepthLimitBehavior::Skip | DepthLimitBehavior::Error => {
+            if ctx.depth_limit_hit.is_none() {
+                ctx.depth_limit_hit = Some(ctx.schema_path());
+            }
+        }
+    }
+
+    fuzz_minimal(ctx, schema)
+}
+
+/// Generates the smallest value satisfying a schema.
+fn fuzz_minimal(ctx: &mut Context, schema: &Schema) -> Value {
+    match schema.form() {
+        Form::Empty => Value::Null,
+        Form::Type(Type::Boolean) => false.into(),
+        Form::Type(Type::Float32) | Form::Type(Type::Float64) => 0.0.into(),
+        Form::Type(Type::String) => match ctx.config.int_as_string.get(&ctx.schema_path()) {
+            Some(digits) => format!("{:0<width$}", 1, width = digits.min).into(),
+            None => "".into(),
+        },
+        Form::Type(Type::Timestamp) => format_timestamp(ctx, 0),
+        Form::Type(_) => 0.into(),
+        Form::Enum(ref vals) => vals.iter().min().unwrap().clone().into(),
+        Form::Elements(_) => Value::Array(Vec::new()),
+        Form::Values(_) => Value::Object(serde_json::Map::new()),
+        Form::Properties { required, .. } => {
+            ctx.path.push("properties".to_owned());
+            let mut keys: Vec<_> = required.keys().collect();
+            keys.sort();
+
+            let mut obj = serde_json::Map::new();
+            for k in keys {
+                ctx.path.push(k.clone());
+                obj.insert(k.clone(), fuzz_minimal(ctx, &required[k]));
+                ctx.path.pop();
+            }
+
+            ctx.path.pop();
+            obj.into()
+        }
+        Form::Discriminator(ref tag, ref mapping) => {
+            // The first variant may only ever recurse, so the one with the
+            // smallest documents is taken instead.
+            let root = ctx.root;
+            let sizes = ctx.def_sizes.get_or_insert_with(|| branch::def_sizes(root));
+            let tag_val = branch::smallest_variant(sizes, mapping);
+
+            ctx.path.extend(vec![
+                "discriminator".to_owned(),
+                "mapping".to_owned(),
+                tag_val.clone(),
+            ]);
+            let mut obj = fuzz_minimal(ctx, &mapping[tag_val]);
+            ctx.path.truncate(ctx.path.len() - 3);
+
+            obj.as_object_mut()
+                .unwrap()
+                .insert(tag.to_owned(), tag_val.clone().into());
+            obj
+        }
+        Form::Ref(def) => {
+            let root = ctx.root;
+            let path =
+                std::mem::replace(&mut ctx.path, vec!["definitions".to_owned(), def.clone()]);
+            let val = fuzz_minimal(ctx, &root.definitions().as_ref().unwrap()[def]);
+            ctx.path = path;
+            val
+        }
+    }
+}
+
+/// Generates a value for a sub-schema, whose schema path is the current one
+/// followed by `token`.
+fn fuzz_at<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    token: &str,
+    schema: &Schema,
+) -> Value {
+    ctx.path.push(token.to_owned());
+    let val = fuzz_schema(ctx, rng, schema);
+    ctx.path.pop();
+    val
+}
+
+fn fuzz_any<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R) -> Value {
+    match structural(ctx, rng).gen_range(0, 5) {
+        0 => Value::Null,
+        1 => fuzz_bool(rng),
+        2 => fuzz_u8(rng),
+        3 => fuzz_f64(ctx.config, rng),
+        _ => fuzz_string(ctx.config, rng),
+    }
+}
+
+fn fuzz_bool<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<bool>().into()
+}
+
+fn fuzz_i8<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<i8>().into()
+}
+
+fn fuzz_u8<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<u8>().into()
+}
+
+fn fuzz_i16<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<i16>().into()
+}
+
+fn fuzz_u16<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<u16>().into()
+}
+
+fn fuzz_i32<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<i32>().into()
+}
+
+fn fuzz_u32<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
+    rng.gen::<u32>().into()
+}
+
+/// Generates a value at one of the extremes of a numeric type, or `None` if
+/// the schema is not for a numeric type.
+fn fuzz_extreme<R: rand::Rng + ?Sized>(rng: &mut R, schema: &Schema) -> Option<Value> {
+    match schema.form() {
+        Form::Type(Type::Float32) => {
+            let vals = [0.0, -0.0, f32::MIN_POSITIVE, f32::MIN, f32::MAX];
+            Some(f32_value(*vals.choose(rng).unwrap()))
+        }
+        Form::Type(Type::Float64) => {
+            let vals = [0.0, -0.0, f64::MIN_POSITIVE, f64::MIN, f64::MAX];
+            Some((*vals.choose(rng).unwrap()).into())
+        }
+        Form::Type(ref typ) => {
+            let (min, max) = int_range(typ)?;
+            let vals = [min, min + 1, 0, max - 1, max];
+            Some((*vals.choose(rng).unwrap()).into())
+        }
+        _ => None,
+    }
+}
+
+/// Integers which are the bounds of some integer type, or just beyond them, so
+/// that a wider type's edge cases include a narrower type's.
+const EDGE_INTS: &[i64] = &[
+    -2_147_483_648,
+    -32_769,
+    -32_768,
+    -129,
+    -128,
+    -1,
+    0,
+    1,
+    127,
+    128,
+    255,
+    256,
+    32_767,
+    32_768,
+    65_535,
+    65_536,
+    2_147_483_647,
+    2_147_483_648,
+    4_294_967_295,
+];
+
+/// The edge cases of a numeric type, or `None` if the type is not numeric.
+///
+/// Integers are those of `EDGE_INTS` in range, and the type's bounds and the
+/// integers next to them. Floats are zero, one, the smallest subnormal and
+/// normal values, the largest subnormal and finite values, the gap between one
+/// and the next float, and the largest integer which can be held exactly, each
+/// both positive and negative.
+fn edge_numbers(typ: &Type) -> Option<Vec<Value>> {
+    match typ {
+        Type::Float32 => {
+            let vals = [
+                0.0,
+                1.0,
+                f32::from_bits(1),
+                f32::from_bits(0x007f_ffff),
+                f32::MIN_POSITIVE,
+                f32::EPSILON,
+                16_777_216.0,
+                f32::MAX,
+            ];
+            Some(
+                vals.iter()
+                    .flat_map(|&f| vec![f32_value(f), f32_value(-f)])
+                    .collect(),
+            )
+        }
+        Type::Float64 => {
+            let vals = [
+                0.0,
+                1.0,
+                f64::from_bits(1),
+                f64::from_bits(0x000f_ffff_ffff_ffff),
+                f64::MIN_POSITIVE,
+                f64::EPSILON,
+                9_007_199_254_740_992.0,
+                f64::MAX,
+            ];
+            Some(
+                vals.iter()
+                    .flat_map(|&f| vec![f.into(), (-f).into()])
+                    .collect(),
+            )
+        }
+        _ => {
+            let (min, max) = int_range(typ)?;
+            let mut vals: Vec<_> = EDGE_INTS
+                .iter()
+                .copied()
+                .chain(vec![min, min + 1, max - 1, max])
+                .filter(|n| min <= *n && *n <= max)
+                .collect();
+            vals.sort();
+            vals.dedup();
+            Some(vals.into_iter().map(Value::from).collect())
+        }
+    }
+}
+
+/// The inclusive range of an integer type, or `None` if the type is not an
+/// integer type.
+fn int_range(typ: &Type) -> Option<(i64, i64)> {
+    match typ {
+        Type::Int8 => Some((-128, 127)),
+        Type::Uint8 => Some((0, 255)),
+        Type::Int16 => Some((-32_768, 32_767)),
+        Type::Uint16 => Some((0, 65_535)),
+        Type::Int32 => Some((-2_147_483_648, 2_147_483_647)),
+        Type::Uint32 => Some((0, 4_294_967_295)),
+        _ => None,
+    }
+}
+
+/// Generates a number written as a float, for an integer type with the given
+/// range.
+///
+/// JDDF accepts any number without a fractional part as an integer, so long as
+/// it is in range, however it is written. So `1.0`, `-0`, and `1e2` are all
+/// valid `uint8` values. Some of the numbers generated here are deliberately
+/// invalid, by having a fractional part or by being just out of range.
+fn fuzz_int_float<R: rand::Rng + ?Sized>(rng: &mut R, min: i64, max: i64) -> Value {
+    let s = match rng.gen_range(0, 6) {
+        0 => format!("{}.0", rng.gen_range(min, max + 1)),
+        1 => "-0".to_owned(),
+        2 => "-0.0".to_owned(),
+        3 => {
+            // A single significant digit, times a power of ten which keeps it in
+            // range.
+            let exp = (max as f64).log10().floor() as u32;
+            let exp = rng.gen_range(0, exp + 1);
+            let digit = rng.gen_range(1, (max / 10_i64.pow(exp)).min(9) + 1);
+            if min < 0 && rng.gen() {
+                format!("-{}e{}", digit, exp)
+            } else {
+                format!("{}e{}", digit, exp)
+            }
+        }
+        4 => format!("{}.5", rng.gen_range(min, max)),
+        _ => {
+            if rng.gen() {
+                format!("{}.0", min - 1)
+            } else {
+                format!("{}.0", max + 1)
+            }
+        }
+    };
+
+    // Parsing the number, rather than converting it from an f64, keeps it
+    // written exactly as it is here.
+    serde_json::from_str(&s).unwrap()
+}
+
+fn fuzz_f32<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> Value {
+    if config.sane {
+        return f32_value(fuzz_sane_float(rng) as f32);
+    }
+
+    if config.float_policy == FloatPolicy::Unit {
+        return f32_value(rng.gen());
+    }
+
+    if let Some(infinity) = fuzz_infinity(config, rng) {
+        return infinity;
+    }
+
+    // Random bits are a float of any sign and magnitude, as often tiny as
+    // huge, but some of them are infinities or NaNs.
+    loop {
+        let f = f32::from_bits(rng.gen());
+        if f.is_finite() {
+            return f32_value(f);
+        }
+    }
+}
+
+/// Converts a float32 to a value, written as the shortest decimal which reads
+/// back as the same `f32`.
+///
+/// Converting the `f32` to a `Value` directly would widen it to an `f64`, and
+/// write out every digit of that, such as `0.10000000149011612` for `0.1`.
+fn f32_value(f: f32) -> Value {
+    // Display writes the shortest decimal which round-trips.
+    f.to_string().parse::<f64>().unwrap().into()
+}
+
+fn fuzz_f64<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> Value {
+    if config.sane {
+        return fuzz_sane_float(rng).into();
+    }
+
+    if config.float_policy == FloatPolicy::Unit {
+        return rng.gen::<f64>().into();
+    }
+
+    if let Some(infinity) = fuzz_infinity(config, rng) {
+        return infinity;
+    }
+
+    loop {
+        let f = f64::from_bits(rng.gen());
+        if f.is_finite() {
+            return f.into();
+        }
+    }
+}
+
+/// A float from zero up to `SANE_MAX`, with at most two decimal places.
+///
+/// Every such float has few enough digits that the shortest decimal which
+/// reads back as it, even as an `f32`, is the one intended.
+fn fuzz_sane_float<R: rand::Rng + ?Sized>(rng: &mut R) -> f64 {
+    rng.gen_range(0, SANE_MAX * 100 + 1) as f64 / 100.0
+}
+
+/// Sometimes an infinity, when the float policy allows them.
+///
+/// Converting an infinite `f64` to a value would make it `null`, so infinities
+/// are written as a number too large for any float instead, which parsers
+/// read as infinity. The proto3 JSON mapping writes them as the strings
+/// `"Infinity"` and `"-Infinity"` instead, and NaN as `"NaN"`, so under
+/// `proto_json` NaN is sometimes generated too.
+fn fuzz_infinity<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> Option<Value> {
+    if config.float_policy != FloatPolicy::Special || !rng.gen_bool(INFINITY_PROB) {
+        return None;
+    }
+
+    if config.proto_json {
+        return Some(
+            ["Infinity", "-Infinity", "NaN"]
+                .choose(rng)
+                .unwrap()
+                .to_owned()
+                .into(),
+        );
+    }
+
+    let s = if rng.gen() { "1e999" } else { "-1e999" };
+    Some(serde_json::from_str(s).unwrap())
+}
+
+/// Generates a random string, of the length and characters the config says.
+fn fuzz_str<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> String {
+    let StringLen { min, max } = config.string_len();
+    let len = match config.strategy {
+        Strategy::Random => rng.gen_range(min, max + 1),
+        Strategy::Minimal => min,
+        Strategy::Maximal => max,
+    };
+    (0..len)
+        .map(|_| fuzz_char(config.charset(), rng))
+        .collect::<String>()
+}
+
+/// Generates a key for a map, or for an additional property, which `taken`
+/// does not already hold.
+///
+/// Keys are random strings, unless those keep clashing with the keys taken,
+/// in which case the key is the first string of an allowed length, spelled out
+/// of the charset's [`Charset::key_chars`] in order, which is not taken.
+/// Returns `None` if every such string is.
+fn fuzz_key<R: rand::Rng + ?Sized>(
+    config: &FuzzConfig,
+    rng: &mut R,
+    taken: impl Fn(&str) -> bool,
+) -> Option<String> {
+    for _ in 0..KEY_ATTEMPTS {
+        let k = fuzz_str(config, rng);
+        if !taken(&k) {
+            return Some(k);
+        }
+    }
+
+    let chars = config.charset().key_chars();
+    let StringLen { min, max } = config.string_len();
+    for len in min..=max {
+        // Each string of the length in turn, as the indices of its characters.
+        let mut digits = vec![0; len];
+        loop {
+            let k: String = digits.iter().map(|&d| chars[d]).collect();
+            if !taken(&k) {
+                return Some(k);
+            }
+
+            match digits.iter().rposition(|&d| d + 1 < chars.len()) {
+                Some(i) => {
+                    digits[i] += 1;
+                    digits[i + 1..].iter_mut().for_each(|d| *d = 0);
+                }
+                None => break,
+            }
+        }
+    }
+
+    None
+}
+
+fn fuzz_string<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> Value {
+    fuzz_str(config, rng).into()
+}
+
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Ranges of characters beyond ASCII, for the unicode charset, chosen among
+/// with equal probability. Single characters are each a range of their own, so
+/// that they come up often.
+const UNICODE_RANGES: &[(char, char)] = &[
+    // Latin-1 and Latin Extended, which take two bytes.
+    ('\u{a0}', '\u{24f}'),
+    // Combining diacritical marks, which modify the character before them, or
+    // nothing at the start of a string.
+    ('\u{300}', '\u{36f}'),
+    // Cyrillic, Greek, Hebrew and Arabic.
+    ('\u{370}', '\u{6ff}'),
+    // CJK ideographs, which take three bytes.
+    ('\u{4e00}', '\u{9fff}'),
+    // Zero-width joiners and spaces, and the byte order mark.
+    ('\u{200b}', '\u{200d}'),
+    ('\u{feff}', '\u{feff}'),
+    // Either side of the surrogates, which UTF-16 encodes as pairs.
+    ('\u{d7ff}', '\u{d7ff}'),
+    ('\u{e000}', '\u{e000}'),
+    // The replacement character, and noncharacters.
+    ('\u{fffd}', '\u{fffd}'),
+    ('\u{fffe}', '\u{ffff}'),
+    // Emoji, which take four bytes.
+    ('\u{1f300}', '\u{1faff}'),
+    // The extremes of the supplementary planes.
+    ('\u{10000}', '\u{10000}'),
+    ('\u{10ffff}', '\u{10ffff}'),
+];
+
+fn fuzz_char<R: rand::Rng + ?Sized>(charset: Charset, rng: &mut R) -> char {
+    match charset {
+        Charset::Ascii => rng.gen_range(0u8, 128u8) as char,
+        Charset::Alphanumeric => char::from(*ALPHANUMERIC.choose(rng).unwrap()),
+        Charset::Printable => rng.gen_range(32u8, 127u8) as char,
+        Charset::Unicode => {
+            // Some ASCII too, so that strings mix widths.
+            if rng.gen_bool(0.2) {
+                return rng.gen_range(32u8, 127u8) as char;
+            }
+
+            let (lo, hi) = *UNICODE_RANGES.choose(rng).unwrap();
+            std::char::from_u32(rng.gen_range(u32::from(lo), u32::from(hi) + 1)).unwrap()
+        }
+    }
+}
+
+/// Generates a string, honoring any per-path string options for the current
+/// schema path, and then any format the schema's metadata hints at.
+fn fuzz_string_at<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R, schema: &Schema) -> Value {
+    if !ctx.config.int_as_string.is_empty()
+        || !ctx.config.homoglyphs.is_empty()
+        || !ctx.config.jwt.is_empty()
+        || !ctx.pools.is_empty()
+    {
+        let path = ctx.schema_path();
+
+        if let Some(digits) = ctx.config.int_as_string.get(&path) {
+            return fuzz_int_str(rng, *digits, ctx.config.adversarial).into();
+        }
+
+        if ctx.config.homoglyphs.contains(&path) {
+            return homoglyph::fuzz_homoglyph(rng).into();
+        }
+
+        if let Some(key) = ctx.config.jwt.get(&path) {
+            return jwt::fuzz_jwt(rng, key.as_ref().map(String::as_str)).into();
+        }
+
+        if let Some(pool) = ctx.pools.get(&path) {
+            return pool.choose(rng).into();
+        }
+    }
+
+    let mut edge_prob = ctx.config.string_edge_cases;
+    if ctx.focused() {
+        edge_prob = edge_prob.max(FOCUSED_EXTREME_PROB);
+    }
+
+    if edge_prob > 0.0 && rng.gen_bool(edge_prob) {
+        return fuzz_edge_str(ctx.config, rng).into();
+    }
+
+    // Unsupported hints are skipped, since anything else has already been
+    // generated in their place.
+    if !ctx.unsupported.is_empty() && ctx.unsupported.contains(&ctx.schema_path()) {
+        return fuzz_string(ctx.config, rng);
+    }
+
+    match hints::of(schema.extra()) {
+        Some(Ok(hints::Hint::Pattern(source))) => ctx.patterns[source].fuzz(rng).into(),
+        Some(Ok(hint)) => hint.fuzz(rng).into(),
+        _ => fuzz_string(ctx.config, rng),
+    }
+}
+
+/// Whitespace characters, including some which naive trimming overlooks.
+const WHITESPACE: &[char] = &[
+    ' ', '\t', '\n', '\r', '\u{a0}', '\u{2003}', '\u{3000}', '\u{feff}',
+];
+
+/// Generates a string which is likely to exercise trimming, emptiness, or
+/// length checks.
+fn fuzz_edge_str<R: rand::Rng + ?Sized>(config: &FuzzConfig, rng: &mut R) -> String {
+    match rng.gen_range(0, 5) {
+        0 => String::new(),
+        1 => " ".to_owned(),
+        2 => (0..rng.gen_range(1, 8))
+            .map(|_| *WHITESPACE.choose(rng).unwrap())
+            .collect(),
+        3 => format!(
+            "{}{}{}",
+            WHITESPACE.choose(rng).unwrap(),
+            fuzz_str(config, rng),
+            WHITESPACE.choose(rng).unwrap()
+        ),
+        _ => (0..rng.gen_range(1024, 4097))
+            .map(|_| rng.gen_range(32u8, 127u8) as char)
+            .collect(),
+    }
+}
+
+/// Generates a base-10 integer serialized as a string.
+///
+/// In adversarial mode, the string may have leading zeros or an explicit sign,
+/// or be negative zero.
+fn fuzz_int_str<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    digits: DigitRange,
+    adversarial: bool,
+) -> String {
+    let len = rng.gen_range(digits.min, digits.max + 1);
+    let mut out = String::with_capacity(len + 1);
+
+    if adversarial && rng.gen_bool(0.25) {
+        out.push(if rng.gen() { '-' } else { '+' });
+    }
+
+    if adversarial && rng.gen_bool(0.1) {
+        out.extend((0..len).map(|_| '0'));
+        return out;
+    }
+
+    for i in 0..len {
+        let leading = i == 0 && len > 1 && !(adversarial && rng.gen_bool(0.25));
+        let digit = if leading {
+            rng.gen_range(1, 10)
+        } else {
+            rng.gen_range(0, 10)
+        };
+
+        out.push((b'0' + digit) as char);
+    }
+
+    out
+}
+
+fn fuzz_timestamp<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R) -> Value {
+    let config = ctx.config;
+    let secs = if config.sane {
+        let now = config.now.unwrap_or_else(|| Utc::now().timestamp());
+        now - rng.gen_range(0, SANE_TIMESTAMP_SECS)
+    } else if let Some(range) = config.timestamp_range {
+        rng.gen_range(range.min, range.max + 1)
+    } else {
+        rng.gen::<i32>() as i64
+    };
+
+    let TimestampPrecision { min, max } = config.timestamp_precision;
+    let mut digits = if max == 0 {
+        0
+    } else {
+        rng.gen_range(min, max + 1)
+    };
+
+    // The proto3 JSON mapping only allows milliseconds, microseconds, or
+    // nanoseconds.
+    if config.proto_json {
+        digits = digits.div_ceil(3) * 3;
+    }
+
+    let nanos = if digits == 0 {
+        0
+    } else {
+        rng.gen_range(0, 10u32.pow(digits as u32)) * 10u32.pow(9 - digits as u32)
+    };
+
+    // Offsets are whole quarter hours, from -12:00 to +14:00, as all those in
+    // use are.
+    let offset = if config.timestamp_offsets && !config.proto_json {
+        rng.gen_range(-48, 57) * 15 * 60
+    } else {
+        0
+    };
+
+    format_precise_timestamp(ctx, secs, nanos, digits, offset)
+}
+
+/// Formats a Unix timestamp, in seconds, as an RFC 3339 timestamp.
+fn format_timestamp(ctx: &Context, secs: i64) -> Value {
+    format_precise_timestamp(ctx, secs, 0, 0, 0)
+}
+
+/// Formats a Unix timestamp, in seconds and nanoseconds, as an RFC 3339
+/// timestamp with `digits` digits of fractional seconds, at an offset from UTC
+/// in seconds.
+fn format_precise_timestamp(
+    ctx: &Context,
+    secs: i64,
+    nanos: u32,
+    digits: usize,
+    offset: i32,
+) -> Value {
+    let date_time = NaiveDateTime::from_timestamp(secs, nanos);
+    let date_time =
+        DateTime::<Utc>::from_utc(date_time, Utc).with_timezone(&FixedOffset::east(offset));
+
+    let mut s = date_time.format("%Y-%m-%dT%H:%M:%S").to_string();
+    if digits > 0 {
+        s.push_str(&format!(".{:09}", nanos)[..digits + 1]);
+    }
+
+    if ctx.config.proto_json {
+        s.push('Z');
+    } else {
+        s.push_str(&date_time.format("%:z").to_string());
+    }
+
+    s.into()
+}
+
+fn fuzz_enum<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    schema: &Schema,
+    vals: &HashSet<String>,
+) -> Value {
+    let mut vals: Vec<_> = vals.iter().collect();
+    vals.sort();
+    let val = match round_robin(ctx, &vals) {
+        Some(val) => val,
+        None => match weights::of_values(schema, &vals) {
+            Some(weights) => {
+                let weighted: Vec<_> = vals.iter().zip(weights).collect();
+                *weighted
+                    .choose_weighted(rng, |(_, weight)| *weight)
+                    .unwrap()
+                    .0
+            }
+            None => *vals.choose(rng).unwrap(),
+        },
+    };
+
+    val.clone().into()
+}
+
+/// With `--enum-round-robin` or `--coverage`, takes the next of the sorted
+/// values of the enum or discriminator at the current schema path, if it has
+/// not yet taken all of them. Returns `None` if the value should be chosen at
+/// random instead.
+fn round_robin<'v>(ctx: &mut Context, vals: &[&'v String]) -> Option<&'v String> {
+    if !ctx.config.enum_round_robin && !ctx.config.coverage {
+        return None;
+    }
+
+    let next = ctx.round_robin.entry(ctx.schema_path()).or_insert(0);
+    let val = *vals.get(*next)?;
+    *next += 1;
+    Some(val)
+}
+
+/// With `--coverage`, takes the next of a number of choices of some kind at
+/// the current schema path, if it has not yet taken all of them. Returns
+/// `None` if the choice should be made at random instead.
+fn next_turn(ctx: &mut Context, kind: &'static str, choices: usize) -> Option<usize> {
+    if !ctx.config.coverage {
+        return None;
+    }
+
+    let next = ctx.turns.entry((kind, ctx.schema_path())).or_insert(0);
+    if *next == choices {
+        return None;
+    }
+
+    *next += 1;
+    Some(*next - 1)
+}
+
+/// Chooses how many elements or values an array or map for the elements or
+/// values form at the current schema path has.
+fn container_len<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R) -> usize {
+    let (min, max) = ctx.len_range();
+
+    let mut empty_prob = None;
+    if !ctx.config.empty_prob.is_empty() {
+        empty_prob = ctx.config.empty_prob.get(&ctx.schema_path()).copied();
+    }
+
+    if empty_prob.is_none() && ctx.config.never_empty {
+        empty_prob = Some(0.0);
+    }
+
+    // Under coverage, the first is empty and the second is not, if they can
+    // be.
+    let turn = next_turn(ctx, "container", 2);
+    let strategy = ctx.config.strategy;
+    let mut rng = structural(ctx, rng);
+    match turn {
+        Some(0) if empty_prob.map_or(min == 0, |prob| prob > 0.0) => return 0,
+        Some(1) if empty_prob != Some(1.0) && max > 0 => return rng.gen_range(min.max(1), max + 1),
+        _ => {}
+    }
+
+    match (strategy, empty_prob) {
+        (Strategy::Minimal, Some(prob)) if prob > 0.0 => return 0,
+        (Strategy::Minimal, Some(_)) => return min.max(1),
+        (Strategy::Minimal, None) => return min,
+        (Strategy::Maximal, Some(1.0)) => return 0,
+        (Strategy::Maximal, _) => return max,
+        (Strategy::Random, _) => {}
+    }
+
+    match empty_prob {
+        Some(prob) if rng.gen_bool(prob) => 0,
+        Some(_) => rng.gen_range(min.max(1), max + 1),
+        None => rng.gen_range(min, max + 1),
+    }
+}
+
+fn fuzz_elems<R: rand::Rng + ?Sized>(ctx: &mut Context, rng: &mut R, sub_schema: &Schema) -> Value {
+    if ctx.truncating() {
+        return Value::Array(Vec::new());
+    }
+
+    let len = container_len(ctx, rng);
+    ctx.depth += 1;
+    let vals = (0..len)
+        .map(|i| {
+            ctx.instance_path.push(i.to_string());
+            let val = fuzz_at(ctx, rng, "elements", sub_schema);
+            ctx.instance_path.pop();
+            val
+        })
+        .collect::<Vec<_>>();
+    ctx.depth -= 1;
+
+    vals.into()
+}
+
+fn fuzz_props<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    required: &HashMap<String, Schema>,
+    optional: &HashMap<String, Schema>,
+    allow_additional: bool,
+) -> Value {
+    let mut vals = Vec::new();
+    let truncating = ctx.truncating();
+    let tag = ctx.discriminator_tag.take();
+
+    ctx.depth += 1;
+    ctx.path.push("properties".to_owned());
+    for k in sorted_keys(required) {
+        ctx.instance_path.push(k.clone());
+        vals.push((k.clone(), fuzz_at(ctx, rng, k, &required[k])));
+        ctx.instance_path.pop();
+    }
+    ctx.path.pop();
+
+    ctx.path.push("optionalProperties".to_owned());
+    for k in sorted_keys(optional) {
+        if truncating {
+            continue;
+        }
+
+        let weights = if ctx.config.optional.is_empty() {
+            None
+        } else {
+            ctx.path.push(k.clone());
+            let weights = ctx.config.optional.get(&ctx.schema_path()).copied();
+            ctx.path.pop();
+            weights
+        };
+
+        let presence = match weights {
+            Some(weights) => {
+                let nullable = accepts_null(ctx.root, &optional[k]);
+                choose_presence(&mut structural(ctx, rng), &weights, nullable)
+            }
+            None => {
+                ctx.path.push(k.clone());
+                let turn = next_turn(ctx, "optional", 2);
+                ctx.path.pop();
+
+                // An inline weight weighs presence against absence, which
+                // weighs one.
+                let prob = match (ctx.config.optional_prob, weights::of(&optional[k])) {
+                    (Some(prob), _) => Some(prob),
+                    (None, Some(weight)) => Some(weight / (1.0 + weight)),
+                    (None, None) => None,
+                };
+
+                // Under coverage, the first is present and the second is not.
+                // Minimal and maximal documents have only what they must, or
+                // everything they may.
+                let present = match (turn, ctx.config.strategy, prob) {
+                    (Some(turn), _, _) => turn == 0,
+                    (None, Strategy::Minimal, prob) => prob == Some(1.0),
+                    (None, Strategy::Maximal, prob) => prob != Some(0.0),
+                    (None, Strategy::Random, Some(prob)) => structural(ctx, rng).gen_bool(prob),
+                    (None, Strategy::Random, None) => structural(ctx, rng).gen(),
+                };
+
+                if present {
+                    Presence::Present
+                } else {
+                    Presence::Absent
+                }
+            }
+        };
+
+        match presence {
+            Presence::Absent => {}
+            Presence::Null => vals.push((k.clone(), Value::Null)),
+            Presence::Present => {
+                ctx.instance_path.push(k.clone());
+                vals.push((k.clone(), fuzz_at(ctx, rng, k, &optional[k])));
+                ctx.instance_path.pop();
+            }
+        }
+    }
+    ctx.path.pop();
+    ctx.depth -= 1;
+
+    let additional = allow_additional
+        && !truncating
+        && match ctx.config.strategy {
+            Strategy::Random => ctx
+                .config
+                .additional_prob
+                .is_none_or(|prob| structural(ctx, rng).gen_bool(prob)),
+            Strategy::Minimal => ctx.config.additional_prob.is_none_or(|prob| prob == 1.0),
+            Strategy::Maximal => ctx.config.additional_prob != Some(0.0),
+        };
+
+    if additional {
+        let (min, max) = ctx.len_range();
+        let len = match ctx.config.strategy {
+            Strategy::Random => structural(ctx, rng).gen_range(min, max + 1),
+            Strategy::Minimal => min,
+            Strategy::Maximal => max,
+        };
+        // Additional properties are named neither as declared properties nor
+        // the tag are, so that none of them overwrite another member.
+        for _ in 0..len {
+            let k = fuzz_key(ctx.config, rng, |k| {
+                required.contains_key(k)
+                    || optional.contains_key(k)
+                    || tag.as_deref() == Some(k)
+                    || vals.iter().any(|(taken, _)| taken == k)
+            });
+            match k {
+                Some(k) => vals.push((k, fuzz_any(ctx, rng))),
+                None => break,
+            }
+        }
+    }
+
+    vals.into_iter()
+        .collect::<serde_json::Map<String, Value>>()
+        .into()
+}
+
+/// Whether an optional property is absent, present as `null`, or present with
+/// any other value.
+enum Presence {
+    Absent,
+    Null,
+    Present,
+}
+
+/// Chooses whether an optional property is present, according to its weights.
+fn choose_presence<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    weights: &OptionalWeights,
+    nullable: bool,
+) -> Presence {
+    let null = if nullable { weights.null } else { 0.0 };
+    let total = weights.absent + null + weights.present;
+    if total <= 0.0 {
+        return Presence::Absent;
+    }
+
+    let x = rng.gen_range(0.0, total);
+    if x < weights.absent {
+        Presence::Absent
+    } else if x < weights.absent + null {
+        Presence::Null
+    } else {
+        Presence::Present
+    }
+}
+
+/// Whether a schema accepts `null`. Only the empty form does.
+fn accepts_null(root: &Schema, schema: &Schema) -> bool {
+    match schema.form() {
+        Form::Empty => true,
+        Form::Ref(def) => accepts_null(root, &root.definitions().as_ref().unwrap()[def]),
+        _ => false,
+    }
+}
+
+fn fuzz_values<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    sub_schema: &Schema,
+) -> Value {
+    if ctx.truncating() {
+        return Value::Object(serde_json::Map::new());
+    }
+
+    let len = container_len(ctx, rng);
+    ctx.depth += 1;
+    let mut vals = serde_json::Map::new();
+    for _ in 0..len {
+        let k = match fuzz_key(ctx.config, rng, |k| vals.contains_key(k)) {
+            Some(k) => k,
+            None => break,
+        };
+
+        ctx.instance_path.push(k.clone());
+        let val = fuzz_at(ctx, rng, "values", sub_schema);
+        ctx.instance_path.pop();
+        vals.insert(k, val);
+    }
+    ctx.depth -= 1;
+
+    vals.into()
+}
+
+fn fuzz_discr<R: rand::Rng + ?Sized>(
+    ctx: &mut Context,
+    rng: &mut R,
+    tag: &str,
+    mapping: &HashMap<String, Schema>,
+) -> Value {
+    let tag_vals = sorted_keys(mapping);
+    let tag_val = match round_robin(ctx, &tag_vals) {
+        Some(tag_val) => tag_val,
+        None if ctx.config.branch_decay > 0.0 && ctx.discriminator_depth > 0 => {
+            let root = ctx.root;
+            let sizes = ctx.def_sizes.get_or_insert_with(|| branch::def_sizes(root));
+            let mut weights = branch::weights(
+                sizes,
+                mapping,
+                &tag_vals,
+                ctx.config.branch_decay,
+                ctx.discriminator_depth,
+            );
+            if let Some(inline) = weights::of_variants(mapping, &tag_vals) {
+                weights = weights.iter().zip(inline).map(|(a, b)| a * b).collect();
+            }
+
+            let weighted: Vec<_> = tag_vals.iter().zip(weights).collect();
+            *weighted
+                .choose_weighted(&mut structural(ctx, rng), |(_, weight)| *weight)
+                .unwrap()
+                .0
+        }
+        None => match weights::of_variants(mapping, &tag_vals) {
+            Some(weights) => {
+                let weighted: Vec<_> = tag_vals.iter().zip(weights).collect();
+                *weighted
+                    .choose_weighted(&mut structural(ctx, rng), |(_, weight)| *weight)
+                    .unwrap()
+                    .0
+            }
+            None => *tag_vals.choose(&mut structural(ctx, rng)).unwrap(),
+        },
+    };
+    let sub_schema = &mapping[tag_val];
+
+    ctx.path.push("discriminator".to_owned());
+    ctx.path.push("mapping".to_owned());
+    ctx.discriminator_depth += 1;
+    ctx.discriminator_tag = Some(tag.to_owned());
+    let mut obj = fuzz_at(ctx, rng, tag_val, sub_schema);
+    ctx.discriminator_tag = None;
+    ctx.discriminator_depth -= 1;
+    ctx.path.truncate(ctx.path.len() - 2);
+
+    obj.as_object_mut()
+        .unwrap()
+        .insert(tag.to_owned(), tag_val.clone().into());
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Generates some documents of a schema under a config.
+    fn generate(schema: Value, config: &FuzzConfig) -> Vec<Value> {
+        let schema = check::parse(schema).unwrap();
+        let mut ctx = Context::new(&schema, config).unwrap();
+        let mut rng = seeded_rng(&[0]);
+        (0..100)
+            .map(|_| fuzz_schema(&mut ctx, &mut rng, &schema))
+            .collect()
+    }
+
+    #[test]
+    fn distinct_keys() {
+        let config = FuzzConfig {
+            container_size: ContainerSize { min: 3, max: 5 },
+            string_len: Some(StringLen { min: 0, max: 1 }),
+            ..FuzzConfig::default()
+        };
+        for doc in generate(json!({ "values": { "type": "uint8" } }), &config) {
+            let len = doc.as_object().unwrap().len();
+            assert!((3..=5).contains(&len), "{}", doc);
+        }
+
+        let config = FuzzConfig {
+            strategy: Strategy::Minimal,
+            ..config
+        };
+        for doc in generate(json!({ "values": { "type": "uint8" } }), &config) {
+            let keys: Vec<_> = doc.as_object().unwrap().keys().collect();
+            assert_eq!(keys, ["", " ", "!"]);
+        }
+    }
+
+    #[test]
+    fn sane_strings() {
+        let config = FuzzConfig {
+            sane: true,
+            ..FuzzConfig::default()
+        };
+        for doc in generate(json!({ "type": "string" }), &config) {
+            let s = doc.as_str().unwrap();
+            assert!((3..=12).contains(&s.len()), "{}", s);
+            assert!(s.chars().all(|c| c.is_ascii_alphanumeric()), "{}", s);
+        }
+    }
+
+    /// Measures how deeply arrays are nested within a value.
+    fn array_depth(val: &Value) -> usize {
+        match val {
+            Value::Array(elems) => 1 + elems.iter().map(array_depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn self_referential_definitions() {
+        let schema = json!({
+            "definitions": { "a": { "elements": { "ref": "a" } } },
+            "ref": "a",
+        });
+        let config = FuzzConfig {
+            container_size: ContainerSize { min: 1, max: 7 },
+            ..FuzzConfig::default()
+        };
+        for doc in generate(schema.clone(), &config) {
+            assert_eq!(array_depth(&doc), DEFAULT_REF_DEPTH + 1, "{}", doc);
+        }
+
+        let config = FuzzConfig {
+            ref_depth: vec![("a".to_owned(), 2)].into_iter().collect(),
+            ..config
+        };
+        for doc in generate(schema.clone(), &config) {
+            assert_eq!(array_depth(&doc), 3, "{}", doc);
+        }
+
+        let mut rng = seeded_rng(&[0]);
+        let schema = check::parse(schema).unwrap();
+        for _ in 0..100 {
+            try_fuzz(&mut rng, &schema).unwrap();
+        }
+    }
+
+    #[test]
+    fn self_referential_discriminators() {
+        // The first variant only ever refers back to the definition, so the
+        // smallest value must take the other.
+        let schema = json!({
+            "definitions": {
+                "t": {
+                    "discriminator": {
+                        "tag": "k",
+                        "mapping": {
+                            "a": { "properties": { "x": { "ref": "t" } } },
+                            "b": { "properties": {} },
+                        },
+                    },
+                },
+            },
+            "ref": "t",
+        });
+
+        fn chain(doc: &Value) -> usize {
+            match doc.get("x") {
+                Some(x) => 1 + chain(x),
+                None => 0,
+            }
+        }
+
+        let config = FuzzConfig {
+            ref_depth: vec![("t".to_owned(), 0)].into_iter().collect(),
+            ..FuzzConfig::default()
+        };
+        for doc in generate(schema.clone(), &config) {
+            assert_eq!(doc, json!({ "k": "b" }));
+        }
+
+        let config = FuzzConfig {
+            ref_depth: vec![("t".to_owned(), 2)].into_iter().collect(),
+            ..FuzzConfig::default()
+        };
+        for doc in generate(schema.clone(), &config) {
+            assert!(chain(&doc) <= 2, "{}", doc);
+        }
+
+        let config = FuzzConfig {
+            strategy: Strategy::Maximal,
+            ..FuzzConfig::default()
+        };
+        for doc in generate(schema, &config) {
+            assert!(chain(&doc) <= MAXIMAL_REF_DEPTH, "{}", doc);
+        }
+    }
+
+    #[test]
+    fn default_max_depth() {
+        let schema = json!({
+            "definitions": { "a": { "elements": { "ref": "a" } } },
+            "ref": "a",
+        });
+        let config = FuzzConfig {
+            container_size: ContainerSize { min: 1, max: 1 },
+            ref_depth: vec![("a".to_owned(), usize::MAX)].into_iter().collect(),
+            ..FuzzConfig::default()
+        };
+        for doc in generate(schema, &config) {
+            assert_eq!(array_depth(&doc), config.max_depth() + 1);
+        }
+    }
+
+    #[test]
+    fn special_floats() {
+        let schema = json!({ "elements": { "type": "float32" } });
+        let parsed = check::parse(schema.clone()).unwrap();
+        let validator = jddf::Validator::new();
+        for proto_json in [false, true] {
+            let config = FuzzConfig {
+                float_policy: FloatPolicy::Special,
+                proto_json,
+                container_size: ContainerSize { min: 8, max: 8 },
+                ..FuzzConfig::default()
+            };
+            let docs = generate(schema.clone(), &config);
+            for doc in &docs {
+                verify::verify(&validator, &parsed, doc, proto_json).unwrap();
+            }
+
+            let specials: HashSet<_> = docs
+                .iter()
+                .flat_map(|doc| doc.as_array().unwrap())
+                .filter(|val| !val.as_f64().is_some_and(f64::is_finite))
+                .map(|val| match val {
+                    // Newer versions of serde_json write 1e999 as 1e+999.
+                    Value::Number(n) => binary::float(n).to_string(),
+                    val => val.to_string(),
+                })
+                .collect();
+            let expected: &[&str] = if proto_json {
+                &["\"Infinity\"", "\"-Infinity\"", "\"NaN\""]
+            } else {
+                &["inf", "-inf"]
+            };
+            assert_eq!(specials, expected.iter().map(|s| s.to_string()).collect());
+        }
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        // Parsed without check::parse, which rejects the pattern itself.
+        let schema = Schema::from_serde(
+            serde_json::from_value(json!({
+                "definitions": {
+                    "code": {
+                        "type": "string",
+                        "metadata": { "fuzzHint": { "pattern": "(" } },
+                    },
+                },
+                "ref": "code",
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let config = FuzzConfig::default();
+        let err = Context::new(&schema, &config).err().unwrap().to_string();
+        assert!(err.contains("/definitions/code"), "{}", err);
+        assert!(try_fuzz(&mut seeded_rng(&[0]), &schema).is_err());
+
+        let config = FuzzConfig {
+            on_unsupported: UnsupportedBehavior::Skip,
+            ..config
+        };
+        let mut ctx = Context::new(&schema, &config).unwrap();
+        assert!(fuzz_schema(&mut ctx, &mut seeded_rng(&[0]), &schema).is_string());
+    }
+
+    #[test]
+    fn additional_properties_keep_declared_names() {
+        let config = FuzzConfig {
+            container_size: ContainerSize { min: 2, max: 2 },
+            string_len: Some(StringLen { min: 0, max: 0 }),
+            ..FuzzConfig::default()
+        };
+        let schema = json!({
+            "discriminator": {
+                "tag": "",
+                "mapping": {
+                    "x": {
+                        "properties": { " ": { "type": "boolean" } },
+                        "additionalProperties": true,
+                    },
+                },
+            },
+        });
+        for doc in generate(schema, &config) {
+            let obj = doc.as_object().unwrap();
+            assert_eq!(obj[""], "x");
+            assert!(obj[" "].is_boolean());
+            assert!((2..=4).contains(&obj.len()), "{}", doc);
+        }
+    }
+}
@@ -1,16 +1,25 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
-use clap::{App, AppSettings, Arg};
-use failure::Error;
-use jddf::schema::{Form, Type};
-use jddf::{Schema, SerdeSchema};
-use rand::seq::IteratorRandom;
-use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use failure::{bail, format_err, Error};
+use jddf_fuzz::commands::{self, MutateOptions, SchemaFile};
+use jddf_fuzz::config::{
+    split_path_option, ContainerSize, DigitRange, FuzzConfig, OptionalWeights, StringLen,
+    TimestampPrecision, TimestampRange, UnsupportedBehavior,
+};
+use jddf_fuzz::generate::{self, Format, Options, Output, Patches, Post, RecordSeparator};
+use jddf_fuzz::pointer::Pointer;
+use serde_json::json;
 use std::fs::File;
 use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How many bytes a file under `--soak` may hold before moving on to the next,
+/// unless given otherwise.
+const DEFAULT_ROTATE_BYTES: u64 = 64 * 1024 * 1024;
 
 fn main() -> Result<(), Error> {
-    let matches = App::new("jddf-fuzz")
+    let app = App::new("jddf-fuzz")
         .version("0.1")
         .about("Creates random JSON documents satisfying a JDDF schema")
         .setting(AppSettings::ColoredHelp)
@@ -26,188 +35,1560 @@ fn main() -> Result<(), Error> {
                 .help("Where to read schema from. Dash (hypen) indicates stdin")
                 .default_value("-"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("schema-inline")
+                .help("Read schema from this JSON, given on the command line, rather than from INPUT")
+                .long("schema-inline")
+                .value_name("JSON"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .help("Read generation options from a JSON file, or a TOML one if its name ends in .toml. Options given on the command line take precedence")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("print-config")
+                .help("Write the options the run uses, from the command line, config file, and defaults, to stderr as a config file --config accepts")
+                .long("print-config"),
+        )
+        .arg(
+            Arg::with_name("adversarial")
+                .help("Favor valid values which are likely to trip up consumers")
+                .long("adversarial"),
+        )
+        .arg(
+            Arg::with_name("sane")
+                .help("Generate demo-quality data: integers and floats from 0 to 10000, floats with two decimals, strings of 3 to 12 letters and digits, and timestamps within the last year")
+                .long("sane"),
+        )
+        .arg(
+            Arg::with_name("int-as-string")
+                .help("Generate integers as strings at a schema path, with DIGITS like 10 or 1..19")
+                .long("int-as-string")
+                .value_name("PATH[=DIGITS]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("string-edge-cases")
+                .help("Sometimes generate empty, whitespace-only, or very long strings")
+                .long("string-edge-cases"),
+        )
+        .arg(
+            Arg::with_name("string-edge-prob")
+                .help("Probability that a string is an edge case [default: 0.25]")
+                .long("string-edge-prob")
+                .value_name("PROB")
+                .requires("string-edge-cases"),
+        )
+        .arg(
+            Arg::with_name("string-len")
+                .help("How many characters random strings have, as a count or an inclusive range like 0..256. Defaults to 0..7")
+                .long("string-len")
+                .value_name("MIN..MAX")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("charset")
+                .help("Which characters random strings are made of. unicode is mostly multi-byte characters, such as emoji and combining marks")
+                .long("charset")
+                .possible_values(&["ascii", "alphanumeric", "printable", "unicode"])
+                .default_value("printable"),
+        )
+        .arg(
+            Arg::with_name("edge-cases")
+                .help("Sometimes generate numbers at the edges of their types, such as zero, their bounds, or subnormal floats")
+                .long("edge-cases"),
+        )
+        .arg(
+            Arg::with_name("edge-prob")
+                .help("Probability that a number is an edge case [default: 0.25]")
+                .long("edge-prob")
+                .value_name("PROB")
+                .requires("edge-cases"),
+        )
+        .arg(
+            Arg::with_name("float-policy")
+                .help("How floats are distributed: any finite float, floats from 0 up to 1, or any finite float or sometimes an infinity, written as 1e999, or under --proto-json as \"Infinity\", alongside \"NaN\"")
+                .long("float-policy")
+                .possible_values(&["full", "unit", "special"])
+                .default_value("full"),
+        )
+        .arg(
+            Arg::with_name("int-floats")
+                .help("Sometimes write integers as floats, such as 1.0, -0, or 1e2, some of which are invalid")
+                .long("int-floats")
+                .conflicts_with("verify"),
+        )
+        .arg(
+            Arg::with_name("int-float-prob")
+                .help("Probability that an integer is written as a float [default: 0.25]")
+                .long("int-float-prob")
+                .value_name("PROB")
+                .requires("int-floats"),
+        )
+        .arg(
+            Arg::with_name("key-variants")
+                .help("Sometimes change a declared property name's case, pad it with whitespace, or give it a lookalike character")
+                .long("key-variants"),
+        )
+        .arg(
+            Arg::with_name("key-variant-prob")
+                .help("Probability that a document has a changed property name [default: 0.25]")
+                .long("key-variant-prob")
+                .value_name("PROB")
+                .requires("key-variants"),
+        )
+        .arg(
+            Arg::with_name("homoglyphs")
+                .help("Generate lookalike strings, differing in script or normalization, at a schema path")
+                .long("homoglyphs")
+                .value_name("PATH")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("jwt")
+                .help("Generate JSON Web Tokens at a schema path, signed with KEY if given")
+                .long("jwt")
+                .value_name("PATH[=KEY]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("pool")
+                .help("Draw the strings at a schema path from the lines of FILE, each optionally followed by a tab and a weight")
+                .long("pool")
+                .value_name("PATH=FILE")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .help("How deeply values may be nested. The root value is at depth zero [default: 128]")
+                .long("max-depth")
+                .value_name("DEPTH")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("on-depth-limit")
+                .help("What to do when generating a value beyond --max-depth")
+                .long("on-depth-limit")
+                .possible_values(&["truncate", "null", "skip", "error"])
+                .default_value("truncate"),
+        )
+        .arg(
+            Arg::with_name("on-unsupported")
+                .help("What to do with parts of the schema jddf-fuzz doesn't support, such as unknown format hints")
+                .long("on-unsupported")
+                .possible_values(&["error", "skip", "null", "placeholder"])
+                .default_value("error"),
+        )
+        .arg(
+            Arg::with_name("ref-depth")
+                .help("How deeply values of a definition may be nested within one another, beyond which they are as small as possible. Defaults to 4, or 1 under --strategy maximal")
+                .long("ref-depth")
+                .value_name("DEF=DEPTH")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("mean-size")
+                .help("Adjust how many elements and values arrays and maps have, so that the mean document size approaches BYTES")
+                .long("mean-size")
+                .value_name("BYTES")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strategy")
+                .help("Make documents as small as allowed, as large as allowed, or anywhere in between")
+                .long("strategy")
+                .possible_values(&["random", "minimal", "maximal"])
+                .default_value("random"),
+        )
+        .arg(
+            Arg::with_name("never-empty")
+                .help("Always generate at least one element or value for the elements and values forms")
+                .long("never-empty"),
+        )
+        .arg(
+            Arg::with_name("container-size")
+                .help("How many elements or values arrays and maps have, and how many additional properties objects have, as a count or an inclusive range like 0..100. Defaults to 0..7")
+                .long("container-size")
+                .value_name("MIN..MAX")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("empty-prob")
+                .help("Generate empty arrays or maps at a schema path of the elements or values form with probability PROB")
+                .long("empty-prob")
+                .value_name("PATH=PROB")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("optional")
+                .help("Weigh how often an optional property is absent, null, or present, like 1,1,2")
+                .long("optional")
+                .value_name("PATH=ABSENT,NULL,PRESENT")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("optional-prob")
+                .help("Probability that an optional property is present, unless --optional weighs it [default: 0.5]")
+                .long("optional-prob")
+                .value_name("PROB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("additional-prob")
+                .help("Probability that an object which allows additional properties is given any. Zero means never [default: 1]")
+                .long("additional-prob")
+                .value_name("PROB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("freeze")
+                .help("Generate non-random values at a schema path. HOW is min, seq, or a JSON value")
+                .long("freeze")
+                .value_name("PATH=HOW")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("set")
+                .help("Put a JSON value at a JSON Pointer into every document, like /tenantId=\"acme\". * matches any array index or property")
+                .long("set")
+                .value_name("POINTER=JSON")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("structure-seed")
+                .help("Derive the structure of each document from this seed, leaving only leaf values random")
+                .long("structure-seed")
+                .value_name("SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .help("Derive every value from this seed, and from where in the schema and document it is")
+                .long("seed")
+                .value_name("SEED")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timestamp-range")
+                .help("Generate timestamps within an inclusive range of RFC 3339 timestamps, like 2020-01-01T00:00:00Z..2021-01-01T00:00:00Z")
+                .long("timestamp-range")
+                .value_name("START..END")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timestamp-precision")
+                .help("How many digits of fractional seconds timestamps have, as a count or an inclusive range like 0..9. Defaults to 0")
+                .long("timestamp-precision")
+                .value_name("DIGITS")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timestamp-offsets")
+                .help("Write timestamps at random offsets from UTC, rather than always in UTC")
+                .long("timestamp-offsets"),
+        )
+        .arg(
+            Arg::with_name("proto-json")
+                .help("Follow proto3 JSON mapping conventions, such as UTC timestamps ending in Z")
+                .long("proto-json"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .help("Only output the parts of documents at these comma-separated JSON Pointers")
+                .long("project")
+                .value_name("POINTERS")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("redact")
+                .help("Mask the parts of documents at these comma-separated JSON Pointers")
+                .long("redact")
+                .value_name("POINTERS")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .help("Check that every document satisfies the schema, and that float32 values are exact f32s")
+                .long("verify"),
+        )
+        .arg(
+            Arg::with_name("tag-validity")
+                .help("Write each document as {\"instance\": ..., \"valid\": ...}, saying whether it satisfies the schema")
+                .long("tag-validity"),
+        )
+        .arg(
+            Arg::with_name("json-patch")
+                .help("After each document, write STEPS RFC 6902 JSON Patches, each changing it into another valid document")
+                .long("json-patch")
+                .value_name("STEPS")
+                .takes_value(true)
+                .conflicts_with_all(&["project", "redact", "tag-validity", "graphql-query"]),
+        )
+        .arg(
+            Arg::with_name("merge-patch")
+                .help("After each document, write STEPS RFC 7386 JSON Merge Patches, each changing it into another valid document")
+                .long("merge-patch")
+                .value_name("STEPS")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "json-patch",
+                    "project",
+                    "redact",
+                    "tag-validity",
+                    "graphql-query",
+                ]),
+        )
+        .arg(
+            Arg::with_name("invalid")
+                .help("Break each document in one place, so that it does not satisfy the schema")
+                .long("invalid")
+                .conflicts_with_all(&["project", "redact", "json-patch", "merge-patch"]),
+        )
+        .arg(
+            Arg::with_name("invalid-kind")
+                .help("Only break documents in these comma-separated ways: wrong-type, missing-required, extra-property, bad-timestamp, out-of-range, bad-enum, or bad-discriminator")
+                .long("invalid-kind")
+                .value_name("KINDS")
+                .requires("invalid"),
+        )
+        .arg(
+            Arg::with_name("novel-only")
+                .help("Leave out documents with the same shape as one already generated, ignoring leaf values")
+                .long("novel-only"),
+        )
+        .arg(
+            Arg::with_name("enum-round-robin")
+                .help("Cycle through every enum value and discriminator variant before choosing them at random")
+                .long("enum-round-robin"),
+        )
+        .arg(
+            Arg::with_name("coverage")
+                .help("Take every enum value, discriminator variant, optional property present and absent, and array and map empty and not, before choosing at random, and report on which were taken to stderr")
+                .long("coverage"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .help("Once done, report how many times each enum value, discriminator variant, optional property present and absent, and array and map empty and not was taken, to stderr")
+                .long("report")
+                .conflicts_with_all(&["giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("branch-decay")
+                .help("Favor discriminator variants with smaller documents, more strongly the more deeply the discriminator is nested within others")
+                .long("branch-decay")
+                .value_name("DECAY"),
+        )
+        .arg(
+            Arg::with_name("focus")
+                .help("Vary the parts of documents at these comma-separated JSON Pointers more than the rest")
+                .long("focus")
+                .value_name("POINTERS")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("graphql-query")
+                .help("Write each document as the variables of a GraphQL request body, alongside the query in this file")
+                .long("graphql-query")
+                .value_name("FILE")
+                .takes_value(true)
+                .conflicts_with("tag-validity"),
+        )
+        .arg(
+            Arg::with_name("graphql-operation")
+                .help("The name of the operation in the GraphQL query to execute")
+                .long("graphql-operation")
+                .value_name("NAME")
+                .requires("graphql-query"),
+        )
+        .arg(
+            Arg::with_name("tenants")
+                .help("Interleave the documents of this many tenants, each generated independently, and write each as {\"tenant\": ..., \"document\": ...}")
+                .long("tenants")
+                .value_name("N")
+                .conflicts_with_all(&["giant", "stream-root", "json-patch", "merge-patch"]),
+        )
+        .arg(
+            Arg::with_name("tenant-mix")
+                .help("How often each tenant's documents come up, as comma-separated relative weights, one per tenant. Defaults to equal weights")
+                .long("tenant-mix")
+                .value_name("WEIGHTS")
+                .requires("tenants"),
+        )
+        .arg(
+            Arg::with_name("soak")
+                .help("Write documents into numbered files in this directory, moving on to a new file every so often")
+                .long("soak")
+                .value_name("DIR")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("corpus")
+                .help("Add each document to this directory as a file named after its hash, unless it is already there")
+                .long("corpus")
+                .value_name("DIR")
+                .takes_value(true)
+                .conflicts_with_all(&["soak", "json-patch", "merge-patch"]),
+        )
+        .arg(
+            Arg::with_name("rotate-bytes")
+                .help("Move on to a new file once the current one holds this many bytes [default: 64 MiB]")
+                .long("rotate-bytes")
+                .value_name("BYTES")
+                .requires("soak"),
+        )
+        .arg(
+            Arg::with_name("rotate-secs")
+                .help("Move on to a new file once the current one is this many seconds old")
+                .long("rotate-secs")
+                .value_name("SECS")
+                .requires("soak"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Write documents to this file, rather than stdout")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .conflicts_with_all(&["soak", "corpus"]),
+        )
+        .arg(
+            Arg::with_name("output-dir")
+                .help("Write each document to a file of its own in this directory, named after --output-template")
+                .long("output-dir")
+                .value_name("DIR")
+                .conflicts_with_all(&["soak", "corpus", "output"]),
+        )
+        .arg(
+            Arg::with_name("post")
+                .help("Send each document in the body of a POST request to this http:// or https:// URL, rather than writing it out, and report how many of each status came back to stderr")
+                .long("post")
+                .value_name("URL")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "output",
+                    "output-dir",
+                    "soak",
+                    "corpus",
+                    "estimate",
+                    "giant",
+                    "stream-root",
+                    "json-patch",
+                    "merge-patch",
+                ]),
+        )
+        .arg(
+            Arg::with_name("header")
+                .help("Add this header, given as 'Name: value', to each request under --post")
+                .long("header")
+                .value_name("HEADER")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("post"),
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .help("How many requests to have in flight at once under --post [default: 1]")
+                .long("concurrency")
+                .value_name("N")
+                .takes_value(true)
+                .requires("post"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .help("Send at most this many requests a second under --post")
+                .long("rate")
+                .value_name("PER_SEC")
+                .takes_value(true)
+                .requires("post"),
+        )
+        .arg(
+            Arg::with_name("sink")
+                .help("Also copy each document to this file, as a line of JSON, or POST it to this http:// or https:// URL. Comma-separated options can follow: sample=P, and on-error=abort|skip|disable")
+                .long("sink")
+                .value_name("SINK")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["giant", "stream-root", "estimate"]),
+        )
+        .arg(
+            Arg::with_name("output-template")
+                .help("The name of each document's file under --output-dir, where {n} is the number of the document, counting from zero")
+                .long("output-template")
+                .value_name("TEMPLATE")
+                .default_value("doc-{n}.json"),
+        )
+        .arg(
+            Arg::with_name("estimate")
+                .help("Instead of writing documents out, predict how big they would be, and how long they would take to generate, from a sample")
+                .long("estimate")
+                .conflicts_with_all(&["soak", "corpus", "output", "output-dir"]),
+        )
+        .arg(
+            Arg::with_name("giant")
+                .help("Write one enormous document, grown wherever the schema allows, without holding it in memory")
+                .long("giant")
+                .requires("target-bytes")
+                .conflicts_with_all(&[
+                    "soak",
+                    "corpus",
+                    "estimate",
+                    "json-patch",
+                    "merge-patch",
+                    "project",
+                    "redact",
+                    "tag-validity",
+                    "graphql-query",
+                ]),
+        )
+        .arg(
+            Arg::with_name("target-bytes")
+                .help("How big a document --giant writes, like 100MB or 2GiB")
+                .long("target-bytes")
+                .value_name("SIZE")
+                .requires("giant"),
+        )
+        .arg(
+            Arg::with_name("stream-root")
+                .help("Write one document whose root array or map has LEN elements or values, writing each out as it is generated")
+                .long("stream-root")
+                .value_name("LEN")
+                .conflicts_with_all(&[
+                    "giant",
+                    "soak",
+                    "corpus",
+                    "estimate",
+                    "json-patch",
+                    "merge-patch",
+                    "project",
+                    "redact",
+                    "tag-validity",
+                    "graphql-query",
+                ]),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .help("Write statistics about the run to this file every so often")
+                .long("stats")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stats-secs")
+                .help("How often to write statistics, in seconds [default: 60]")
+                .long("stats-secs")
+                .value_name("SECS")
+                .takes_value(true)
+                .requires("stats"),
+        )
+        .arg(
+            Arg::with_name("progress-json")
+                .help("Write a line of JSON about the run's progress every so often to this file, or to an open file descriptor given like fd:3")
+                .long("progress-json")
+                .value_name("TARGET")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("progress-secs")
+                .help("How often to write progress, in seconds [default: 1]")
+                .long("progress-secs")
+                .value_name("SECS")
+                .takes_value(true)
+                .requires("progress-json"),
+        )
+        .arg(
+            Arg::with_name("tui")
+                .help("Redraw a dashboard of the run's statistics on stderr as it goes")
+                .long("tui"),
+        )
+        .arg(
+            Arg::with_name("stop-after-bytes")
+                .help("Stop once this many bytes have been written, like 100MB or 2GiB")
+                .long("stop-after-bytes")
+                .value_name("SIZE")
+                .conflicts_with_all(&["estimate", "giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("stop-after-duration")
+                .help("Stop once the run has gone on for this many seconds")
+                .long("stop-after-duration")
+                .value_name("SECS")
+                .conflicts_with_all(&["estimate", "giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("stop-after-coverage")
+                .help("Stop once documents have taken every value of every enum, and every variant of every discriminator, which can be generated")
+                .long("stop-after-coverage")
+                .conflicts_with_all(&["estimate", "giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("record-separator")
+                .help("How to delimit documents. rs produces an RFC 7464 JSON text sequence")
+                .long("record-separator")
+                .possible_values(&["lf", "crlf", "nul", "rs"])
+                .default_value("lf"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("How to write documents out: ndjson, one per record, array, as the elements of a single JSON array, pretty, indented, or form-urlencoded or query-string, one object per line [default: ndjson]")
+                .long("format")
+                .visible_alias("output-format")
+                .takes_value(true)
+                .possible_values(&["ndjson", "array", "pretty", "form-urlencoded", "query-string"])
+                .conflicts_with_all(&["corpus", "giant", "stream-root"]),
+        )
+        .arg(
+            Arg::with_name("encoding")
+                .help("Encode documents as JSON, or as CBOR or MessagePack records, each preceded by its length as a big-endian 32-bit integer")
+                .long("encoding")
+                .possible_values(&["json", "cbor", "msgpack"])
+                .default_value("json"),
+        )
+        .arg(
+            Arg::with_name("delimiter")
+                .help("The line written between documents under --format pretty")
+                .long("delimiter")
+                .value_name("LINE")
+                .default_value(""),
+        )
+        .subcommand(
+            SubCommand::with_name("dict")
+                .about("Writes the literal strings in a schema as an AFL-style dictionary")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("codegen-fixtures")
+                .about("Writes a Rust source file of fixed documents for a schema, as string constants to include! into tests")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("Derive the documents' values from this seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("features")
+                .about("Reports which parts of JDDF a schema uses, and fails if any are unsupported or never generated")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .help("Read generation options from a JSON or TOML file, as the main command does")
+                        .long("config")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pointers")
+                .about("Lists the JSON Pointers into documents of a schema, with * for any array index or values key, or samples them at random")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                )
+                .arg(
+                    Arg::with_name("max-len")
+                        .help("The most tokens a pointer can have")
+                        .long("max-len")
+                        .value_name("TOKENS")
+                        .default_value("8"),
+                )
+                .arg(
+                    Arg::with_name("sample")
+                        .help("Write this many pointers chosen at random, with concrete indices and keys, rather than all of them")
+                        .long("sample")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("Derive the sampled pointers from this seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .takes_value(true)
+                        .requires("sample"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("transform")
+                .about("Generates documents of one schema, each alongside the document a mapping transforms it into, which must satisfy another schema")
+                .arg(
+                    Arg::with_name("INPUT_SCHEMA")
+                        .help("Where to read the schema of input documents from")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT_SCHEMA")
+                        .help("Where to read the schema of output documents from")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("MAPPING")
+                        .help("Where to read the mapping from, as JSON")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("n")
+                        .help("How many pairs to generate. Zero (0) indicates infinity")
+                        .default_value("0")
+                        .short("n")
+                        .long("num-values"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("Derive the documents from this seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serves a new document over HTTP in response to each GET request, or the same one each time for requests with ?seed=N")
+                .arg(
+                    Arg::with_name("SCHEMA")
+                        .help("Where to read schema from")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("listen")
+                        .help("The address and port to listen on")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .default_value("127.0.0.1:8080"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .help("Read generation options from a JSON or TOML file, as the main command does")
+                        .long("config")
+                        .value_name("FILE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("Derive the documents for requests without a seed of their own from this seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-corpus")
+                .about("Generates the documents of a corpus's recorded runs again, and fails if they differ from its files")
+                .arg(
+                    Arg::with_name("SCHEMA")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("CORPUS")
+                        .help("The directory of the corpus, as written by --corpus")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merges corpus directories into one, leaving out duplicate documents")
+                .arg(
+                    Arg::with_name("OUT")
+                        .help("The directory to merge into")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("CORPUS")
+                        .help("The directories of documents to merge")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("schema")
+                        .help("Leave out documents which do not satisfy this schema")
+                        .long("schema")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mutate")
+                .about("Applies random mutations to documents, in ways that keep them valid")
+                .arg(
+                    Arg::with_name("SCHEMA")
+                        .help("Where to read schema from")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("DOCUMENTS")
+                        .help("Where to read documents from, as JSON or a JSON text sequence. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                )
+                .arg(
+                    Arg::with_name("rounds")
+                        .help("How many mutations to apply to each document")
+                        .long("rounds")
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::with_name("mutations")
+                        .help("Only apply these comma-separated mutations: flip-optional, resize-array, mutate-scalar, swap-discriminator, or regenerate")
+                        .long("mutations")
+                        .value_name("NAMES")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("focus")
+                        .help("Mutate the parts of documents at these comma-separated JSON Pointers more than the rest")
+                        .long("focus")
+                        .value_name("POINTERS")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .help("Derive every mutation from this seed, so that the same documents are always mutated the same way")
+                        .long("seed")
+                        .value_name("SEED")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("Validates every file in a corpus directory, and reports on the results")
+                .arg(
+                    Arg::with_name("SCHEMA")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("CORPUS")
+                        .help("The directory of documents to validate")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("quarantine")
+                        .help("Move invalid documents into this directory")
+                        .long("quarantine")
+                        .value_name("DIR")
+                        .takes_value(true),
+                ),
+        );
+
+    #[cfg(feature = "avro")]
+    let app = app
+        .arg(
+            Arg::with_name("avro")
+                .help("Encode documents as Avro, either as length-prefixed records or as an object container file")
+                .long("avro")
+                .possible_values(&["records", "container"])
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("avro-schema")
+                .about("Prints the Avro schema documents are encoded with under --avro")
+                .arg(
+                    Arg::with_name("INPUT")
+                        .help("Where to read schema from. Dash (hypen) indicates stdin")
+                        .default_value("-"),
+                ),
+        );
+
+    let matches = app.get_matches();
+    let stdout = io::stdout();
+
+    #[cfg(feature = "avro")]
+    {
+        if let Some(matches) = matches.subcommand_matches("avro-schema") {
+            let schema = commands::read_schema(matches.value_of("INPUT").unwrap())?;
+            println!("{}", commands::avro_schema(&schema)?);
+            return Ok(());
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("audit") {
+        let schema = commands::read_schema(matches.value_of("SCHEMA").unwrap())?;
+        let report = commands::audit(
+            &schema,
+            Path::new(matches.value_of("CORPUS").unwrap()),
+            matches.value_of("quarantine").map(Path::new),
+        )?;
+
+        println!("{}", report);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("verify-corpus") {
+        // Runs may have been recorded with unsupported parts allowed, and
+        // those which weren't would never have been recorded at all.
+        let schema = SchemaFile::read(matches.value_of("SCHEMA").unwrap(), true)?;
+        let dir = Path::new(matches.value_of("CORPUS").unwrap());
+        let (runs, differences) = commands::verify_corpus(&schema, dir)?;
+
+        println!("{}", json!({ "runs": runs, "differences": differences }));
+        if !differences.is_empty() {
+            bail!("the corpus differs from what its runs generate");
+        }
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("merge") {
+        let schema = match matches.value_of("schema") {
+            Some(path) => Some(commands::read_schema(path)?),
+            None => None,
+        };
+
+        let dirs: Vec<_> = matches
+            .values_of("CORPUS")
+            .unwrap()
+            .map(Path::new)
+            .collect();
+        let out = Path::new(matches.value_of("OUT").unwrap());
+
+        println!("{}", commands::merge(schema.as_ref(), &dirs, out)?);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("mutate") {
+        let schema = commands::read_schema(matches.value_of("SCHEMA").unwrap())?;
+        let docs = commands::read_documents(matches.value_of("DOCUMENTS").unwrap())?;
+        let options = MutateOptions {
+            rounds: matches.value_of("rounds").unwrap().parse()?,
+            mutations: match matches.value_of("mutations") {
+                Some(names) => names.split(',').map(String::from).collect(),
+                None => Vec::new(),
+            },
+            focus: pointers(matches, "focus")?,
+            seed: match matches.value_of("seed") {
+                Some(seed) => Some(seed.parse()?),
+                None => None,
+            },
+        };
+
+        return commands::mutate(&schema, docs, &options, stdout.lock());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("dict") {
+        let schema = commands::read_schema(matches.value_of("INPUT").unwrap())?;
+        return commands::dict(&schema, stdout.lock());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("features") {
+        let schema = commands::read_schema(matches.value_of("INPUT").unwrap())?;
+        let config = read_config(matches.value_of("config"))?;
+
+        let (report, exercised) = commands::features(&schema, &config);
+        println!("{}", report);
+        if !exercised {
+            bail!("the schema uses features which are unsupported, or never generated");
+        }
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("pointers") {
+        let schema = commands::read_schema(matches.value_of("INPUT").unwrap())?;
+        let sample = match matches.value_of("sample") {
+            Some(n) => Some(n.parse()?),
+            None => None,
+        };
+
+        let seed = match matches.value_of("seed") {
+            Some(seed) => Some(seed.parse()?),
+            None => None,
+        };
+
+        return commands::pointers(
+            &schema,
+            matches.value_of("max-len").unwrap().parse()?,
+            sample,
+            seed,
+            stdout.lock(),
+        );
+    }
+
+    if let Some(matches) = matches.subcommand_matches("transform") {
+        let input_schema = commands::read_schema(matches.value_of("INPUT_SCHEMA").unwrap())?;
+        let output_schema = commands::read_schema(matches.value_of("OUTPUT_SCHEMA").unwrap())?;
+        let mapping = serde_json::from_reader(io::BufReader::new(File::open(
+            matches.value_of("MAPPING").unwrap(),
+        )?))?;
+        let seed = match matches.value_of("seed") {
+            Some(seed) => Some(seed.parse()?),
+            None => None,
+        };
+
+        return commands::transform(
+            &input_schema,
+            &output_schema,
+            &mapping,
+            matches.value_of("n").unwrap().parse()?,
+            seed,
+            stdout.lock(),
+        );
+    }
 
-    let num_values: usize = matches.value_of("n").unwrap().parse()?;
+    if let Some(matches) = matches.subcommand_matches("serve") {
+        let schema = commands::read_schema(matches.value_of("SCHEMA").unwrap())?;
+        let config = read_config(matches.value_of("config"))?;
+        let config = FuzzConfig {
+            seed: match matches.value_of("seed") {
+                Some(seed) => Some(seed.parse()?),
+                None => config.seed,
+            },
+            ..config
+        };
 
-    let reader: Box<io::Read> = match matches.value_of("INPUT").unwrap() {
-        "-" => Box::new(io::stdin()),
-        file @ _ => Box::new(io::BufReader::new(File::open(file)?)),
+        let listener = TcpListener::bind(matches.value_of("listen").unwrap())?;
+        eprintln!("listening on http://{}", listener.local_addr()?);
+        return commands::serve(&schema, &config, listener);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("codegen-fixtures") {
+        let input = matches.value_of("INPUT").unwrap();
+        let schema = commands::read_schema(input)?;
+        let seed = matches.value_of("seed").unwrap().parse()?;
+        return commands::codegen_fixtures(&schema, input, seed, stdout.lock());
+    }
+
+    let config = config_from_args(&matches)?;
+    if matches.is_present("print-config") {
+        // A config file can't give these, so the config printed wouldn't
+        // repeat the run.
+        let unrepeatable: Vec<_> = [
+            "focus",
+            "project",
+            "redact",
+            "tenants",
+            "mean-size",
+            "graphql-query",
+            "json-patch",
+            "merge-patch",
+            "format",
+            "encoding",
+        ]
+        .iter()
+        .filter(|name| matches.occurrences_of(**name) > 0)
+        .map(|name| format!("--{}", name))
+        .collect();
+        if !unrepeatable.is_empty() {
+            bail!(
+                "--print-config can't be combined with {}, since a config file can't give them",
+                unrepeatable.join(", ")
+            );
+        }
+
+        eprintln!(
+            "{}",
+            serde_json::to_string_pretty(&config.to_canonical_json())?
+        );
+    }
+
+    let allow_unsupported = config.on_unsupported != UnsupportedBehavior::Error;
+    let schema = match matches.value_of("schema-inline") {
+        Some(_) if matches.occurrences_of("INPUT") > 0 => {
+            bail!("--schema-inline can't be combined with INPUT, since both give the schema")
+        }
+        Some(json) => (|| SchemaFile::from_json(serde_json::from_str(json)?, allow_unsupported))()
+            .map_err(|err| format_err!("in --schema-inline: {}", err))?,
+        None => SchemaFile::read(matches.value_of("INPUT").unwrap(), allow_unsupported)?,
     };
 
-    let serde_schema: SerdeSchema = serde_json::from_reader(reader)?;
-    let schema = Schema::from_serde(serde_schema)?;
+    for warning in generate::warnings(&schema, &config) {
+        eprintln!("warning: {}", warning);
+    }
+
+    let summary = generate::run(&schema, &config, options_from_args(&matches)?)?;
+    for warning in &summary.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    if let Some(posted) = summary.posted {
+        eprintln!("{}", posted);
+    }
 
-    let mut rng = rand::thread_rng();
-    let mut i = 0;
-    while i != num_values || num_values == 0 {
-        println!("{}", fuzz(&schema, &mut rng, &schema));
-        i += 1;
+    if let Some(estimate) = summary.estimate {
+        println!("{}", estimate);
+    }
+
+    if let Some(report) = summary.report {
+        eprintln!("{}", report);
+    }
+
+    if let Some(corpus) = summary.corpus {
+        println!("{}", corpus);
     }
 
     Ok(())
 }
 
-fn fuzz<R: rand::Rng + ?Sized>(root: &Schema, rng: &mut R, schema: &Schema) -> Value {
-    match schema.form() {
-        Form::Empty => fuzz_any(rng),
-        Form::Type(Type::Boolean) => fuzz_bool(rng),
-        Form::Type(Type::Int8) => fuzz_i8(rng),
-        Form::Type(Type::Uint8) => fuzz_u8(rng),
-        Form::Type(Type::Int16) => fuzz_i16(rng),
-        Form::Type(Type::Uint16) => fuzz_u16(rng),
-        Form::Type(Type::Int32) => fuzz_i32(rng),
-        Form::Type(Type::Uint32) => fuzz_u32(rng),
-        Form::Type(Type::Float32) => fuzz_f32(rng),
-        Form::Type(Type::Float64) => fuzz_f64(rng),
-        Form::Type(Type::String) => fuzz_string(rng),
-        Form::Type(Type::Timestamp) => fuzz_timestamp(rng),
-        Form::Enum(ref vals) => fuzz_enum(rng, vals),
-        Form::Elements(ref sub_schema) => fuzz_elems(root, rng, sub_schema),
-        Form::Properties {
-            required,
-            optional,
-            allow_additional,
-            ..
-        } => fuzz_props(root, rng, required, optional, *allow_additional),
-        Form::Values(ref sub_schema) => fuzz_values(root, rng, sub_schema),
-        Form::Discriminator(ref tag, ref mapping) => fuzz_discr(root, rng, tag, mapping),
-        Form::Ref(def) => fuzz(
-            root,
-            rng,
-            root.definitions().as_ref().unwrap().get(def).unwrap(),
-        ),
+/// Reads the config file given with `--config`, if there is one, and applies
+/// the options given on the command line, which take precedence over it.
+fn config_from_args(matches: &ArgMatches) -> Result<FuzzConfig, Error> {
+    let mut config = read_config(matches.value_of("config"))?;
+    // Options given on the command line take precedence over the config file.
+    if matches.is_present("adversarial") {
+        config.adversarial = true;
     }
-}
 
-fn fuzz_any<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    vec![
-        Value::Null,
-        fuzz_bool(rng),
-        fuzz_u8(rng),
-        fuzz_f64(rng),
-        fuzz_string(rng),
-    ]
-    .into_iter()
-    .choose(rng)
-    .unwrap()
-}
+    if matches.is_present("sane") {
+        config.sane = true;
+    }
 
-fn fuzz_bool<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<bool>().into()
-}
+    if matches.is_present("string-edge-cases") {
+        config.string_edge_cases =
+            parse_prob(matches.value_of("string-edge-prob").unwrap_or("0.25"))?;
+    }
 
-fn fuzz_i8<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<i8>().into()
-}
+    if let Some(len) = matches.value_of("string-len") {
+        config.string_len = Some(StringLen::parse(len)?);
+    }
 
-fn fuzz_u8<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<u8>().into()
-}
+    if matches.occurrences_of("charset") > 0 {
+        config.charset = Some(matches.value_of("charset").unwrap().parse()?);
+    }
 
-fn fuzz_i16<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<i16>().into()
-}
+    if matches.is_present("edge-cases") {
+        config.number_edge_cases = parse_prob(matches.value_of("edge-prob").unwrap_or("0.25"))?;
+    }
 
-fn fuzz_u16<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<u16>().into()
-}
+    if matches.occurrences_of("float-policy") > 0 {
+        config.float_policy = matches.value_of("float-policy").unwrap().parse()?;
+    }
 
-fn fuzz_i32<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<i32>().into()
-}
+    if matches.is_present("int-floats") {
+        config.int_floats = parse_prob(matches.value_of("int-float-prob").unwrap_or("0.25"))?;
+    }
 
-fn fuzz_u32<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<u32>().into()
-}
+    if matches.is_present("key-variants") {
+        config.key_variants = parse_prob(matches.value_of("key-variant-prob").unwrap_or("0.25"))?;
+    }
 
-fn fuzz_f32<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<f32>().into()
-}
+    if let Some(depth) = matches.value_of("max-depth") {
+        config.max_depth = Some(depth.parse()?);
+    }
 
-fn fuzz_f64<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    rng.gen::<f64>().into()
-}
+    if matches.occurrences_of("on-depth-limit") > 0 {
+        config.on_depth_limit = matches.value_of("on-depth-limit").unwrap().parse()?;
+    }
 
-fn fuzz_str<R: rand::Rng + ?Sized>(rng: &mut R) -> String {
-    (0..rng.gen_range(0, 8))
-        .map(|_| rng.gen_range(32u8, 127u8) as char)
-        .collect::<String>()
-}
+    if matches.occurrences_of("on-unsupported") > 0 {
+        config.on_unsupported = matches.value_of("on-unsupported").unwrap().parse()?;
+    }
 
-fn fuzz_string<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    fuzz_str(rng).into()
-}
+    if let Some(range) = matches.value_of("timestamp-range") {
+        config.timestamp_range = Some(TimestampRange::parse(range)?);
+    }
 
-fn fuzz_timestamp<R: rand::Rng + ?Sized>(rng: &mut R) -> Value {
-    let date_time = NaiveDateTime::from_timestamp(rng.gen::<i32>() as i64, 0);
-    let date_time = DateTime::<Utc>::from_utc(date_time, Utc);
-    date_time.to_rfc3339().into()
-}
+    if let Some(digits) = matches.value_of("timestamp-precision") {
+        config.timestamp_precision = TimestampPrecision::parse(digits)?;
+    }
 
-fn fuzz_enum<R: rand::Rng + ?Sized>(rng: &mut R, vals: &HashSet<String>) -> Value {
-    vals.iter().choose(rng).unwrap().clone().into()
-}
+    if matches.is_present("timestamp-offsets") {
+        config.timestamp_offsets = true;
+    }
+
+    if matches.is_present("proto-json") {
+        config.proto_json = true;
+    }
+
+    if matches.is_present("verify") {
+        config.verify = true;
+    }
+
+    if matches.is_present("tag-validity") {
+        config.tag_validity = true;
+    }
+
+    if matches.is_present("novel-only") {
+        config.novel_only = true;
+    }
+
+    if matches.is_present("invalid") {
+        config.invalid = true;
+    }
+
+    if let Some(kinds) = matches.value_of("invalid-kind") {
+        config.invalid_kinds = kinds
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+
+    if matches.is_present("enum-round-robin") {
+        config.enum_round_robin = true;
+    }
+
+    if matches.is_present("coverage") {
+        config.coverage = true;
+    }
+
+    if let Some(decay) = matches.value_of("branch-decay") {
+        config.branch_decay = decay.parse()?;
+    }
+
+    if let Some(seed) = matches.value_of("structure-seed") {
+        config.structure_seed = Some(seed.parse()?);
+    }
+
+    if let Some(seed) = matches.value_of("seed") {
+        config.seed = Some(seed.parse()?);
+    }
+
+    for opt in matches.values_of("int-as-string").into_iter().flatten() {
+        let (path, digits) = split_path_option(opt);
+        let digits = match digits {
+            Some(digits) => DigitRange::parse(digits)?,
+            None => DigitRange::default(),
+        };
+
+        config.int_as_string.insert(path.to_owned(), digits);
+    }
+
+    config.homoglyphs.extend(
+        matches
+            .values_of("homoglyphs")
+            .into_iter()
+            .flatten()
+            .map(String::from),
+    );
+
+    for opt in matches.values_of("jwt").into_iter().flatten() {
+        let (path, key) = split_path_option(opt);
+        config.jwt.insert(path.to_owned(), key.map(String::from));
+    }
 
-fn fuzz_elems<R: rand::Rng + ?Sized>(root: &Schema, rng: &mut R, sub_schema: &Schema) -> Value {
-    (0..rng.gen_range(0, 8))
-        .map(|_| fuzz(root, rng, sub_schema))
-        .collect::<Vec<_>>()
-        .into()
+    for opt in matches.values_of("pool").into_iter().flatten() {
+        match split_path_option(opt) {
+            (path, Some(file)) => config.pool.insert(path.to_owned(), file.to_owned()),
+            (path, None) => bail!("missing file of values for schema path: {}", path),
+        };
+    }
+
+    for opt in matches.values_of("ref-depth").into_iter().flatten() {
+        match split_path_option(opt) {
+            (def, Some(depth)) => config.ref_depth.insert(def.to_owned(), depth.parse()?),
+            (def, None) => bail!("missing depth for definition: {}", def),
+        };
+    }
+
+    if matches.occurrences_of("strategy") > 0 {
+        config.strategy = matches.value_of("strategy").unwrap().parse()?;
+    }
+
+    if matches.is_present("never-empty") {
+        config.never_empty = true;
+    }
+
+    if let Some(size) = matches.value_of("container-size") {
+        config.container_size = ContainerSize::parse(size)?;
+    }
+
+    for opt in matches.values_of("empty-prob").into_iter().flatten() {
+        match split_path_option(opt) {
+            (path, Some(prob)) => config.empty_prob.insert(path.to_owned(), parse_prob(prob)?),
+            (path, None) => bail!("missing probability of being empty: {}", path),
+        };
+    }
+
+    for opt in matches.values_of("optional").into_iter().flatten() {
+        match split_path_option(opt) {
+            (path, Some(weights)) => config
+                .optional
+                .insert(path.to_owned(), OptionalWeights::parse(weights)?),
+            (path, None) => bail!("missing weights for optional property: {}", path),
+        };
+    }
+
+    if let Some(prob) = matches.value_of("optional-prob") {
+        config.optional_prob = Some(parse_prob(prob)?);
+    }
+
+    if let Some(prob) = matches.value_of("additional-prob") {
+        config.additional_prob = Some(parse_prob(prob)?);
+    }
+
+    for opt in matches.values_of("freeze").into_iter().flatten() {
+        match split_path_option(opt) {
+            (path, Some(how)) => config.freeze.insert(path.to_owned(), how.parse()?),
+            (path, None) => bail!("missing how to freeze schema path: {}", path),
+        };
+    }
+
+    for opt in matches.values_of("set").into_iter().flatten() {
+        match split_path_option(opt) {
+            (pointer, Some(json)) => {
+                Pointer::parse(pointer)?;
+                let val = serde_json::from_str(json)
+                    .map_err(|err| format_err!("invalid JSON to set at {}: {}", pointer, err))?;
+                config.set.insert(pointer.to_owned(), val)
+            }
+            (pointer, None) => bail!("missing the value to set at JSON Pointer: {}", pointer),
+        };
+    }
+
+    // Options are each checked as they're parsed, but not against each other.
+    config.validate()?;
+    config.pin_now();
+    Ok(config)
 }
 
-fn fuzz_props<R: rand::Rng + ?Sized>(
-    root: &Schema,
-    rng: &mut R,
-    required: &HashMap<String, Schema>,
-    optional: &HashMap<String, Schema>,
-    allow_additional: bool,
-) -> Value {
-    let mut vals = Vec::new();
+/// Gathers the options which say everything about a run besides the schema
+/// and config.
+fn options_from_args(matches: &ArgMatches) -> Result<Options, Error> {
+    let record_separator: RecordSeparator =
+        matches.value_of("record-separator").unwrap().parse()?;
+    let binary = matches.value_of("encoding").unwrap() != "json";
+    if binary && matches.is_present("format") {
+        bail!("--format chooses how JSON is written, so it can't be combined with --encoding");
+    }
 
-    for (k, v) in required {
-        vals.push((k.clone(), fuzz(root, rng, v)));
+    if binary && matches.occurrences_of("record-separator") > 0 {
+        bail!("binary records are delimited by their length, so --record-separator can't be combined with --encoding");
     }
 
-    for (k, v) in optional {
-        if rng.gen() {
-            vals.push((k.clone(), fuzz(root, rng, v)));
+    let format = match matches.value_of("format") {
+        _ if binary => match matches.value_of("encoding").unwrap() {
+            "cbor" => Format::Cbor,
+            _ => Format::MessagePack,
+        },
+        None | Some("ndjson") => Format::Json(record_separator),
+        _ if matches.occurrences_of("record-separator") > 0 => {
+            bail!("--record-separator only applies under --format ndjson")
         }
-    }
+        Some("array") => Format::Array,
+        Some("form-urlencoded") => Format::Form { query: false },
+        Some("query-string") => Format::Form { query: true },
+        _ => Format::Pretty {
+            delimiter: matches.value_of("delimiter").unwrap().to_owned(),
+        },
+    };
 
-    if allow_additional {
-        for _ in 0..rng.gen_range(0, 8) {
-            vals.push((fuzz_str(rng), fuzz_any(rng)));
+    #[cfg(feature = "avro")]
+    let format = match matches.value_of("avro") {
+        Some(avro) => {
+            if matches.is_present("format") {
+                bail!("--format chooses how JSON is written, so it can't be combined with --avro");
+            }
+
+            if matches.occurrences_of("encoding") > 0 {
+                bail!("--encoding and --avro both choose how documents are encoded, so they can't be combined");
+            }
+
+            match avro {
+                "records" => Format::AvroRecords,
+                _ => Format::AvroContainer,
+            }
         }
+        None => format,
+    };
+
+    let output = match matches.value_of("soak") {
+        Some(dir) => Output::Rotating {
+            dir: PathBuf::from(dir),
+            max_bytes: match matches.value_of("rotate-bytes") {
+                Some(bytes) => Some(bytes.parse()?),
+                None if matches.is_present("rotate-secs") => None,
+                None => Some(DEFAULT_ROTATE_BYTES),
+            },
+            max_age: match matches.value_of("rotate-secs") {
+                Some(secs) => Some(Duration::from_secs(secs.parse()?)),
+                None => None,
+            },
+        },
+        None => match (matches.value_of("output"), matches.value_of("output-dir")) {
+            (Some(path), _) => Output::File(PathBuf::from(path)),
+            (_, Some(dir)) => Output::Documents {
+                dir: PathBuf::from(dir),
+                template: matches.value_of("output-template").unwrap().to_owned(),
+            },
+            (None, None) => Output::Stdout,
+        },
+    };
+
+    let post = match matches.value_of("post") {
+        Some(url) => Some(Post {
+            url: url.to_owned(),
+            headers: values(matches, "header"),
+            concurrency: matches.value_of("concurrency").unwrap_or("1").parse()?,
+            rate: match matches.value_of("rate") {
+                Some(rate) => Some(rate.parse()?),
+                None => None,
+            },
+        }),
+        None => None,
+    };
+
+    let tenant_mix = match matches.value_of("tenant-mix") {
+        Some(weights) => Some(
+            weights
+                .split(',')
+                .map(|w| w.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+
+    let giant = match matches.value_of("target-bytes") {
+        Some(size) => Some(parse_bytes(size)?),
+        None => None,
+    };
+
+    let patches = match (
+        matches.value_of("json-patch"),
+        matches.value_of("merge-patch"),
+    ) {
+        (Some(steps), _) => Some(Patches::JsonPatch(steps.parse()?)),
+        (_, Some(steps)) => Some(Patches::MergePatch(steps.parse()?)),
+        _ => None,
+    };
+
+    Ok(Options {
+        count: matches.value_of("n").unwrap().parse()?,
+        format,
+        output,
+        project: pointers(matches, "project")?,
+        focus: pointers(matches, "focus")?,
+        redact: pointers(matches, "redact")?,
+        tenants: matches.value_of("tenants").unwrap_or("1").parse()?,
+        tenant_mix,
+        estimate: matches.is_present("estimate"),
+        post,
+        sinks: values(matches, "sink"),
+        corpus: matches.value_of("corpus").map(PathBuf::from),
+        mean_size: match matches.value_of("mean-size") {
+            Some(bytes) => Some(bytes.parse()?),
+            None => None,
+        },
+        stats: matches.value_of("stats").map(PathBuf::from),
+        stats_interval: Duration::from_secs(
+            matches.value_of("stats-secs").unwrap_or("60").parse()?,
+        ),
+        progress: matches.value_of("progress-json").map(String::from),
+        progress_interval: Duration::from_secs(
+            matches.value_of("progress-secs").unwrap_or("1").parse()?,
+        ),
+        tui: matches.is_present("tui"),
+        stop_after_bytes: match matches.value_of("stop-after-bytes") {
+            Some(size) => Some(parse_bytes(size)?),
+            None => None,
+        },
+        stop_after: match matches.value_of("stop-after-duration") {
+            Some(secs) => Some(Duration::from_secs(secs.parse()?)),
+            None => None,
+        },
+        stop_after_coverage: matches.is_present("stop-after-coverage"),
+        report: matches.is_present("report"),
+        giant,
+        stream_root: match matches.value_of("stream-root") {
+            Some(len) => Some(len.parse()?),
+            None => None,
+        },
+        graphql_query: matches.value_of("graphql-query").map(PathBuf::from),
+        graphql_operation: matches.value_of("graphql-operation").map(String::from),
+        patches,
+    })
+}
+
+/// Reads a config file, or gives the default config if there's no file.
+fn read_config(path: Option<&str>) -> Result<FuzzConfig, Error> {
+    match path {
+        Some(path) => FuzzConfig::read(Path::new(path)),
+        None => Ok(FuzzConfig::default()),
     }
+}
+
+/// Parses the JSON Pointers given with an option, each time as a
+/// comma-separated list.
+fn pointers(matches: &ArgMatches, name: &str) -> Result<Vec<Pointer>, Error> {
+    let mut pointers = Vec::new();
+    for list in matches.values_of(name).into_iter().flatten() {
+        pointers.extend(Pointer::parse_list(list)?);
+    }
+
+    Ok(pointers)
+}
 
-    vals.into_iter()
-        .collect::<serde_json::Map<String, Value>>()
-        .into()
+/// Each value given with an option.
+fn values(matches: &ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(String::from)
+        .collect()
 }
 
-fn fuzz_values<R: rand::Rng + ?Sized>(root: &Schema, rng: &mut R, sub_schema: &Schema) -> Value {
-    (0..rng.gen_range(0, 8))
-        .map(|_| {
-            (
-                fuzz_string(rng).as_str().unwrap().to_owned(),
-                fuzz(root, rng, sub_schema),
-            )
-        })
-        .collect::<serde_json::Map<String, Value>>()
-        .into()
+/// Parses a probability, which must be between zero and one.
+fn parse_prob(s: &str) -> Result<f64, Error> {
+    let prob: f64 = s.parse()?;
+    if !(0.0..=1.0).contains(&prob) {
+        bail!("probability must be between 0 and 1: {}", s);
+    }
+
+    Ok(prob)
 }
 
-fn fuzz_discr<R: rand::Rng + ?Sized>(
-    root: &Schema,
-    rng: &mut R,
-    tag: &str,
-    mapping: &HashMap<String, Schema>,
-) -> Value {
-    let (tag_val, sub_schema) = mapping.iter().choose(rng).unwrap();
-    let mut obj = fuzz(root, rng, sub_schema);
-    obj.as_object_mut()
-        .unwrap()
-        .insert(tag.to_owned(), tag_val.clone().into());
-    obj
+/// Parses a number of bytes, which may be followed by a unit such as KB or
+/// MiB.
+fn parse_bytes(s: &str) -> Result<u64, Error> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "KiB" => 1 << 10,
+        "MB" => 1_000_000,
+        "MiB" => 1 << 20,
+        "GB" => 1_000_000_000,
+        "GiB" => 1 << 30,
+        _ => bail!("invalid size: {}", s),
+    };
+
+    match n.parse::<u64>()?.checked_mul(multiplier) {
+        Some(bytes) => Ok(bytes),
+        None => bail!("size too large: {}", s),
+    }
 }
@@ -0,0 +1,138 @@
+//! Merging several corpus directories into one, without duplicates.
+
+use crate::corpus;
+use crate::input;
+use failure::Error;
+use jddf::{Schema, Validator};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the file, within a merged corpus, listing where each of its
+/// files came from.
+const MANIFEST: &str = "manifest.json";
+
+/// The results of merging corpora.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The files of the merged corpus, by name, and the files each was merged
+    /// from. The first of those is the one which was copied.
+    pub files: Vec<(String, Vec<PathBuf>)>,
+
+    /// Files which were not merged, because they contain a document which
+    /// does not satisfy the schema.
+    pub invalid: Vec<PathBuf>,
+
+    /// Files which were not merged, because they are not JSON at all.
+    pub unparseable: Vec<PathBuf>,
+}
+
+impl Report {
+    /// How many files were not merged, because an identical one was.
+    pub fn duplicates(&self) -> usize {
+        self.files
+            .iter()
+            .map(|(_, sources)| sources.len() - 1)
+            .sum()
+    }
+
+    /// The manifest of the merged corpus, as JSON.
+    pub fn manifest(&self) -> Value {
+        json!({
+            "files": self
+                .files
+                .iter()
+                .map(|(name, sources)| json!({
+                    "file": name,
+                    "sources": sources
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The report as JSON.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "merged": self.files.len(),
+            "duplicates": self.duplicates(),
+            "invalid": self.invalid.len(),
+            "unparseable": self.unparseable.len(),
+        })
+    }
+}
+
+/// Copies every file directly within some directories into another, skipping
+/// files whose documents are the same as those of a file already copied.
+///
+/// Each file is named after a hash of its canonical form, and a manifest lists
+/// which files each was merged from. This is the same layout `--corpus` writes,
+/// so later runs can add to a merged corpus. If a schema is given, files with
+/// a document which does not satisfy it are skipped too.
+pub fn merge(schema: Option<&Schema>, dirs: &[&Path], out: &Path) -> Result<Report, Error> {
+    let validator = Validator::new();
+    let mut report = Report::default();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    fs::create_dir_all(out)?;
+
+    for dir in dirs {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+
+        for path in paths {
+            let name = path.file_name();
+            if !path.is_file()
+                || name == Some(OsStr::new(MANIFEST))
+                || name.is_some_and(corpus::is_metadata)
+            {
+                continue;
+            }
+
+            let bytes = fs::read(&path)?;
+            let instances = match input::parse_documents(&bytes) {
+                Ok(instances) => instances,
+                Err(_) => {
+                    report.unparseable.push(path);
+                    continue;
+                }
+            };
+
+            if let Some(schema) = schema {
+                let mut valid = true;
+                for instance in &instances {
+                    valid = valid && validator.validate(schema, instance)?.is_empty();
+                }
+
+                if !valid {
+                    report.invalid.push(path);
+                    continue;
+                }
+            }
+
+            let canonical = corpus::canonical(&instances);
+            if let Some(&i) = seen.get(&canonical) {
+                report.files[i].1.push(path);
+                continue;
+            }
+
+            let name = corpus::file_name(&canonical);
+            fs::write(out.join(&name), &bytes)?;
+            seen.insert(canonical, report.files.len());
+            report.files.push((name, vec![path]));
+        }
+    }
+
+    fs::write(
+        out.join(MANIFEST),
+        serde_json::to_string_pretty(&report.manifest())?,
+    )?;
+
+    Ok(report)
+}
@@ -0,0 +1,397 @@
+//! Operators which change one valid document into another.
+//!
+//! A [`Mutation`] changes a single value within a document, without making the
+//! document invalid. It applies at a [`Site`]: a value within the document,
+//! along with the schema that value satisfies. [`mutate`] finds every site in
+//! a document, and applies one of the mutations which apply there.
+
+use crate::pointer::Pointer;
+use failure::{format_err, Error};
+use jddf::schema::{Form, Type};
+use jddf::Schema;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use serde_json::Value;
+
+/// How many times more likely a mutation is to apply at a site within a
+/// focused part of the document than elsewhere.
+const FOCUS_WEIGHT: usize = 10;
+
+/// Generates new values, for mutations which add to a document.
+pub trait Generator {
+    /// Generates a value satisfying a schema. The value's schema path and
+    /// instance path are given as unescaped tokens.
+    fn generate(
+        &mut self,
+        rng: &mut dyn RngCore,
+        schema_path: &[String],
+        instance_path: &[String],
+        schema: &Schema,
+    ) -> Value;
+}
+
+/// A value within a document, and the schema it satisfies.
+#[derive(Debug)]
+pub struct Site<'s> {
+    /// The tokens of the path to the value within the document.
+    pub instance_path: Vec<String>,
+
+    /// The tokens of the path to the schema within the root schema.
+    pub schema_path: Vec<String>,
+
+    /// The schema. This is never a ref; refs are resolved to their
+    /// definitions.
+    pub schema: &'s Schema,
+}
+
+/// A way to change a value, such that it still satisfies its schema.
+pub trait Mutation {
+    /// The name of the mutation, as given to `mutate --mutations`.
+    fn name(&self) -> &'static str;
+
+    /// Whether the mutation can change the value at a site.
+    fn applies(&self, site: &Site, val: &Value) -> bool;
+
+    /// Changes the value at a site. This is only called where the mutation
+    /// applies.
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator);
+}
+
+/// Every mutation, in the order they are listed in `--help`.
+pub fn all() -> Vec<Box<dyn Mutation>> {
+    vec![
+        Box::new(FlipOptional),
+        Box::new(ResizeArray),
+        Box::new(MutateScalar),
+        Box::new(SwapDiscriminator),
+        Box::new(Regenerate),
+    ]
+}
+
+/// Looks up a mutation by its name.
+pub fn by_name(name: &str) -> Result<Box<dyn Mutation>, Error> {
+    all()
+        .into_iter()
+        .find(|mutation| mutation.name() == name)
+        .ok_or_else(|| format_err!("unknown mutation: {}", name))
+}
+
+/// Finds every site within a document, which must satisfy the root schema.
+///
+/// The object for a discriminator is two sites: one for the discriminator, and
+/// one for the properties of the variant its tag selects.
+pub fn sites<'s>(root: &'s Schema, doc: &Value) -> Vec<Site<'s>> {
+    let mut out = Vec::new();
+    find_sites(root, root, doc, &mut Vec::new(), &mut Vec::new(), &mut out);
+    out
+}
+
+fn find_sites<'s>(
+    root: &'s Schema,
+    schema: &'s Schema,
+    val: &Value,
+    instance_path: &mut Vec<String>,
+    schema_path: &mut Vec<String>,
+    out: &mut Vec<Site<'s>>,
+) {
+    if let Form::Ref(def) = schema.form() {
+        let definition = &root.definitions().as_ref().unwrap()[def];
+        let path = std::mem::replace(schema_path, vec!["definitions".to_owned(), def.clone()]);
+        find_sites(root, definition, val, instance_path, schema_path, out);
+        *schema_path = path;
+        return;
+    }
+
+    out.push(Site {
+        instance_path: instance_path.clone(),
+        schema_path: schema_path.clone(),
+        schema,
+    });
+
+    match (schema.form(), val) {
+        (Form::Elements(sub_schema), Value::Array(vals)) => {
+            schema_path.push("elements".to_owned());
+            for (i, val) in vals.iter().enumerate() {
+                instance_path.push(i.to_string());
+                find_sites(root, sub_schema, val, instance_path, schema_path, out);
+                instance_path.pop();
+            }
+            schema_path.pop();
+        }
+        (
+            Form::Properties {
+                required, optional, ..
+            },
+            Value::Object(vals),
+        ) => {
+            for (k, val) in vals {
+                let (token, sub_schema) = match (required.get(k), optional.get(k)) {
+                    (Some(sub_schema), _) => ("properties", sub_schema),
+                    (None, Some(sub_schema)) => ("optionalProperties", sub_schema),
+                    (None, None) => continue,
+                };
+
+                instance_path.push(k.clone());
+                schema_path.extend(vec![token.to_owned(), k.clone()]);
+                find_sites(root, sub_schema, val, instance_path, schema_path, out);
+                schema_path.truncate(schema_path.len() - 2);
+                instance_path.pop();
+            }
+        }
+        (Form::Values(sub_schema), Value::Object(vals)) => {
+            schema_path.push("values".to_owned());
+            for (k, val) in vals {
+                instance_path.push(k.clone());
+                find_sites(root, sub_schema, val, instance_path, schema_path, out);
+                instance_path.pop();
+            }
+            schema_path.pop();
+        }
+        (Form::Discriminator(tag, mapping), Value::Object(vals)) => {
+            if let Some(tag_val) = vals.get(tag).and_then(Value::as_str) {
+                if let Some(sub_schema) = mapping.get(tag_val) {
+                    schema_path.extend(vec![
+                        "discriminator".to_owned(),
+                        "mapping".to_owned(),
+                        tag_val.to_owned(),
+                    ]);
+                    find_sites(root, sub_schema, val, instance_path, schema_path, out);
+                    schema_path.truncate(schema_path.len() - 3);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Applies one of the given mutations somewhere within a document, choosing at
+/// random among every site and every mutation which applies there.
+///
+/// Sites within any of the parts of the document that `focus` points to are
+/// favored over the rest.
+///
+/// Returns the name of the mutation applied, or `None` if none of them apply
+/// anywhere in the document.
+pub fn mutate(
+    root: &Schema,
+    doc: &mut Value,
+    mutations: &[Box<dyn Mutation>],
+    focus: &[Pointer],
+    rng: &mut dyn RngCore,
+    gen: &mut dyn Generator,
+) -> Option<&'static str> {
+    let sites = sites(root, doc);
+
+    let mut candidates = Vec::new();
+    for (i, site) in sites.iter().enumerate() {
+        let val = doc.pointer(&Pointer(site.instance_path.clone()).to_string())?;
+        let weight = if focus.iter().any(|p| p.contains(&site.instance_path)) {
+            FOCUS_WEIGHT
+        } else {
+            1
+        };
+
+        for (j, mutation) in mutations.iter().enumerate() {
+            if mutation.applies(site, val) {
+                candidates.extend((0..weight).map(|_| (i, j)));
+            }
+        }
+    }
+
+    let &(i, j) = candidates.choose(&mut *rng)?;
+    let site = &sites[i];
+    let val = doc.pointer_mut(&Pointer(site.instance_path.clone()).to_string())?;
+    mutations[j].apply(site, val, rng, gen);
+    Some(mutations[j].name())
+}
+
+/// Removes an optional property if it is present, or adds it if it is absent.
+pub struct FlipOptional;
+
+impl Mutation for FlipOptional {
+    fn name(&self) -> &'static str {
+        "flip-optional"
+    }
+
+    fn applies(&self, site: &Site, val: &Value) -> bool {
+        match site.schema.form() {
+            Form::Properties { optional, .. } => !optional.is_empty() && val.is_object(),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator) {
+        if let Form::Properties { optional, .. } = site.schema.form() {
+            let mut keys: Vec<_> = optional.keys().collect();
+            keys.sort();
+            let k = *keys.choose(&mut *rng).unwrap();
+
+            let obj = val.as_object_mut().unwrap();
+            if obj.remove(k).is_none() {
+                let mut path = site.schema_path.clone();
+                path.extend(vec!["optionalProperties".to_owned(), k.clone()]);
+                let mut instance_path = site.instance_path.clone();
+                instance_path.push(k.clone());
+
+                let sub_val = gen.generate(rng, &path, &instance_path, &optional[k]);
+                obj.insert(k.clone(), sub_val);
+            }
+        }
+    }
+}
+
+/// Changes how many elements an array has, by removing elements from its end
+/// or adding new ones there.
+pub struct ResizeArray;
+
+impl Mutation for ResizeArray {
+    fn name(&self) -> &'static str {
+        "resize-array"
+    }
+
+    fn applies(&self, site: &Site, val: &Value) -> bool {
+        match site.schema.form() {
+            Form::Elements(_) => val.is_array(),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator) {
+        if let Form::Elements(sub_schema) = site.schema.form() {
+            let vals = val.as_array_mut().unwrap();
+
+            // Anywhere from empty to about twice as long, but never the same.
+            let mut len = rng.gen_range(0, vals.len() * 2 + 2);
+            if len == vals.len() {
+                len += 1;
+            }
+
+            let mut path = site.schema_path.clone();
+            path.push("elements".to_owned());
+
+            vals.truncate(len);
+            while vals.len() < len {
+                let mut instance_path = site.instance_path.clone();
+                instance_path.push(vals.len().to_string());
+                vals.push(gen.generate(rng, &path, &instance_path, sub_schema));
+            }
+        }
+    }
+}
+
+/// Replaces a boolean, number, string, timestamp, or enum value with another.
+///
+/// Integers are kept within the range of their type, and are often moved to
+/// one end of that range, or just next to where they were.
+pub struct MutateScalar;
+
+impl Mutation for MutateScalar {
+    fn name(&self) -> &'static str {
+        "mutate-scalar"
+    }
+
+    fn applies(&self, site: &Site, _val: &Value) -> bool {
+        match site.schema.form() {
+            Form::Type(_) => true,
+            Form::Enum(vals) => vals.len() > 1,
+            _ => false,
+        }
+    }
+
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator) {
+        match (site.schema.form(), &*val) {
+            (Form::Type(Type::Boolean), Value::Bool(b)) => *val = (!b).into(),
+            (Form::Type(typ), Value::Number(n))
+                if crate::int_range(typ).is_some() && n.is_i64() =>
+            {
+                let (min, max) = crate::int_range(typ).unwrap();
+                let n = n.as_i64().unwrap();
+                *val = match rng.gen_range(0, 4) {
+                    0 => min,
+                    1 => max,
+                    2 if n == max || (n > min && rng.gen()) => n - 1,
+                    2 => n + 1,
+                    _ => rng.gen_range(min, max + 1),
+                }
+                .into();
+            }
+            (Form::Enum(vals), _) => {
+                let mut vals: Vec<_> = vals
+                    .iter()
+                    .filter(|v| Some(v.as_str()) != val.as_str())
+                    .collect();
+                vals.sort();
+                *val = (*vals.choose(&mut *rng).unwrap()).clone().into();
+            }
+            _ => *val = gen.generate(rng, &site.schema_path, &site.instance_path, site.schema),
+        }
+    }
+}
+
+/// Replaces the object for a discriminator with one for a different variant,
+/// and sets its tag to match.
+pub struct SwapDiscriminator;
+
+impl Mutation for SwapDiscriminator {
+    fn name(&self) -> &'static str {
+        "swap-discriminator"
+    }
+
+    fn applies(&self, site: &Site, val: &Value) -> bool {
+        match site.schema.form() {
+            Form::Discriminator(_, mapping) => mapping.len() > 1 && val.is_object(),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator) {
+        if let Form::Discriminator(tag, mapping) = site.schema.form() {
+            let current = val.get(tag).and_then(Value::as_str);
+            let mut tag_vals: Vec<_> = mapping
+                .keys()
+                .filter(|tag_val| Some(tag_val.as_str()) != current)
+                .collect();
+            tag_vals.sort();
+            let tag_val = *tag_vals.choose(&mut *rng).unwrap();
+
+            let mut path = site.schema_path.clone();
+            path.extend(vec![
+                "discriminator".to_owned(),
+                "mapping".to_owned(),
+                tag_val.clone(),
+            ]);
+
+            let mut obj = gen.generate(rng, &path, &site.instance_path, &mapping[tag_val]);
+            obj.as_object_mut()
+                .unwrap()
+                .insert(tag.to_owned(), tag_val.clone().into());
+            *val = obj;
+        }
+    }
+}
+
+/// Replaces a value within the document with a newly generated one for its
+/// schema, keeping the rest of the document as it was.
+///
+/// The object for a discriminator is only regenerated as a whole, tag and
+/// all, rather than as the properties of its variant.
+pub struct Regenerate;
+
+impl Mutation for Regenerate {
+    fn name(&self) -> &'static str {
+        "regenerate"
+    }
+
+    fn applies(&self, site: &Site, _val: &Value) -> bool {
+        let variant = match site.schema_path.len() {
+            n if n >= 3 => site.schema_path[n - 3..n - 1] == ["discriminator", "mapping"],
+            _ => false,
+        };
+
+        !site.instance_path.is_empty() && !variant
+    }
+
+    fn apply(&self, site: &Site, val: &mut Value, rng: &mut dyn RngCore, gen: &mut dyn Generator) {
+        *val = gen.generate(rng, &site.schema_path, &site.instance_path, site.schema);
+    }
+}
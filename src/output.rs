@@ -0,0 +1,761 @@
+//! Writing generated documents out.
+
+#[cfg(feature = "avro")]
+use crate::avro::{AvroSchema, ContainerWriter};
+use crate::binary;
+use crate::form;
+use crate::http;
+use failure::{bail, format_err, Error};
+use rand::Rng;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How consecutive documents are delimited from one another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordSeparator {
+    /// Each document is followed by a newline.
+    Lf,
+
+    /// Each document is followed by a carriage return and a newline.
+    CrLf,
+
+    /// Each document is followed by a NUL byte, as `xargs -0` expects.
+    Nul,
+
+    /// Each document is preceded by an ASCII record separator (0x1E) and
+    /// followed by a newline, as in RFC 7464 JSON text sequences.
+    Rs,
+}
+
+impl FromStr for RecordSeparator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "lf" => Ok(RecordSeparator::Lf),
+            "crlf" => Ok(RecordSeparator::CrLf),
+            "nul" => Ok(RecordSeparator::Nul),
+            "rs" => Ok(RecordSeparator::Rs),
+            _ => Err(format_err!("unknown record separator: {}", s)),
+        }
+    }
+}
+
+impl RecordSeparator {
+    /// The bytes written before each document.
+    pub fn prefix(self) -> &'static [u8] {
+        match self {
+            RecordSeparator::Rs => b"\x1e",
+            _ => b"",
+        }
+    }
+
+    /// The bytes written after each document.
+    pub fn suffix(self) -> &'static [u8] {
+        match self {
+            RecordSeparator::Lf | RecordSeparator::Rs => b"\n",
+            RecordSeparator::CrLf => b"\r\n",
+            RecordSeparator::Nul => b"\0",
+        }
+    }
+}
+
+/// Writes a single document, delimited by a record separator.
+pub fn write_record<W: Write>(out: &mut W, sep: RecordSeparator, val: &Value) -> io::Result<()> {
+    out.write_all(sep.prefix())?;
+    serde_json::to_writer(&mut *out, val)?;
+    out.write_all(sep.suffix())
+}
+
+/// How documents are encoded when written out.
+#[derive(Debug)]
+pub enum Encoder {
+    /// Each document is JSON, delimited by a record separator.
+    Json(RecordSeparator),
+
+    /// Each file holds a single JSON array, whose elements are the documents,
+    /// one per line.
+    Array {
+        /// Whether the current file's array has been begun.
+        open: bool,
+
+        /// Whether no array has been begun at all, so that one is written even
+        /// if there are no documents.
+        empty: bool,
+    },
+
+    /// Each document is indented JSON followed by a newline, with a line
+    /// holding a delimiter between consecutive documents in a file.
+    Pretty {
+        delimiter: String,
+
+        /// Whether a document has been written to the current file.
+        started: bool,
+    },
+
+    /// Each document is form data, or a query string if `query`, followed by
+    /// a newline.
+    Form { query: bool },
+
+    /// Each document is CBOR, preceded by its length in bytes as a big-endian
+    /// 32-bit integer.
+    Cbor,
+
+    /// Each document is MessagePack, preceded by its length in bytes as a
+    /// big-endian 32-bit integer.
+    MessagePack,
+
+    /// Each document is an Avro datum, preceded by its length in bytes as a
+    /// big-endian 32-bit integer.
+    #[cfg(feature = "avro")]
+    AvroRecords(AvroSchema),
+
+    /// Documents are written as an Avro object container file.
+    #[cfg(feature = "avro")]
+    AvroContainer(ContainerWriter),
+}
+
+impl Encoder {
+    pub fn array() -> Self {
+        Encoder::Array {
+            open: false,
+            empty: true,
+        }
+    }
+
+    pub fn pretty(delimiter: &str) -> Self {
+        Encoder::Pretty {
+            delimiter: delimiter.to_owned(),
+            started: false,
+        }
+    }
+
+    pub fn write<W: Write>(&mut self, out: &mut W, val: &Value) -> Result<(), Error> {
+        match self {
+            Encoder::Json(sep) => write_record(out, *sep, val)?,
+            Encoder::Array { open, empty } => {
+                out.write_all(if *open { b",\n" } else { b"[\n" })?;
+                *open = true;
+                *empty = false;
+
+                // As io::Errors, failures to write are found by is_broken_pipe.
+                serde_json::to_writer(&mut *out, val).map_err(io::Error::from)?;
+            }
+            Encoder::Pretty { delimiter, started } => {
+                if *started {
+                    writeln!(out, "{}", delimiter)?;
+                }
+
+                *started = true;
+                serde_json::to_writer_pretty(&mut *out, val).map_err(io::Error::from)?;
+                out.write_all(b"\n")?;
+            }
+
+            Encoder::Form { query } => writeln!(out, "{}", form::encode(val, *query)?)?,
+
+            Encoder::Cbor => binary::write_record(out, &binary::cbor(val)?)?,
+            Encoder::MessagePack => binary::write_record(out, &binary::msgpack(val)?)?,
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroRecords(schema) => {
+                let mut datum = Vec::new();
+                schema.encode(val, &mut datum)?;
+                binary::write_record(out, &datum)?;
+            }
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroContainer(writer) => writer.write(out, val)?,
+        }
+
+        Ok(())
+    }
+
+    /// A document encoded as the body of a request of its own, without any of
+    /// what separates it from other documents in a file.
+    pub fn body(&self, val: &Value) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+        match self {
+            Encoder::Json(_) | Encoder::Array { .. } => serde_json::to_writer(&mut body, val)?,
+            Encoder::Pretty { .. } => serde_json::to_writer_pretty(&mut body, val)?,
+            Encoder::Form { query } => body = form::encode(val, *query)?.into_bytes(),
+            Encoder::Cbor => body = binary::cbor(val)?,
+            Encoder::MessagePack => body = binary::msgpack(val)?,
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroRecords(schema) => schema.encode(val, &mut body)?,
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroContainer(_) => bail!("an Avro container file can't be split into bodies"),
+        }
+
+        Ok(body)
+    }
+
+    /// The content type of bodies encoded this way.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoder::Json(_) | Encoder::Array { .. } | Encoder::Pretty { .. } => "application/json",
+            Encoder::Form { .. } => "application/x-www-form-urlencoded",
+            Encoder::Cbor => "application/cbor",
+            Encoder::MessagePack => "application/msgpack",
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroRecords(_) | Encoder::AvroContainer(_) => "avro/binary",
+        }
+    }
+
+    /// The extension of files holding documents encoded this way.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoder::Json(_) | Encoder::Array { .. } | Encoder::Pretty { .. } => "json",
+            Encoder::Form { .. } => "txt",
+            Encoder::Cbor => "cbor",
+            Encoder::MessagePack => "msgpack",
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroRecords(_) => "bin",
+
+            #[cfg(feature = "avro")]
+            Encoder::AvroContainer(_) => "avro",
+        }
+    }
+
+    /// Writes out anything the encoder has buffered, before moving on to
+    /// writing a new file.
+    pub fn end_file<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self {
+            Encoder::Array { open, .. } if *open => {
+                out.write_all(b"\n]\n")?;
+                *open = false;
+            }
+            Encoder::Pretty { started, .. } => *started = false,
+            #[cfg(feature = "avro")]
+            Encoder::AvroContainer(writer) => writer.end_file(out)?,
+            _ => {}
+        }
+
+        out.flush()
+    }
+
+    /// Writes out anything the encoder has buffered.
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self {
+            Encoder::Array { open: true, .. } => out.write_all(b"\n]\n")?,
+            Encoder::Array { empty: true, .. } => out.write_all(b"[]\n")?,
+            #[cfg(feature = "avro")]
+            Encoder::AvroContainer(writer) => writer.flush(out)?,
+            _ => {}
+        }
+
+        out.flush()
+    }
+}
+
+/// Where documents are written out to.
+#[derive(Debug)]
+pub struct Sink<'a> {
+    dest: Dest<'a>,
+
+    /// How many bytes have been written, in total.
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+enum Dest<'a> {
+    Stdout(io::BufWriter<Timed<io::StdoutLock<'a>>>),
+    File(io::BufWriter<Timed<File>>),
+    Files(RotatingFiles),
+    Documents(DocumentFiles),
+    Discard,
+}
+
+impl<'a> Sink<'a> {
+    pub fn stdout(stdout: io::StdoutLock<'a>) -> Self {
+        Sink {
+            dest: Dest::Stdout(io::BufWriter::new(Timed::new(stdout))),
+            bytes: 0,
+        }
+    }
+
+    /// Writes to a file, replacing anything already in it.
+    pub fn file(path: &Path) -> io::Result<Self> {
+        Ok(Sink {
+            dest: Dest::File(io::BufWriter::new(Timed::new(File::create(path)?))),
+            bytes: 0,
+        })
+    }
+
+    pub fn files(files: RotatingFiles) -> Self {
+        Sink {
+            dest: Dest::Files(files),
+            bytes: 0,
+        }
+    }
+
+    pub fn documents(files: DocumentFiles) -> Self {
+        Sink {
+            dest: Dest::Documents(files),
+            bytes: 0,
+        }
+    }
+
+    /// Counts the bytes written, but otherwise throws them away.
+    pub fn discard() -> Self {
+        Sink {
+            dest: Dest::Discard,
+            bytes: 0,
+        }
+    }
+
+    /// Whether it's time to move on to a new file. This is never the case when
+    /// writing to stdout or a single file, and always the case after a
+    /// document when writing each to its own file.
+    pub fn rotation_due(&self) -> bool {
+        match self.dest {
+            Dest::Stdout(_) | Dest::File(_) | Dest::Discard => false,
+            Dest::Files(ref files) => files.due(),
+            Dest::Documents(_) => true,
+        }
+    }
+
+    /// Moves on to a new file.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        match self.dest {
+            Dest::Stdout(_) | Dest::File(_) | Dest::Discard => Ok(()),
+            Dest::Files(ref mut files) => files.rotate(),
+            Dest::Documents(ref mut files) => files.rotate(),
+        }
+    }
+
+    /// How long has been spent waiting for writes to complete, in total.
+    ///
+    /// Writes block when whatever is reading our output, such as a pipe or a
+    /// disk, cannot keep up. Generation waits for it rather than buffering
+    /// without bound, so this is how long generation was held up.
+    pub fn blocked(&self) -> Duration {
+        match self.dest {
+            Dest::Stdout(ref out) => out.get_ref().blocked,
+            Dest::File(ref out) => out.get_ref().blocked,
+            Dest::Files(ref files) => files.blocked(),
+            Dest::Documents(ref files) => files.blocked(),
+            Dest::Discard => Duration::from_secs(0),
+        }
+    }
+}
+
+impl<'a> Write for Sink<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match self.dest {
+            Dest::Stdout(ref mut out) => out.write(buf)?,
+            Dest::File(ref mut out) => out.write(buf)?,
+            Dest::Files(ref mut files) => files.write(buf)?,
+            Dest::Documents(ref mut files) => files.write(buf)?,
+            Dest::Discard => buf.len(),
+        };
+
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.dest {
+            Dest::Stdout(ref mut out) => out.flush(),
+            Dest::File(ref mut out) => out.flush(),
+            Dest::Files(ref mut files) => files.flush(),
+            Dest::Documents(ref mut files) => files.flush(),
+            Dest::Discard => Ok(()),
+        }
+    }
+}
+
+/// Numbered files within a directory, which are written to one at a time,
+/// moving on to the next once the current one is big or old enough.
+#[derive(Debug)]
+pub struct RotatingFiles {
+    dir: PathBuf,
+    extension: &'static str,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+
+    /// The number of the current file.
+    index: u64,
+    file: io::BufWriter<Timed<File>>,
+    bytes: u64,
+    opened: Instant,
+
+    /// How long was spent waiting for writes to files before the current one.
+    blocked: Duration,
+}
+
+impl RotatingFiles {
+    /// Begins writing to the files in a directory, creating it if need be.
+    ///
+    /// Numbering starts after any files already in the directory, so that
+    /// restarting a run does not overwrite what it wrote before.
+    pub fn new(
+        dir: &Path,
+        extension: &'static str,
+        max_bytes: Option<u64>,
+        max_age: Option<Duration>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let mut index = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let n = path
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse::<u64>().ok());
+            if let Some(n) = n {
+                index = index.max(n + 1);
+            }
+        }
+
+        Ok(RotatingFiles {
+            file: Self::open(dir, extension, index)?,
+            dir: dir.to_owned(),
+            extension,
+            max_bytes,
+            max_age,
+            index,
+            bytes: 0,
+            opened: Instant::now(),
+            blocked: Duration::from_secs(0),
+        })
+    }
+
+    fn open(dir: &Path, extension: &str, index: u64) -> io::Result<io::BufWriter<Timed<File>>> {
+        let path = dir.join(format!("{:06}.{}", index, extension));
+        Ok(io::BufWriter::new(Timed::new(File::create(path)?)))
+    }
+
+    /// How long has been spent waiting for writes to files, in total.
+    pub fn blocked(&self) -> Duration {
+        self.blocked + self.file.get_ref().blocked
+    }
+
+    /// Whether the current file is big or old enough to move on from.
+    pub fn due(&self) -> bool {
+        let too_big = self.max_bytes.is_some_and(|max| self.bytes >= max);
+        let too_old = self.max_age.is_some_and(|max| self.opened.elapsed() >= max);
+        too_big || too_old
+    }
+
+    /// Finishes the current file, and moves on to the next.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.blocked += self.file.get_ref().blocked;
+        self.index += 1;
+        self.file = Self::open(&self.dir, self.extension, self.index)?;
+        self.bytes = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFiles {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A file for each document within a directory, named after a template in
+/// which `{n}` is the number of the document, counting from zero.
+#[derive(Debug)]
+pub struct DocumentFiles {
+    dir: PathBuf,
+    template: String,
+
+    /// The number of the current document.
+    n: u64,
+
+    /// The current document's file, once anything has been written to it.
+    file: Option<io::BufWriter<Timed<File>>>,
+
+    /// How long was spent waiting for writes to files before the current one.
+    blocked: Duration,
+}
+
+impl DocumentFiles {
+    /// Begins writing documents to files in a directory, creating it if need
+    /// be. Files already there with the same names are replaced.
+    pub fn new(dir: &Path, template: &str) -> Result<Self, Error> {
+        if !template.contains("{n}") {
+            return Err(format_err!(
+                "file name template must contain {{n}}, so that each document has its own file: {}",
+                template
+            ));
+        }
+
+        fs::create_dir_all(dir)?;
+        Ok(DocumentFiles {
+            dir: dir.to_owned(),
+            template: template.to_owned(),
+            n: 0,
+            file: None,
+            blocked: Duration::from_secs(0),
+        })
+    }
+
+    /// How long has been spent waiting for writes to files, in total.
+    pub fn blocked(&self) -> Duration {
+        self.blocked
+            + self
+                .file
+                .as_ref()
+                .map_or(Duration::from_secs(0), |file| file.get_ref().blocked)
+    }
+
+    /// Finishes the current document's file, and moves on to the next.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+            self.blocked += file.get_ref().blocked;
+        }
+
+        self.n += 1;
+        Ok(())
+    }
+}
+
+impl Write for DocumentFiles {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() {
+            let name = self.template.replace("{n}", &self.n.to_string());
+            let file = File::create(self.dir.join(name))?;
+            self.file = Some(io::BufWriter::new(Timed::new(file)));
+        }
+
+        self.file.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file {
+            Some(ref mut file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Another destination which documents are copied to, alongside the main
+/// output, as given with `--sink`. Files get a line of JSON for each document,
+/// and `http://` or `https://` URLs a POST request with each one.
+///
+/// As a string, this is the file or URL, optionally followed by
+/// comma-separated options: `sample=P`, to copy each document with
+/// probability `P`, and `on-error=abort|skip|disable`, which is what to do
+/// when a document can't be copied: end the run, which is the default, leave
+/// out that document, or leave out every document from then on.
+#[derive(Debug)]
+pub struct Tee {
+    spec: String,
+    dest: TeeDest,
+    sample: Option<f64>,
+    on_error: TeeFailure,
+
+    /// How many documents couldn't be copied.
+    failures: u64,
+
+    /// The first error copying a document, if there has been one.
+    first_error: Option<String>,
+}
+
+#[derive(Debug)]
+enum TeeDest {
+    File(io::BufWriter<File>),
+    Http(http::Endpoint),
+    Disabled,
+}
+
+/// What to do when a document can't be copied to a tee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TeeFailure {
+    Abort,
+    Skip,
+    Disable,
+}
+
+impl FromStr for Tee {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.split(',');
+        let target = parts.next().unwrap();
+
+        let mut sample = None;
+        let mut on_error = TeeFailure::Abort;
+        for option in parts {
+            match option.find('=').map(|i| (&option[..i], &option[i + 1..])) {
+                Some(("sample", prob)) => {
+                    let prob: f64 = prob.parse()?;
+                    if !(0.0..=1.0).contains(&prob) {
+                        bail!("probability must be between 0 and 1: {}", prob);
+                    }
+
+                    sample = Some(prob);
+                }
+                Some(("on-error", "abort")) => on_error = TeeFailure::Abort,
+                Some(("on-error", "skip")) => on_error = TeeFailure::Skip,
+                Some(("on-error", "disable")) => on_error = TeeFailure::Disable,
+                _ => bail!(
+                    "unknown sink option {:?} (expected sample=P or on-error=abort|skip|disable): {}",
+                    option,
+                    s
+                ),
+            }
+        }
+
+        let dest = if target.contains("://") {
+            TeeDest::Http(http::Endpoint::parse(target)?)
+        } else {
+            TeeDest::File(io::BufWriter::new(File::create(target)?))
+        };
+
+        Ok(Tee {
+            spec: s.to_owned(),
+            dest,
+            sample,
+            on_error,
+            failures: 0,
+            first_error: None,
+        })
+    }
+}
+
+impl Tee {
+    /// Copies a document, unless it is left out of the sample.
+    ///
+    /// Fails only if the document can't be copied and the tee's failures
+    /// should end the run.
+    pub fn write<R: Rng + ?Sized>(&mut self, rng: &mut R, val: &Value) -> Result<(), Error> {
+        if let TeeDest::Disabled = self.dest {
+            return Ok(());
+        }
+
+        if let Some(prob) = self.sample {
+            if !rng.gen_bool(prob) {
+                return Ok(());
+            }
+        }
+
+        let result = match self.dest {
+            TeeDest::File(ref mut out) => {
+                write_record(out, RecordSeparator::Lf, val).map_err(Error::from)
+            }
+            TeeDest::Http(ref endpoint) => {
+                match endpoint.post("application/json", &[], val.to_string().as_bytes()) {
+                    Ok(status) if (200..300).contains(&status) => Ok(()),
+                    Ok(status) => Err(format_err!("{} responded with status {}", endpoint, status)),
+                    Err(err) => Err(err),
+                }
+            }
+            TeeDest::Disabled => Ok(()),
+        };
+
+        if let Err(err) = result {
+            match self.on_error {
+                TeeFailure::Abort => bail!("sink {}: {}", self.spec, err),
+                TeeFailure::Skip => {}
+                TeeFailure::Disable => self.dest = TeeDest::Disabled,
+            }
+
+            self.failures += 1;
+            self.first_error.get_or_insert_with(|| err.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Flushes anything written to a file.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if let TeeDest::File(ref mut out) = self.dest {
+            if let Err(err) = out.flush() {
+                match self.on_error {
+                    TeeFailure::Abort => bail!("sink {}: {}", self.spec, err),
+                    _ => {
+                        self.failures += 1;
+                        self.first_error.get_or_insert_with(|| err.to_string());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A description of how copying to the tee failed, if it ever did.
+    pub fn warning(&self) -> Option<String> {
+        let first_error = self.first_error.as_ref()?;
+        Some(match self.on_error {
+            TeeFailure::Disable => format!(
+                "sink {} was disabled after failing: {}",
+                self.spec, first_error
+            ),
+            _ => format!(
+                "sink {} failed {} times, first with: {}",
+                self.spec, self.failures, first_error
+            ),
+        })
+    }
+}
+
+/// A writer which keeps track of how long writes to it take.
+///
+/// This goes beneath any buffering, so that only the writes which actually
+/// reach the destination, and so may block, are timed.
+#[derive(Debug)]
+pub struct Timed<W> {
+    inner: W,
+
+    /// How long has been spent in writes and flushes.
+    pub blocked: Duration,
+}
+
+impl<W> Timed<W> {
+    pub fn new(inner: W) -> Self {
+        Timed {
+            inner,
+            blocked: Duration::from_secs(0),
+        }
+    }
+}
+
+impl<W: Write> Write for Timed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.write(buf);
+        self.blocked += start.elapsed();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+        let result = self.inner.flush();
+        self.blocked += start.elapsed();
+        result
+    }
+}
+
+/// Whether an error is due to a broken pipe, meaning whatever is reading our
+/// output has stopped doing so, such as when piping into `head`. That's a
+/// normal way for output to end.
+///
+/// Errors from serializing JSON to output must be converted to `io::Error`s
+/// first, since `serde_json::Error` doesn't say what kind of IO error it
+/// wraps.
+pub fn is_broken_pipe(err: &Error) -> bool {
+    match err.downcast_ref::<io::Error>() {
+        Some(err) => err.kind() == io::ErrorKind::BrokenPipe,
+        None => false,
+    }
+}
@@ -0,0 +1,151 @@
+//! Describing how one document changed into another, for consumers of
+//! incremental updates.
+
+use crate::pointer::Pointer;
+use serde_json::{json, Map, Value};
+
+/// A way of describing how a document changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// RFC 6902 JSON Patch.
+    JsonPatch,
+
+    /// RFC 7386 JSON Merge Patch.
+    MergePatch,
+}
+
+impl Format {
+    /// The patch which changes `old` into `new`, or `None` if patches of this
+    /// format can't express the change.
+    pub fn diff(self, old: &Value, new: &Value) -> Option<Value> {
+        match self {
+            Format::JsonPatch => Some(json_patch(old, new)),
+            Format::MergePatch => {
+                let patch = merge_patch(old, new);
+                if apply_merge_patch(old, &patch) == *new {
+                    Some(patch)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// The RFC 6902 JSON Patch which changes `old` into `new`.
+///
+/// Values which differ in type are replaced outright. Otherwise, object
+/// members and array elements are added, removed, or changed one at a time.
+pub fn json_patch(old: &Value, new: &Value) -> Value {
+    let mut ops = Vec::new();
+    diff(&mut Vec::new(), old, new, &mut ops);
+    Value::Array(ops)
+}
+
+fn diff(path: &mut Vec<String>, old: &Value, new: &Value, ops: &mut Vec<Value>) {
+    match (old, new) {
+        _ if old == new => {}
+        (Value::Object(old), Value::Object(new)) => {
+            for (k, old_val) in old {
+                path.push(k.clone());
+                match new.get(k) {
+                    Some(new_val) => diff(path, old_val, new_val, ops),
+                    None => ops.push(json!({ "op": "remove", "path": pointer(path) })),
+                }
+                path.pop();
+            }
+
+            for (k, new_val) in new {
+                if !old.contains_key(k) {
+                    path.push(k.clone());
+                    ops.push(json!({ "op": "add", "path": pointer(path), "value": new_val }));
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (old_val, new_val)) in old.iter().zip(new).enumerate() {
+                path.push(i.to_string());
+                diff(path, old_val, new_val, ops);
+                path.pop();
+            }
+
+            // Elements are removed from the end, so that removing one doesn't
+            // move those still to be removed.
+            for i in (new.len()..old.len()).rev() {
+                path.push(i.to_string());
+                ops.push(json!({ "op": "remove", "path": pointer(path) }));
+                path.pop();
+            }
+
+            for (i, new_val) in new.iter().enumerate().skip(old.len()) {
+                path.push(i.to_string());
+                ops.push(json!({ "op": "add", "path": pointer(path), "value": new_val }));
+                path.pop();
+            }
+        }
+        _ => ops.push(json!({ "op": "replace", "path": pointer(path), "value": new })),
+    }
+}
+
+/// The RFC 7386 JSON Merge Patch which changes `old` into `new`, as far as one
+/// can.
+///
+/// Merge patches remove object members by setting them to `null`, so they
+/// can't set one to `null`, nor add an object with a `null` member anywhere
+/// within it. Arrays are always replaced whole.
+pub fn merge_patch(old: &Value, new: &Value) -> Value {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for k in old.keys() {
+                if !new.contains_key(k) {
+                    patch.insert(k.clone(), Value::Null);
+                }
+            }
+
+            for (k, new_val) in new {
+                match old.get(k) {
+                    Some(old_val) if old_val == new_val => {}
+                    Some(old_val) => {
+                        patch.insert(k.clone(), merge_patch(old_val, new_val));
+                    }
+                    None => {
+                        patch.insert(k.clone(), new_val.clone());
+                    }
+                }
+            }
+
+            Value::Object(patch)
+        }
+        _ => new.clone(),
+    }
+}
+
+/// Applies a merge patch, as RFC 7386 describes.
+fn apply_merge_patch(target: &Value, patch: &Value) -> Value {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => return patch.clone(),
+    };
+
+    let mut out = match target {
+        Value::Object(target) => target.clone(),
+        _ => Map::new(),
+    };
+
+    for (k, val) in patch {
+        if val.is_null() {
+            out.remove(k);
+        } else {
+            let merged = apply_merge_patch(out.get(k).unwrap_or(&Value::Null), val);
+            out.insert(k.clone(), merged);
+        }
+    }
+
+    Value::Object(out)
+}
+
+fn pointer(path: &[String]) -> String {
+    Pointer(path.to_vec()).to_string()
+}
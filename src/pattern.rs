@@ -0,0 +1,313 @@
+//! Generating strings which match a regular expression, for strings hinted at
+//! with `{ "fuzzHint": { "pattern": "^[A-Z]{2}-[0-9]{4}$" } }`.
+//!
+//! This supports the parts of regular expressions which identifiers and codes
+//! are usually described with: literals, `.`, character classes and the `\d`,
+//! `\w` and `\s` shorthands, groups, alternation, and quantifiers. Anchors are
+//! accepted, but have no effect, since a generated string is always matched
+//! whole. Backreferences, lookaround, and word boundaries are rejected.
+//!
+//! Where a pattern leaves characters open, as `.` and negated classes do, they
+//! are chosen from printable ASCII.
+
+use rand::Rng;
+
+/// How many more repetitions than the minimum an unbounded quantifier, like
+/// `*` or `{2,}`, generates at most.
+const MAX_EXTRA_REPEATS: u32 = 8;
+
+/// Printable ASCII, as a range of characters.
+const PRINTABLE: (char, char) = (' ', '~');
+
+#[derive(Debug)]
+pub struct Pattern(Node);
+
+#[derive(Debug)]
+enum Node {
+    Literal(char),
+
+    /// Any character within one of these inclusive ranges.
+    Class(Vec<(char, char)>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat(Box<Node>, u32, u32),
+}
+
+impl Pattern {
+    /// Parses a regular expression, or describes why it can't be generated for.
+    pub fn parse(source: &str) -> Result<Pattern, String> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+
+        let node = parser.alternate()?;
+        match parser.peek() {
+            None => Ok(Pattern(node)),
+            Some(c) => Err(format!("unexpected {:?} at {}", c, parser.pos)),
+        }
+    }
+
+    pub fn fuzz<R: Rng + ?Sized>(&self, rng: &mut R) -> String {
+        let mut out = String::new();
+        self.0.fuzz(rng, &mut out);
+        out
+    }
+}
+
+impl Node {
+    fn fuzz<R: Rng + ?Sized>(&self, rng: &mut R, out: &mut String) {
+        match self {
+            Node::Literal(c) => out.push(*c),
+            Node::Class(ranges) => {
+                let size = |&(lo, hi): &(char, char)| u32::from(hi) - u32::from(lo) + 1;
+                let mut i = rng.gen_range(0, ranges.iter().map(size).sum::<u32>());
+                for range in ranges {
+                    if i < size(range) {
+                        // Ranges are built from characters, but may span
+                        // surrogates, which aren't.
+                        out.push(std::char::from_u32(u32::from(range.0) + i).unwrap_or(range.0));
+                        return;
+                    }
+                    i -= size(range);
+                }
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    node.fuzz(rng, out);
+                }
+            }
+            Node::Alternate(nodes) => nodes[rng.gen_range(0, nodes.len())].fuzz(rng, out),
+            Node::Repeat(node, min, max) => {
+                for _ in 0..rng.gen_range(*min, *max + 1) {
+                    node.fuzz(rng, out);
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternate(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concat()?];
+        while self.eat('|') {
+            branches.push(self.concat()?);
+        }
+
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+
+        Ok(Node::Concat(nodes))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err("only non-capturing (?:...) groups are supported".to_owned());
+                }
+
+                let node = self.alternate()?;
+                if !self.eat(')') {
+                    return Err("unclosed group".to_owned());
+                }
+
+                Ok(node)
+            }
+            '[' => self.class(),
+            '.' => Ok(Node::Class(vec![PRINTABLE])),
+            '^' | '$' => Ok(Node::Concat(Vec::new())),
+            '\\' => match self.next()? {
+                c @ 'd' | c @ 'w' | c @ 's' | c @ 'D' | c @ 'W' | c @ 'S' => {
+                    Ok(Node::Class(shorthand(c)))
+                }
+                c => escaped(c).map(Node::Literal),
+            },
+            c @ '*' | c @ '+' | c @ '?' | c @ '{' => {
+                Err(format!("nothing for {:?} to repeat at {}", c, self.pos - 1))
+            }
+            c => Ok(Node::Literal(c)),
+        }
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, MAX_EXTRA_REPEATS),
+            Some('+') => (1, 1 + MAX_EXTRA_REPEATS),
+            Some('?') => (0, 1),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.number()?;
+                let max = if self.eat(',') {
+                    if self.peek() == Some('}') {
+                        min.saturating_add(MAX_EXTRA_REPEATS)
+                    } else {
+                        self.number()?
+                    }
+                } else {
+                    min
+                };
+
+                if self.peek() != Some('}') {
+                    return Err("unclosed {} quantifier".to_owned());
+                }
+                if max < min {
+                    return Err(format!("quantifier {{{},{}}} has max below min", min, max));
+                }
+
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+
+        self.pos += 1;
+
+        // Laziness only changes what a match captures, not what matches.
+        self.eat('?');
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map_err(|_| format!("expected a number at {}", start))
+    }
+
+    /// Parses a character class, after its opening `[`.
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+
+        // A `]` right at the start is a character in the class.
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Ok(']') if !first => break,
+                Ok(c) => c,
+                Err(_) => return Err("unclosed character class".to_owned()),
+            };
+            first = false;
+
+            let lo = match c {
+                '\\' => match self.next()? {
+                    c @ 'd' | c @ 'w' | c @ 's' | c @ 'D' | c @ 'W' | c @ 'S' => {
+                        ranges.extend(shorthand(c));
+                        continue;
+                    }
+                    c => escaped(c)?,
+                },
+                c => c,
+            };
+
+            // A `-` right before the closing `]` is a character in the class.
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                self.pos += 1;
+                let hi = match self.next()? {
+                    '\\' => escaped(self.next()?)?,
+                    c => c,
+                };
+
+                if hi < lo {
+                    return Err(format!("range {}-{} is out of order", lo, hi));
+                }
+                ranges.push((lo, hi));
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+
+        if negated {
+            ranges = complement(&ranges);
+        }
+
+        if ranges.is_empty() {
+            return Err("character class matches no printable ASCII".to_owned());
+        }
+
+        Ok(Node::Class(ranges))
+    }
+}
+
+/// The character an escape other than a shorthand stands for.
+fn escaped(c: char) -> Result<char, String> {
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        '1'..='9' => Err("backreferences are not supported".to_owned()),
+        'b' | 'B' => Err("word boundaries are not supported".to_owned()),
+        c if c.is_ascii_alphanumeric() => Err(format!("unknown escape \\{}", c)),
+        c => Ok(c),
+    }
+}
+
+/// The ranges of the `\d`, `\w`, or `\s` shorthands, or of their negations,
+/// `\D`, `\W`, and `\S`.
+fn shorthand(c: char) -> Vec<(char, char)> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        _ => vec![('\t', '\t'), ('\n', '\n'), ('\r', '\r'), (' ', ' ')],
+    };
+
+    if c.is_ascii_uppercase() {
+        complement(&ranges)
+    } else {
+        ranges
+    }
+}
+
+/// The printable ASCII characters not within any of the ranges.
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    (PRINTABLE.0 as u8..=PRINTABLE.1 as u8)
+        .map(char::from)
+        .filter(|c| !ranges.iter().any(|&(lo, hi)| lo <= *c && *c <= hi))
+        .map(|c| (c, c))
+        .collect()
+}
@@ -0,0 +1,317 @@
+//! JSON Pointers into generated documents.
+
+use crate::config::FuzzConfig;
+use failure::{bail, Error};
+use jddf::schema::Form;
+use jddf::Schema;
+use rand::Rng;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+/// What redacted values are replaced with.
+const REDACTED: &str = "[REDACTED]";
+
+/// How likely [`sample`] is to stop at each value it reaches which has values
+/// within it, rather than go on into one of them.
+const SAMPLE_STOP_PROB: f64 = 0.25;
+
+/// Array indices [`sample`] chooses from, as many as the largest array the
+/// default options generate.
+const SAMPLE_INDICES: usize = 8;
+
+/// A JSON Pointer into a document, as a list of unescaped tokens.
+///
+/// A `*` token matches any array index or object property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer(pub Vec<String>);
+
+impl Pointer {
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        if s.is_empty() {
+            return Ok(Pointer(Vec::new()));
+        }
+
+        if !s.starts_with('/') {
+            bail!("JSON Pointer must be empty or start with a slash: {}", s);
+        }
+
+        Ok(Pointer(
+            s[1..]
+                .split('/')
+                .map(|token| token.replace("~1", "/").replace("~0", "~"))
+                .collect(),
+        ))
+    }
+
+    /// Whether a path, as unescaped tokens, is at or within the part of a
+    /// document this pointer points to.
+    pub fn contains(&self, path: &[String]) -> bool {
+        path.len() >= self.0.len()
+            && self
+                .0
+                .iter()
+                .zip(path)
+                .all(|(token, key)| matches(token, key))
+    }
+
+    /// Parses a comma-separated list of pointers.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, Error> {
+        s.split(',').map(Pointer::parse).collect()
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in &self.0 {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn matches(token: &str, key: &str) -> bool {
+    token == "*" || token == key
+}
+
+/// Returns only the parts of a document at any of the given pointers.
+///
+/// Parts of the document which do not lead to any of the pointers are removed.
+/// If none of the pointers exist, the result is an empty object or array.
+pub fn project(val: &Value, pointers: &[Pointer]) -> Value {
+    let tokens: Vec<_> = pointers.iter().map(|p| &p.0[..]).collect();
+    project_tokens(val, &tokens).unwrap_or_else(|| match val {
+        Value::Array(_) => Value::Array(Vec::new()),
+        _ => Value::Object(serde_json::Map::new()),
+    })
+}
+
+fn project_tokens(val: &Value, pointers: &[&[String]]) -> Option<Value> {
+    if pointers.iter().any(|p| p.is_empty()) {
+        return Some(val.clone());
+    }
+
+    let children = |key: &str| -> Vec<&[String]> {
+        pointers
+            .iter()
+            .filter(|p| matches(&p[0], key))
+            .map(|p| &p[1..])
+            .collect()
+    };
+
+    match val {
+        Value::Array(vals) => {
+            let out: Vec<_> = vals
+                .iter()
+                .enumerate()
+                .filter_map(|(i, v)| project_tokens(v, &children(&i.to_string())))
+                .collect();
+
+            if out.is_empty() {
+                None
+            } else {
+                Some(Value::Array(out))
+            }
+        }
+        Value::Object(vals) => {
+            let out: serde_json::Map<_, _> = vals
+                .iter()
+                .filter_map(|(k, v)| project_tokens(v, &children(k)).map(|v| (k.clone(), v)))
+                .collect();
+
+            if out.is_empty() {
+                None
+            } else {
+                Some(Value::Object(out))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Replaces the parts of a document at any of the given pointers with a
+/// placeholder string.
+pub fn redact(val: &mut Value, pointers: &[Pointer]) {
+    for pointer in pointers {
+        redact_tokens(val, &pointer.0);
+    }
+}
+
+fn redact_tokens(val: &mut Value, tokens: &[String]) {
+    if tokens.is_empty() {
+        *val = REDACTED.into();
+        return;
+    }
+
+    match val {
+        Value::Array(vals) => {
+            for (i, v) in vals.iter_mut().enumerate() {
+                if matches(&tokens[0], &i.to_string()) {
+                    redact_tokens(v, &tokens[1..]);
+                }
+            }
+        }
+        Value::Object(vals) => {
+            for (k, v) in vals.iter_mut() {
+                if matches(&tokens[0], k) {
+                    redact_tokens(v, &tokens[1..]);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Puts a value at a pointer into a document, wherever the pointer matches.
+///
+/// Object members missing along the way are added, as objects, and so is the
+/// member at the end, so that an absent optional property is set too. Array
+/// elements are never added, and a `*` only matches what is already there.
+pub fn set(val: &mut Value, pointer: &Pointer, to: &Value) {
+    set_tokens(val, &pointer.0, to);
+}
+
+fn set_tokens(val: &mut Value, tokens: &[String], to: &Value) {
+    if tokens.is_empty() {
+        *val = to.clone();
+        return;
+    }
+
+    match val {
+        Value::Array(vals) => {
+            for (i, v) in vals.iter_mut().enumerate() {
+                if matches(&tokens[0], &i.to_string()) {
+                    set_tokens(v, &tokens[1..], to);
+                }
+            }
+        }
+        Value::Object(vals) => {
+            if tokens[0] != "*" && !vals.contains_key(&tokens[0]) {
+                vals.insert(tokens[0].clone(), Value::Object(serde_json::Map::new()));
+            }
+
+            for (k, v) in vals.iter_mut() {
+                if matches(&tokens[0], k) {
+                    set_tokens(v, &tokens[1..], to);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Finds every pointer into instances of a schema, up to `max_len` tokens
+/// long, with `*` tokens where any array index or `values` key goes, as
+/// [`Pointer::contains`] reads them.
+///
+/// Additional properties aren't included, since nothing is known of them.
+pub fn enumerate(root: &Schema, max_len: usize) -> Vec<Pointer> {
+    let mut out = Vec::new();
+    enumerate_tokens(root, Some(root), max_len, &mut Vec::new(), &mut out);
+
+    // Discriminator variants can share properties, which would be found once
+    // for each of them.
+    let mut seen = HashSet::new();
+    out.retain(|pointer: &Pointer| seen.insert(pointer.0.clone()));
+    out
+}
+
+fn enumerate_tokens(
+    root: &Schema,
+    schema: Option<&Schema>,
+    max_len: usize,
+    tokens: &mut Vec<String>,
+    out: &mut Vec<Pointer>,
+) {
+    out.push(Pointer(tokens.clone()));
+    if tokens.len() == max_len {
+        return;
+    }
+
+    for (child, sub_schema) in children(root, schema) {
+        tokens.push(match child {
+            Child::Index | Child::Key => "*".to_owned(),
+            Child::Name(name) => name,
+        });
+        enumerate_tokens(root, sub_schema, max_len, tokens, out);
+        tokens.pop();
+    }
+}
+
+/// Chooses a pointer into instances of a schema at random, at most `max_len`
+/// tokens long, with array indices below eight and random `values` keys.
+///
+/// Each pointer leads to a value in some instance of the schema, though not
+/// every instance has a value at every pointer.
+pub fn sample<R: Rng + ?Sized>(root: &Schema, max_len: usize, rng: &mut R) -> Pointer {
+    let mut tokens = Vec::new();
+    let mut schema = Some(root);
+    while tokens.len() < max_len {
+        let mut children = children(root, schema);
+        if children.is_empty() || rng.gen_bool(SAMPLE_STOP_PROB) {
+            break;
+        }
+
+        let (child, sub_schema) = children.swap_remove(rng.gen_range(0, children.len()));
+        tokens.push(match child {
+            Child::Index => rng.gen_range(0, SAMPLE_INDICES).to_string(),
+            Child::Key => crate::fuzz_str(&FuzzConfig::default(), rng),
+            Child::Name(name) => name,
+        });
+        schema = sub_schema;
+    }
+
+    Pointer(tokens)
+}
+
+/// How a value within an instance of a schema is reached.
+enum Child {
+    /// By any array index.
+    Index,
+
+    /// By any object key, as the `values` form allows.
+    Key,
+
+    /// By the name of a property.
+    Name(String),
+}
+
+/// The values within instances of a schema, and their schemas, resolving
+/// refs. A discriminator's tag is a child, as are the properties of each of
+/// its variants. The tag is the one child without a schema, and nothing is
+/// within it.
+fn children<'a>(root: &'a Schema, schema: Option<&'a Schema>) -> Vec<(Child, Option<&'a Schema>)> {
+    let mut schema = match schema {
+        Some(schema) => schema,
+        None => return Vec::new(),
+    };
+
+    while let Form::Ref(def) = schema.form() {
+        schema = &root.definitions().as_ref().unwrap()[def];
+    }
+
+    match schema.form() {
+        Form::Elements(sub_schema) => vec![(Child::Index, Some(sub_schema))],
+        Form::Values(sub_schema) => vec![(Child::Key, Some(sub_schema))],
+        Form::Properties {
+            required, optional, ..
+        } => {
+            let mut names: Vec<_> = required.iter().chain(optional).collect();
+            names.sort_by_key(|(a, _)| *a);
+            names
+                .into_iter()
+                .map(|(name, sub_schema)| (Child::Name(name.clone()), Some(sub_schema)))
+                .collect()
+        }
+        Form::Discriminator(tag, mapping) => {
+            let mut out = vec![(Child::Name(tag.clone()), None)];
+            for tag_val in crate::sorted_keys(mapping) {
+                out.extend(children(root, Some(&mapping[tag_val])));
+            }
+
+            out
+        }
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,69 @@
+//! Values for a field drawn from a list in a file, for categorical fields such
+//! as country codes, where random strings are unrealistic.
+
+use failure::{bail, format_err, Error};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::fs;
+use std::path::Path;
+
+/// A list of values, and how much weight each is given.
+#[derive(Debug)]
+pub struct Pool {
+    values: Vec<(String, f64)>,
+}
+
+impl Pool {
+    /// Reads a pool from a file. See [`Pool::parse`].
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| format_err!("{}: {}", path.display(), err))
+    }
+
+    /// Parses a pool with one value per line.
+    ///
+    /// A value may be followed by a tab and a weight, like `US\t3`, to choose
+    /// it that many times as often as a value with a weight of one. Values
+    /// without a weight have a weight of one. Blank lines are skipped.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let mut values = Vec::new();
+        for line in s.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.rsplitn(2, '\t');
+            let value = match (parts.next(), parts.next()) {
+                (Some(weight), Some(value)) => {
+                    let weight: f64 = weight
+                        .parse()
+                        .map_err(|_| format_err!("invalid weight: {}", weight))?;
+                    if !weight.is_finite() || weight < 0.0 {
+                        bail!("invalid weight: {}", weight);
+                    }
+
+                    (value.to_owned(), weight)
+                }
+                _ => (line.to_owned(), 1.0),
+            };
+
+            values.push(value);
+        }
+
+        if values.iter().all(|(_, weight)| *weight == 0.0) {
+            bail!("no values to choose from");
+        }
+
+        Ok(Pool { values })
+    }
+
+    /// Chooses a value, in proportion to the weights of the values.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> &str {
+        &self
+            .values
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .unwrap()
+            .0
+    }
+}
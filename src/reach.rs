@@ -0,0 +1,251 @@
+//! Finding options in a configuration which can never take effect, because
+//! the configuration keeps the parts of the schema they apply to from ever
+//! being generated, or because those parts don't exist.
+
+use crate::config::{FuzzConfig, Strategy};
+use crate::pointer::Pointer;
+use crate::weights;
+use jddf::schema::Form;
+use jddf::Schema;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Checks a configuration against a schema, and describes anything in it
+/// which can never take effect.
+pub fn warnings(schema: &Schema, config: &FuzzConfig) -> Vec<String> {
+    let (walk, mut out) = walk(schema, config);
+
+    let options: Vec<(&str, Vec<&String>)> = vec![
+        ("--int-as-string", config.int_as_string.keys().collect()),
+        ("--homoglyphs", config.homoglyphs.iter().collect()),
+        ("--jwt", config.jwt.keys().collect()),
+        ("--pool", config.pool.keys().collect()),
+        ("--empty-prob", config.empty_prob.keys().collect()),
+        ("--optional", config.optional.keys().collect()),
+        ("--freeze", config.freeze.keys().collect()),
+    ];
+
+    for (option, mut paths) in options {
+        paths.sort();
+        for path in paths {
+            match walk.paths.get(path) {
+                None => out.push(format!(
+                    "{} has no effect at {}, which is not a path in the schema",
+                    option, path
+                )),
+                Some(Some(reason)) => out.push(format!(
+                    "{} has no effect at {}, which is never generated, since {}",
+                    option, path, reason
+                )),
+                Some(None) if option != "--freeze" && config.freeze.contains_key(path) => out.push(
+                    format!("{} has no effect at {}, which is frozen", option, path),
+                ),
+                Some(None) => {}
+            }
+        }
+    }
+
+    let mut defs: Vec<_> = config.ref_depth.keys().collect();
+    defs.sort();
+    for def in defs {
+        let exists = schema
+            .definitions()
+            .as_ref()
+            .is_some_and(|defs| defs.contains_key(def));
+        if !exists {
+            out.push(format!(
+                "--ref-depth has no effect for {}, which is not a definition in the schema",
+                def
+            ));
+        }
+    }
+
+    out
+}
+
+/// Every schema path, and why the configuration keeps it from ever being
+/// generated, if it does. Paths within definitions which are never referred
+/// to are left out.
+pub fn paths(schema: &Schema, config: &FuzzConfig) -> HashMap<String, Option<String>> {
+    walk(schema, config).0.paths
+}
+
+/// Walks every part of the schema which is referred to, returning the walk,
+/// and descriptions of the definitions which are never generated.
+fn walk<'a>(schema: &'a Schema, config: &'a FuzzConfig) -> (Walk<'a>, Vec<String>) {
+    let mut walk = Walk {
+        root: schema,
+        config,
+        paths: HashMap::new(),
+        walked: BTreeSet::new(),
+        blocked_refs: BTreeMap::new(),
+    };
+
+    walk.walk(schema, &mut Vec::new(), None);
+
+    // Definitions which are only referred to from parts of the schema which
+    // are never generated are never generated either.
+    let mut out = Vec::new();
+    while !walk.blocked_refs.is_empty() {
+        for (def, mut from) in std::mem::take(&mut walk.blocked_refs) {
+            if !walk.walked.insert(def.clone()) {
+                continue;
+            }
+
+            from.sort();
+            out.push(format!(
+                "definition {} is never generated, since it is only referred to from {}",
+                def,
+                from.join(", ")
+            ));
+
+            let reason = format!("definition {} is never generated", def);
+            let mut path = vec!["definitions".to_owned(), def.clone()];
+            walk.walk(
+                &schema.definitions().as_ref().unwrap()[&def],
+                &mut path,
+                Some(reason),
+            );
+        }
+    }
+
+    (walk, out)
+}
+
+struct Walk<'a> {
+    root: &'a Schema,
+    config: &'a FuzzConfig,
+
+    /// Every schema path, and why it is never generated, if it isn't.
+    paths: HashMap<String, Option<String>>,
+
+    /// The definitions which have been walked.
+    walked: BTreeSet<String>,
+
+    /// Definitions referred to from parts of the schema which are never
+    /// generated, and the schema paths they are referred to from.
+    blocked_refs: BTreeMap<String, Vec<String>>,
+}
+
+impl<'a> Walk<'a> {
+    /// Walks a schema at a schema path, given why it is never generated, if
+    /// it isn't.
+    fn walk(&mut self, schema: &'a Schema, path: &mut Vec<String>, blocked: Option<String>) {
+        let pointer = Pointer(path.clone()).to_string();
+
+        // A schema path is generated if it is reachable in any way at all.
+        if self.paths.get(&pointer) != Some(&None) {
+            self.paths.insert(pointer.clone(), blocked.clone());
+        }
+
+        let mut blocked = blocked;
+        if blocked.is_none() && self.config.freeze.contains_key(&pointer) {
+            blocked = Some(format!("{} is frozen", pointer));
+        }
+
+        match schema.form() {
+            Form::Ref(def) => match blocked {
+                None => {
+                    if self.walked.insert(def.clone()) {
+                        let definition = &self.root.definitions().as_ref().unwrap()[def];
+                        let mut path = vec!["definitions".to_owned(), def.clone()];
+                        self.walk(definition, &mut path, None);
+                    }
+                }
+                Some(_) => self
+                    .blocked_refs
+                    .entry(def.clone())
+                    .or_default()
+                    .push(pointer),
+            },
+            Form::Elements(sub_schema) | Form::Values(sub_schema) => {
+                let token = match schema.form() {
+                    Form::Elements(_) => "elements",
+                    _ => "values",
+                };
+
+                let empty_prob = self.config.empty_prob.get(&pointer).copied();
+                let always_empty = match self.config.strategy {
+                    Strategy::Minimal => match empty_prob {
+                        Some(prob) => prob > 0.0,
+                        None => !self.config.never_empty && self.config.container_size.min == 0,
+                    },
+                    _ => empty_prob == Some(1.0),
+                };
+                if blocked.is_none() && always_empty {
+                    blocked = Some(format!("{} is always empty", pointer));
+                }
+
+                self.walk_at(sub_schema, path, &[token], blocked);
+            }
+            Form::Properties {
+                required, optional, ..
+            } => {
+                for (k, sub_schema) in required {
+                    self.walk_at(sub_schema, path, &["properties", k], blocked.clone());
+                }
+
+                for (k, sub_schema) in optional {
+                    path.extend(vec!["optionalProperties".to_owned(), k.clone()]);
+                    let sub_pointer = Pointer(path.clone()).to_string();
+                    path.truncate(path.len() - 2);
+
+                    let never_present = match self.config.optional.get(&sub_pointer) {
+                        Some(weights) => weights.present == 0.0,
+                        None => {
+                            let prob = match self.config.optional_prob {
+                                Some(prob) => Some(prob),
+                                None => weights::of(sub_schema).map(|w| w / (1.0 + w)),
+                            };
+
+                            match self.config.strategy {
+                                Strategy::Minimal => prob != Some(1.0),
+                                _ => prob == Some(0.0),
+                            }
+                        }
+                    };
+                    let blocked = if blocked.is_none() && never_present {
+                        Some(format!("{} is never present", sub_pointer))
+                    } else {
+                        blocked.clone()
+                    };
+
+                    self.walk_at(sub_schema, path, &["optionalProperties", k], blocked);
+                }
+            }
+            Form::Discriminator(_, mapping) => {
+                for (k, sub_schema) in mapping {
+                    let tokens = ["discriminator", "mapping", k.as_str()];
+                    let round_robin = self.config.enum_round_robin || self.config.coverage;
+                    let blocked = if blocked.is_none()
+                        && !round_robin
+                        && weights::of(sub_schema) == Some(0.0)
+                    {
+                        path.extend(tokens.iter().map(|token| (*token).to_owned()));
+                        let sub_pointer = Pointer(path.clone()).to_string();
+                        path.truncate(path.len() - tokens.len());
+                        Some(format!("{} weighs zero", sub_pointer))
+                    } else {
+                        blocked.clone()
+                    };
+
+                    self.walk_at(sub_schema, path, &tokens, blocked);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Walks a sub-schema, whose schema path is the current one followed by
+    /// some tokens.
+    fn walk_at(
+        &mut self,
+        schema: &'a Schema,
+        path: &mut Vec<String>,
+        tokens: &[&str],
+        blocked: Option<String>,
+    ) {
+        path.extend(tokens.iter().map(|token| (*token).to_owned()));
+        self.walk(schema, path, blocked);
+        path.truncate(path.len() - tokens.len());
+    }
+}
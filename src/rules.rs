@@ -0,0 +1,54 @@
+//! Making documents follow business rules which a schema can't express.
+
+use crate::config::{Comparison, Condition, Rule};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Makes a document follow some rules, if it can, and returns whether it does.
+///
+/// Where a rule is broken, and the condition it requires is that a value
+/// equals another, that value is replaced. Other broken rules can't be fixed
+/// this way, and the document should be generated anew.
+pub fn enforce(rules: &[Rule], doc: &mut Value) -> bool {
+    for rule in rules {
+        if holds(&rule.when, doc) && !holds(&rule.then, doc) && rule.then.op == Comparison::Eq {
+            if let Some(val) = doc.pointer_mut(&rule.then.path) {
+                *val = rule.then.value.clone();
+            }
+        }
+    }
+
+    // Fixing one rule may have broken another.
+    rules
+        .iter()
+        .all(|rule| !holds(&rule.when, doc) || holds(&rule.then, doc))
+}
+
+/// Whether a condition holds of a document.
+fn holds(condition: &Condition, doc: &Value) -> bool {
+    let val = match doc.pointer(&condition.path) {
+        Some(val) => val,
+        None => return false,
+    };
+
+    match (condition.op, compare(val, &condition.value)) {
+        (Comparison::Eq, ordering) => ordering == Some(Ordering::Equal),
+        (Comparison::Ne, ordering) => ordering != Some(Ordering::Equal),
+        (Comparison::Lt, Some(ordering)) => ordering == Ordering::Less,
+        (Comparison::Le, Some(ordering)) => ordering != Ordering::Greater,
+        (Comparison::Gt, Some(ordering)) => ordering == Ordering::Greater,
+        (Comparison::Ge, Some(ordering)) => ordering != Ordering::Less,
+        (_, None) => false,
+    }
+}
+
+/// Compares two values, if they are comparable. Values other than numbers and
+/// strings are only ever equal, or not comparable.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
@@ -0,0 +1,94 @@
+//! Fingerprinting the structure of documents, ignoring their leaf values.
+
+use jddf::schema::Form;
+use jddf::Schema;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Hashes the shape of a document which satisfies a schema.
+///
+/// Two documents have the same shape if they have the same properties, use
+/// the same discriminator variants, and have elements and values of the same
+/// shapes. Leaf values, additional properties, the keys of the values form,
+/// and how many elements or values there are of each shape are all ignored.
+pub fn fingerprint(schema: &Schema, instance: &Value) -> u64 {
+    let mut out = String::new();
+    write_shape(&mut out, schema, schema, instance);
+    crate::fnv1a(&out)
+}
+
+fn write_shape(out: &mut String, root: &Schema, schema: &Schema, instance: &Value) {
+    match (schema.form(), instance) {
+        (Form::Ref(def), _) => {
+            let definition = &root.definitions().as_ref().unwrap()[def];
+            write_shape(out, root, definition, instance);
+        }
+        (Form::Empty, _) => out.push(match instance {
+            Value::Null => 'n',
+            Value::Bool(_) => 'b',
+            Value::Number(_) => '0',
+            Value::String(_) => 's',
+            Value::Array(_) => 'a',
+            Value::Object(_) => 'o',
+        }),
+        (Form::Elements(sub_schema), Value::Array(vals)) => {
+            out.push('[');
+            write_shapes(out, root, sub_schema, vals);
+            out.push(']');
+        }
+        (Form::Values(sub_schema), Value::Object(vals)) => {
+            out.push('{');
+            write_shapes(out, root, sub_schema, vals.values());
+            out.push('}');
+        }
+        (
+            Form::Properties {
+                required, optional, ..
+            },
+            Value::Object(vals),
+        ) => {
+            // Members of a serde_json::Map iterate in sorted order. Additional
+            // properties, and discriminator tags, are ignored.
+            out.push('(');
+            for (k, val) in vals {
+                if let Some(sub_schema) = required.get(k).or_else(|| optional.get(k)) {
+                    out.push_str(&serde_json::to_string(k).unwrap());
+                    out.push(':');
+                    write_shape(out, root, sub_schema, val);
+                    out.push(',');
+                }
+            }
+            out.push(')');
+        }
+        (Form::Discriminator(tag, mapping), Value::Object(vals)) => {
+            let tag_val = vals.get(tag).and_then(Value::as_str).unwrap_or_default();
+            out.push_str(&serde_json::to_string(tag_val).unwrap());
+            if let Some(sub_schema) = mapping.get(tag_val) {
+                write_shape(out, root, sub_schema, instance);
+            }
+        }
+        _ => out.push('_'),
+    }
+}
+
+/// Writes the distinct shapes of some values, in sorted order.
+fn write_shapes<'v, I: IntoIterator<Item = &'v Value>>(
+    out: &mut String,
+    root: &Schema,
+    schema: &Schema,
+    vals: I,
+) {
+    let shapes: BTreeSet<_> = vals
+        .into_iter()
+        .map(|val| {
+            let mut shape = String::new();
+            write_shape(&mut shape, root, schema, val);
+            shape
+        })
+        .collect();
+
+    for shape in shapes {
+        out.push_str(&shape);
+        out.push(',');
+    }
+}
@@ -0,0 +1,54 @@
+//! Steering how big documents are, so that their mean size approaches a
+//! target.
+
+/// How much weight the moving average of document sizes gives each new one.
+const SMOOTHING: f64 = 0.1;
+
+/// How strongly the scale responds to the moving average being off target.
+/// Smaller is slower, but steadier.
+const GAIN: f64 = 0.05;
+
+/// The bounds of the scale, so that it can't run away when the target can't
+/// be reached, such as when it's smaller than the smallest valid document.
+const MIN_SCALE: f64 = 0.25;
+const MAX_SCALE: f64 = 16.0;
+
+/// Adjusts a scale for how many elements and values arrays and maps have,
+/// based on the sizes of the documents generated so far.
+#[derive(Debug)]
+pub struct SizeController {
+    target: f64,
+
+    /// A moving average of the sizes of recent documents, in bytes.
+    average: Option<f64>,
+
+    /// How much larger than usual arrays and maps should be.
+    pub scale: f64,
+}
+
+impl SizeController {
+    /// Starts steering towards a mean size in bytes.
+    pub fn new(target: u64) -> Self {
+        SizeController {
+            target: target as f64,
+            average: None,
+            scale: 1.0,
+        }
+    }
+
+    /// Records the size of a document, in bytes, and adjusts the scale for the
+    /// documents after it.
+    pub fn record(&mut self, bytes: usize) {
+        let bytes = bytes as f64;
+        let average = match self.average {
+            Some(average) => average + SMOOTHING * (bytes - average),
+            None => bytes,
+        };
+
+        self.average = Some(average);
+        if average > 0.0 {
+            self.scale =
+                (self.scale * (self.target / average).powf(GAIN)).clamp(MIN_SCALE, MAX_SCALE);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Statistics about a run, written out periodically for long-running ones.
+
+use crate::mutations;
+use crate::pointer::Pointer;
+use jddf::schema::Form;
+use jddf::Schema;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// A choice a document took at a schema path: which enum value or
+/// discriminator variant, whether an optional property is `present` or
+/// `absent`, or whether an array or map is `empty` or `nonEmpty`.
+#[derive(Debug)]
+pub struct Branch {
+    /// What kind of choice it is: `enum`, `discriminator`, `optional`, or
+    /// `container`.
+    pub kind: &'static str,
+    pub schema_path: String,
+    pub value: String,
+
+    /// How many values or variants there are to take there.
+    pub choices: usize,
+}
+
+/// Finds the choices a document takes.
+pub fn branches(root: &Schema, doc: &Value) -> Vec<Branch> {
+    let mut out = Vec::new();
+    for site in mutations::sites(root, doc) {
+        let val = match doc.pointer(&Pointer(site.instance_path).to_string()) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let mut branch = |kind, schema_path: Vec<String>, value: &str, choices| {
+            out.push(Branch {
+                kind,
+                schema_path: Pointer(schema_path).to_string(),
+                value: value.to_owned(),
+                choices,
+            })
+        };
+
+        match (site.schema.form(), val) {
+            (Form::Enum(vals), Value::String(val)) => {
+                branch("enum", site.schema_path, val, vals.len())
+            }
+            (Form::Discriminator(tag, mapping), _) => {
+                if let Some(tag_val) = val.get(tag).and_then(Value::as_str) {
+                    branch("discriminator", site.schema_path, tag_val, mapping.len());
+                }
+            }
+            (Form::Properties { optional, .. }, Value::Object(vals)) => {
+                for k in crate::sorted_keys(optional) {
+                    let mut schema_path = site.schema_path.clone();
+                    schema_path.extend(vec!["optionalProperties".to_owned(), k.clone()]);
+                    let presence = if vals.contains_key(k) {
+                        "present"
+                    } else {
+                        "absent"
+                    };
+
+                    branch("optional", schema_path, presence, 2);
+                }
+            }
+            (Form::Elements(_), Value::Array(vals)) => {
+                let emptiness = if vals.is_empty() { "empty" } else { "nonEmpty" };
+                branch("container", site.schema_path, emptiness, 2);
+            }
+            (Form::Values(_), Value::Object(vals)) => {
+                let emptiness = if vals.is_empty() { "empty" } else { "nonEmpty" };
+                branch("container", site.schema_path, emptiness, 2);
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/// How many choices there are at a schema path, and how many times each one
+/// has been taken.
+type Tally = (usize, BTreeMap<String, u64>);
+
+/// How many times documents have taken each choice a schema offers, for
+/// reporting on once a run is done.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// For each kind of choice and schema path, how many choices there are
+    /// there, and how many times each one has been taken.
+    choices: BTreeMap<(&'static str, String), Tally>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Report::default()
+    }
+
+    /// Records the choices a document took.
+    pub fn record(&mut self, branches: &[Branch]) {
+        for branch in branches {
+            *self
+                .choices
+                .entry((branch.kind, branch.schema_path.clone()))
+                .or_insert_with(|| (branch.choices, BTreeMap::new()))
+                .1
+                .entry(branch.value.clone())
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// The report as JSON: for each schema path, how many times each choice
+    /// there was taken, and whether every choice was.
+    pub fn to_json(&self) -> Value {
+        let choices: Vec<_> = self
+            .choices
+            .iter()
+            .map(|((kind, schema_path), (choices, taken))| {
+                json!({
+                    "kind": kind,
+                    "schemaPath": schema_path,
+                    "choices": choices,
+                    "taken": taken,
+                    "covered": taken.len() >= *choices,
+                })
+            })
+            .collect();
+
+        json!({
+            "covered": choices.iter().filter(|choice| choice["covered"] == true).count(),
+            "total": choices.len(),
+            "choices": choices,
+        })
+    }
+}
+
+/// Counts of what has happened so far in a run.
+#[derive(Debug)]
+pub struct Stats {
+    started: Instant,
+
+    /// When the last snapshot was taken, and how long output had been blocked
+    /// for then.
+    last_snapshot: (Instant, Duration),
+
+    /// How many documents have been written out.
+    pub documents: u64,
+
+    /// How many documents were skipped for exceeding the maximum depth.
+    pub skipped: u64,
+
+    /// How many documents were left out for having a shape already generated.
+    pub stale: u64,
+
+    /// How many documents were left out for breaking the config's rules.
+    pub rejected: u64,
+
+    /// How many files have been written to, if writing to rotating files.
+    pub files: u64,
+
+    /// For each enum and discriminator schema path, how many values or
+    /// variants it has, and which of them documents have taken so far.
+    coverage: BTreeMap<String, (usize, BTreeSet<String>)>,
+}
+
+impl Stats {
+    /// Starts counting, from now.
+    pub fn start() -> Self {
+        Stats {
+            started: Instant::now(),
+            last_snapshot: (Instant::now(), Duration::from_secs(0)),
+            documents: 0,
+            skipped: 0,
+            stale: 0,
+            rejected: 0,
+            files: 0,
+            coverage: BTreeMap::new(),
+        }
+    }
+
+    /// Whether documents have taken every value or variant at each of the
+    /// given enum and discriminator schema paths.
+    pub fn covers(&self, paths: &BTreeSet<String>) -> bool {
+        paths.iter().all(|path| match self.coverage.get(path) {
+            Some((choices, seen)) => seen.len() >= *choices,
+            None => false,
+        })
+    }
+
+    /// Records the enum values and discriminator variants a document took.
+    pub fn record(&mut self, branches: Vec<Branch>) {
+        for branch in branches {
+            if branch.kind != "enum" && branch.kind != "discriminator" {
+                continue;
+            }
+
+            let choices = branch.choices;
+            self.coverage
+                .entry(branch.schema_path)
+                .or_insert_with(|| (choices, BTreeSet::new()))
+                .1
+                .insert(branch.value);
+        }
+    }
+
+    /// Takes a snapshot of the statistics as JSON, given how many bytes have
+    /// been written out, and how long has been spent waiting for them to be.
+    ///
+    /// `backpressure` is the fraction of the time since the last snapshot
+    /// which was spent waiting on output. When it stays near one, whatever is
+    /// reading the output cannot keep up.
+    pub fn snapshot(&mut self, bytes: u64, blocked: Duration) -> Value {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = |n: u64| {
+            if elapsed > 0.0 {
+                n as f64 / elapsed
+            } else {
+                0.0
+            }
+        };
+
+        let (last_time, last_blocked) = self.last_snapshot;
+        let interval = last_time.elapsed().as_secs_f64();
+        let backpressure = if interval > 0.0 {
+            ((blocked - last_blocked).as_secs_f64() / interval).min(1.0)
+        } else {
+            0.0
+        };
+        self.last_snapshot = (Instant::now(), blocked);
+
+        json!({
+            "elapsedSecs": elapsed,
+            "documents": self.documents,
+            "bytes": bytes,
+            "documentsPerSec": rate(self.documents),
+            "bytesPerSec": rate(bytes),
+            "skipped": self.skipped,
+            "stale": self.stale,
+            "rejected": self.rejected,
+            "files": self.files,
+            "blockedSecs": blocked.as_secs_f64(),
+            "backpressure": backpressure,
+            "coverage": self
+                .coverage
+                .iter()
+                .map(|(path, (choices, seen))| json!({
+                    "schemaPath": path,
+                    "seen": seen.len(),
+                    "choices": choices,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A file which statistics are written to every so often, replacing what was
+/// there before.
+#[derive(Debug)]
+pub struct StatsFile {
+    path: PathBuf,
+    interval: Duration,
+    last: Instant,
+}
+
+impl StatsFile {
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        StatsFile {
+            path,
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    /// Whether it has been long enough since statistics were last written.
+    pub fn due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// Writes statistics out.
+    ///
+    /// They are written to a temporary file, which is then renamed over the
+    /// stats file, so that readers never see a partially-written file.
+    pub fn write(&mut self, stats: &Value) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, format!("{}\n", stats))?;
+        fs::rename(&tmp, &self.path)?;
+
+        self.last = Instant::now();
+        Ok(())
+    }
+}
+
+/// A stream of progress events, each a line of JSON, for other programs to
+/// follow a run by. Each event is a snapshot of the statistics, with an
+/// `event` of `progress`, or `done` for the last one.
+pub struct ProgressStream {
+    out: Box<dyn Write>,
+    interval: Duration,
+    last: Instant,
+}
+
+impl ProgressStream {
+    pub fn new(out: Box<dyn Write>, interval: Duration) -> Self {
+        ProgressStream {
+            out,
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    /// Whether it has been long enough since an event was last written.
+    pub fn due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// Writes an event, and flushes it, so that it's seen straight away.
+    pub fn write(&mut self, event: &str, stats: &Value) -> io::Result<()> {
+        let mut stats = stats.clone();
+        stats["event"] = event.into();
+        writeln!(self.out, "{}", stats)?;
+        self.out.flush()?;
+
+        self.last = Instant::now();
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Documents whose root is an array or map of any length, written out one
+//! element or value at a time, so that memory use doesn't grow with the
+//! length.
+
+use crate::mutations::Generator;
+use jddf::schema::Form;
+use jddf::Schema;
+use rand::RngCore;
+use std::io::{self, Write};
+
+/// The array or map at the root of a schema.
+#[derive(Debug)]
+pub struct Container<'a> {
+    /// Whether this is a map, of the values form, rather than an array.
+    values: bool,
+
+    /// The schema of the container's elements or values.
+    sub_schema: &'a Schema,
+
+    /// The tokens of the schema path of `sub_schema`.
+    schema_path: Vec<String>,
+}
+
+impl<'a> Container<'a> {
+    /// Finds the array or map at the root of a schema, following references.
+    /// Returns `None` if the root is not of the elements or values form.
+    pub fn root(root: &'a Schema) -> Option<Self> {
+        let mut schema = root;
+        let mut schema_path = Vec::new();
+
+        // A definition can only refer to itself through a chain of at most as
+        // many references as there are definitions.
+        let definitions = root.definitions().as_ref().map_or(0, |defs| defs.len());
+        for _ in 0..=definitions {
+            let (values, sub_schema, token) = match schema.form() {
+                Form::Ref(def) => {
+                    schema = &root.definitions().as_ref().unwrap()[def];
+                    schema_path = vec!["definitions".to_owned(), def.clone()];
+                    continue;
+                }
+                Form::Elements(sub_schema) => (false, sub_schema, "elements"),
+                Form::Values(sub_schema) => (true, sub_schema, "values"),
+                _ => return None,
+            };
+
+            schema_path.push(token.to_owned());
+            return Some(Container {
+                values,
+                sub_schema,
+                schema_path,
+            });
+        }
+
+        None
+    }
+
+    /// Writes a container of `len` elements or values, generating each just
+    /// before it is written out.
+    ///
+    /// The keys of a map are numbered, as `k0`, `k1`, and so on, so that none
+    /// of them repeat without having to remember them all.
+    pub fn write<W: Write>(
+        &self,
+        out: &mut W,
+        len: u64,
+        rng: &mut dyn RngCore,
+        gen: &mut dyn Generator,
+    ) -> io::Result<()> {
+        out.write_all(if self.values { b"{" } else { b"[" })?;
+        for i in 0..len {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+
+            let token = if self.values {
+                let k = format!("k{}", i);
+                write!(out, "\"{}\":", k)?;
+                k
+            } else {
+                i.to_string()
+            };
+
+            let val = gen.generate(rng, &self.schema_path, &[token], self.sub_schema);
+            serde_json::to_writer(&mut *out, &val)?;
+        }
+
+        out.write_all(if self.values { b"}" } else { b"]" })
+    }
+}
@@ -0,0 +1,141 @@
+//! Pairs of documents for testing services which transform documents of one
+//! schema into documents of another.
+//!
+//! How documents are transformed is given by a mapping, from JSON Pointers
+//! into the output to what goes there, like:
+//!
+//! ```json
+//! {
+//!   "/fullName": { "from": "/name" },
+//!   "/contact/email": { "from": "/email", "default": null },
+//!   "/version": { "const": 2 }
+//! }
+//! ```
+//!
+//! A `from` copies the value at a pointer into the input. Where the input has
+//! no such value, as when an optional property is absent, the `default` goes
+//! in its place, or nothing at all if there is no default. A `const` is always
+//! the same value. Objects along the way to each output pointer are created as
+//! needed.
+
+use crate::pointer::Pointer;
+use failure::{bail, format_err, Error};
+use jddf::{Schema, Validator};
+use serde_json::{Map, Value};
+
+/// A mapping from documents of one schema into documents of another.
+#[derive(Debug)]
+pub struct Mapping(Vec<(Pointer, Source)>);
+
+#[derive(Debug)]
+enum Source {
+    From(Pointer, Option<Value>),
+    Const(Value),
+}
+
+impl Mapping {
+    /// Parses a mapping, as JSON.
+    pub fn parse(json: &Value) -> Result<Self, Error> {
+        let entries = json
+            .as_object()
+            .ok_or_else(|| format_err!("mapping must be an object of output pointers: {}", json))?;
+
+        // Map iterates in order of its keys, so that shorter pointers come
+        // first, and values within them are put in place after they are.
+        let mut mapping = Vec::new();
+        for (to, source) in entries {
+            let to = concrete(to)?;
+            if to.0.is_empty() {
+                bail!("mapping can't replace the whole output: {}", source);
+            }
+
+            let source = match (source.get("from"), source.get("const")) {
+                (Some(Value::String(from)), None) => {
+                    Source::From(concrete(from)?, source.get("default").cloned())
+                }
+                (None, Some(val)) => Source::Const(val.clone()),
+                _ => bail!(
+                    "mapping must have either a from pointer or a const value: {}",
+                    source
+                ),
+            };
+
+            mapping.push((to, source));
+        }
+
+        Ok(Mapping(mapping))
+    }
+
+    /// Applies the mapping to an input document, giving the output document
+    /// it's expected to be transformed into.
+    pub fn apply(&self, input: &Value) -> Result<Value, Error> {
+        let mut output = Value::Object(Map::new());
+        for (to, source) in &self.0 {
+            let val = match source {
+                Source::From(from, default) => match input.pointer(&from.to_string()) {
+                    Some(val) => val.clone(),
+                    None => match default {
+                        Some(default) => default.clone(),
+                        None => continue,
+                    },
+                },
+                Source::Const(val) => val.clone(),
+            };
+
+            insert(&mut output, to, val)?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Generates an input document, and the output it is expected to be
+/// transformed into, which must satisfy the output schema.
+pub fn pair<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    input_schema: &Schema,
+    output_schema: &Schema,
+    mapping: &Mapping,
+) -> Result<(Value, Value), Error> {
+    let input = crate::fuzz(rng, input_schema);
+    let output = mapping.apply(&input)?;
+
+    if let Some(error) = Validator::new().validate(output_schema, &output)?.first() {
+        bail!(
+            "mapping gives output which does not satisfy the output schema at schema path: {} (for input: {})",
+            error.schema_path(),
+            input
+        );
+    }
+
+    Ok((input, output))
+}
+
+/// Parses a pointer, which must point to one place rather than use `*`.
+fn concrete(s: &str) -> Result<Pointer, Error> {
+    let pointer = Pointer::parse(s)?;
+    if pointer.0.iter().any(|token| token == "*") {
+        bail!("mapping pointers can't use *: {}", s);
+    }
+
+    Ok(pointer)
+}
+
+/// Puts a value at a pointer within an object, creating objects along the way.
+fn insert(output: &mut Value, to: &Pointer, val: Value) -> Result<(), Error> {
+    let (last, parents) = to.0.split_last().unwrap();
+    let mut parent = output;
+    for token in parents {
+        parent = parent
+            .as_object_mut()
+            .ok_or_else(|| format_err!("mapping puts a value within a non-object: {}", to))?
+            .entry(token.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    parent
+        .as_object_mut()
+        .ok_or_else(|| format_err!("mapping puts a value within a non-object: {}", to))?
+        .insert(last.clone(), val);
+    Ok(())
+}
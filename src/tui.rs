@@ -0,0 +1,121 @@
+//! A dashboard of a run's statistics, redrawn in place on stderr.
+//!
+//! It shows the same snapshots `--stats` writes, so that anything on the
+//! dashboard can be found in the stats file too.
+
+use serde_json::Value;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How often the dashboard is redrawn.
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// How many characters wide a coverage bar is.
+const BAR_WIDTH: usize = 20;
+
+/// How many coverage bars are shown, for the least covered schema paths.
+const MAX_BARS: usize = 10;
+
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    /// When the dashboard was last drawn, if it has been.
+    last: Option<Instant>,
+
+    /// How many lines were drawn last time, to move back up over.
+    lines: usize,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Dashboard::default()
+    }
+
+    /// Whether it has been long enough since the dashboard was last drawn.
+    pub fn due(&self) -> bool {
+        self.last.is_none_or(|last| last.elapsed() >= REFRESH)
+    }
+
+    /// Draws a snapshot of the statistics over whatever was drawn before.
+    pub fn draw(&mut self, snapshot: &Value) -> io::Result<()> {
+        let float = |k: &str| snapshot[k].as_f64().unwrap_or(0.0);
+        let mut lines = vec![
+            format!(
+                "documents  {} ({:.1}/s)",
+                snapshot["documents"],
+                float("documentsPerSec")
+            ),
+            format!(
+                "bytes      {} ({:.0}/s)",
+                snapshot["bytes"],
+                float("bytesPerSec")
+            ),
+            format!(
+                "left out   {} too deep, {} already seen, {} breaking rules",
+                snapshot["skipped"], snapshot["stale"], snapshot["rejected"]
+            ),
+            format!(
+                "waiting    {:.0}% of the time on output, {:.1}s in total",
+                float("backpressure") * 100.0,
+                float("blockedSecs")
+            ),
+        ];
+
+        let mut coverage: Vec<_> = snapshot["coverage"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let seen = entry["seen"].as_u64()?;
+                let choices = entry["choices"].as_u64()?.max(1);
+                Some((
+                    seen as f64 / choices as f64,
+                    seen,
+                    choices,
+                    entry["schemaPath"].as_str()?,
+                ))
+            })
+            .collect();
+        coverage.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        if !coverage.is_empty() {
+            lines.push(format!(
+                "coverage   {} of {} enums and discriminators fully covered",
+                coverage
+                    .iter()
+                    .filter(|(covered, ..)| *covered >= 1.0)
+                    .count(),
+                coverage.len()
+            ));
+        }
+
+        for (covered, seen, choices, path) in coverage.into_iter().take(MAX_BARS) {
+            let filled = (covered * BAR_WIDTH as f64).round() as usize;
+            lines.push(format!(
+                "  [{}{}] {}/{} {}",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                seen,
+                choices,
+                if path.is_empty() { "(root)" } else { path }
+            ));
+        }
+
+        let stderr = io::stderr();
+        let mut out = stderr.lock();
+        if self.lines > 0 {
+            write!(out, "\x1b[{}A", self.lines)?;
+        }
+
+        for line in &lines {
+            writeln!(out, "\x1b[2K{}", line)?;
+        }
+
+        // Clears anything left below from a longer dashboard.
+        write!(out, "\x1b[J")?;
+        out.flush()?;
+
+        self.last = Some(Instant::now());
+        self.lines = lines.len();
+        Ok(())
+    }
+}
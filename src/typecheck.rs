@@ -0,0 +1,46 @@
+//! Checking that a Rust type can hold every value a schema allows, for keeping
+//! structs and schemas in sync.
+
+use jddf::Schema;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A value which satisfies the schema, but which the type failed to
+/// deserialize.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// The value, as generated.
+    pub instance: Value,
+
+    /// Why it failed to deserialize, as serde_json put it.
+    pub error: String,
+}
+
+/// Generates `samples` values satisfying a schema, and tries to deserialize
+/// each as a `T`.
+///
+/// Returns the values which failed to deserialize, keeping only the first for
+/// each distinct error, so that one mistake in the type is reported once
+/// rather than for nearly every sample.
+pub fn check<T: DeserializeOwned, R: Rng + ?Sized>(
+    rng: &mut R,
+    schema: &Schema,
+    samples: usize,
+) -> Vec<Mismatch> {
+    let mut errors = HashSet::new();
+    let mut mismatches = Vec::new();
+
+    for _ in 0..samples {
+        let instance = crate::fuzz(rng, schema);
+        if let Err(err) = serde_json::from_value::<T>(instance.clone()) {
+            let error = err.to_string();
+            if errors.insert(error.clone()) {
+                mismatches.push(Mismatch { instance, error });
+            }
+        }
+    }
+
+    mismatches
+}
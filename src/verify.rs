@@ -0,0 +1,178 @@
+//! Checking generated documents before they are written out.
+
+use crate::binary;
+use crate::pointer::Pointer;
+use failure::{bail, Error};
+use jddf::schema::{Form, Type};
+use jddf::{Schema, Validator};
+use serde_json::Value;
+
+/// Checks that a generated document satisfies its schema, and that every
+/// `float32` value in it reads back as the same `f32`.
+///
+/// Under `proto_json`, floats may be the strings the proto3 JSON mapping
+/// writes infinities and NaN as, which satisfy the schema too.
+pub fn verify(
+    validator: &Validator,
+    schema: &Schema,
+    instance: &Value,
+    proto_json: bool,
+) -> Result<(), Error> {
+    let mut numeric;
+    let instance = if proto_json {
+        numeric = instance.clone();
+        proto_floats_to_numbers(schema, schema, &mut numeric);
+        &numeric
+    } else {
+        instance
+    };
+
+    if let Some(error) = validator.validate(schema, instance)?.first() {
+        bail!(
+            "generated document does not satisfy schema at schema path: {} (instance path: {})",
+            error.schema_path(),
+            error.instance_path()
+        );
+    }
+
+    verify_f32(schema, schema, instance, &mut Vec::new())
+}
+
+/// Whether a number is the shortest decimal form of some `f32`.
+///
+/// Such a number is what a consumer gets back when it parses the number as an
+/// `f64` and then narrows it to an `f32`, and so it survives a round trip
+/// through JSON unchanged.
+///
+/// Infinities are too, since they narrow to the same infinity as an `f32`.
+pub fn is_f32_exact(n: f64) -> bool {
+    if n.is_infinite() {
+        return true;
+    }
+
+    let f = n as f32;
+    f.is_finite() && f.to_string().parse::<f64>().ok() == Some(n)
+}
+
+/// Replaces the strings the proto3 JSON mapping writes float infinities and
+/// NaN as with numbers, so that the schema can check the rest of the value.
+/// Infinities become `1e999` or `-1e999`, and NaN becomes zero, since it has
+/// no number of its own.
+fn proto_floats_to_numbers(root: &Schema, schema: &Schema, instance: &mut Value) {
+    match (schema.form(), instance) {
+        (Form::Type(Type::Float32), instance) | (Form::Type(Type::Float64), instance) => {
+            let number = match instance.as_str() {
+                Some("Infinity") => "1e999",
+                Some("-Infinity") => "-1e999",
+                Some("NaN") => "0",
+                _ => return,
+            };
+
+            *instance = serde_json::from_str(number).unwrap();
+        }
+        (Form::Ref(def), instance) => {
+            let definition = &root.definitions().as_ref().unwrap()[def];
+            proto_floats_to_numbers(root, definition, instance);
+        }
+        (Form::Elements(sub_schema), Value::Array(vals)) => {
+            for val in vals {
+                proto_floats_to_numbers(root, sub_schema, val);
+            }
+        }
+        (
+            Form::Properties {
+                required, optional, ..
+            },
+            Value::Object(vals),
+        ) => {
+            for (k, val) in vals {
+                if let Some(sub_schema) = required.get(k).or_else(|| optional.get(k)) {
+                    proto_floats_to_numbers(root, sub_schema, val);
+                }
+            }
+        }
+        (Form::Values(sub_schema), Value::Object(vals)) => {
+            for val in vals.values_mut() {
+                proto_floats_to_numbers(root, sub_schema, val);
+            }
+        }
+        (Form::Discriminator(tag, mapping), instance) => {
+            let sub_schema = instance
+                .get(tag)
+                .and_then(Value::as_str)
+                .and_then(|tag_val| mapping.get(tag_val));
+
+            if let Some(sub_schema) = sub_schema {
+                proto_floats_to_numbers(root, sub_schema, instance);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn verify_f32(
+    root: &Schema,
+    schema: &Schema,
+    instance: &Value,
+    path: &mut Vec<String>,
+) -> Result<(), Error> {
+    match (schema.form(), instance) {
+        (Form::Type(Type::Float32), Value::Number(n)) => {
+            // Newer versions of serde_json give no f64 for numbers like 1e999,
+            // rather than an infinite one.
+            let n = binary::float(n);
+            if !is_f32_exact(n) {
+                bail!(
+                    "float32 value is not exactly representable as an f32 at instance path: {} ({})",
+                    Pointer(path.clone()),
+                    n
+                );
+            }
+        }
+        (Form::Ref(def), _) => {
+            let definition = &root.definitions().as_ref().unwrap()[def];
+            verify_f32(root, definition, instance, path)?;
+        }
+        (Form::Elements(sub_schema), Value::Array(vals)) => {
+            for (i, val) in vals.iter().enumerate() {
+                path.push(i.to_string());
+                verify_f32(root, sub_schema, val, path)?;
+                path.pop();
+            }
+        }
+        (
+            Form::Properties {
+                required, optional, ..
+            },
+            Value::Object(vals),
+        ) => {
+            for (k, val) in vals {
+                if let Some(sub_schema) = required.get(k).or_else(|| optional.get(k)) {
+                    path.push(k.clone());
+                    verify_f32(root, sub_schema, val, path)?;
+                    path.pop();
+                }
+            }
+        }
+        (Form::Values(sub_schema), Value::Object(vals)) => {
+            for (k, val) in vals {
+                path.push(k.clone());
+                verify_f32(root, sub_schema, val, path)?;
+                path.pop();
+            }
+        }
+        (Form::Discriminator(tag, mapping), Value::Object(vals)) => {
+            let sub_schema = vals
+                .get(tag)
+                .and_then(Value::as_str)
+                .and_then(|tag_val| mapping.get(tag_val));
+
+            if let Some(sub_schema) = sub_schema {
+                verify_f32(root, sub_schema, instance, path)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Weights given inline, in a schema's metadata, so that some choices are made
+//! more often than others, without options kept apart from the schema.
+//!
+//! An enum's metadata can weigh its values, with an object, like
+//! `{ "enum": ["ok", "failed"], "metadata": { "fuzzWeight": { "ok": 9 } } }`.
+//! A discriminator variant's, or an optional property's, can weigh it with a
+//! number. Values and variants without a weight weigh one, and an optional
+//! property weighing `w` is present `w` times as often as it is absent.
+//!
+//! Options which make the same choices, such as `--optional`, take precedence.
+
+use crate::pointer::Pointer;
+use failure::{bail, Error};
+use jddf::schema::Form;
+use jddf::Schema;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The metadata member which weighs a schema's choices.
+pub const KEY: &str = "fuzzWeight";
+
+/// The weight of a discriminator variant or optional property, if it has one.
+pub fn of(schema: &Schema) -> Option<f64> {
+    get(schema)?.as_f64()
+}
+
+/// The weights of an enum's values, given in `vals`, if it has any.
+pub fn of_values(schema: &Schema, vals: &[&String]) -> Option<Vec<f64>> {
+    let weights = get(schema)?.as_object()?;
+    Some(
+        vals.iter()
+            .map(|val| weights.get(*val).and_then(Value::as_f64).unwrap_or(1.0))
+            .collect(),
+    )
+}
+
+/// The weights of a discriminator's variants, given in `tag_vals`, if any of
+/// them has one.
+pub fn of_variants(mapping: &HashMap<String, Schema>, tag_vals: &[&String]) -> Option<Vec<f64>> {
+    if tag_vals
+        .iter()
+        .all(|tag_val| of(&mapping[*tag_val]).is_none())
+    {
+        return None;
+    }
+
+    Some(
+        tag_vals
+            .iter()
+            .map(|tag_val| of(&mapping[*tag_val]).unwrap_or(1.0))
+            .collect(),
+    )
+}
+
+/// Rejects weights which aren't finite and non-negative, which rule out every choice, or which
+/// are on a schema that has no such choice to weigh. `weighable` is whether
+/// the schema is a discriminator variant or an optional property.
+pub fn check(schema: &Schema, path: &[String], weighable: bool) -> Result<(), Error> {
+    match (get(schema), schema.form()) {
+        (None, _) => {}
+        (Some(Value::Object(weights)), Form::Enum(vals)) => {
+            for (val, weight) in weights {
+                if !vals.contains(val) {
+                    bail!(
+                        "{} weighs {:?}, which is not one of the enum's values, at schema path: {}",
+                        KEY,
+                        val,
+                        Pointer(path.to_vec())
+                    );
+                }
+
+                if !weight.as_f64().is_some_and(|w| w >= 0.0 && w.is_finite()) {
+                    bail!(
+                        "{} of {:?} must be a finite, non-negative number, not {}, at schema path: {}",
+                        KEY,
+                        val,
+                        weight,
+                        Pointer(path.to_vec())
+                    );
+                }
+            }
+
+            let mut vals: Vec<_> = vals.iter().collect();
+            vals.sort();
+            if of_values(schema, &vals).unwrap().iter().all(|w| *w == 0.0) {
+                bail!(
+                    "{} rules out every value of the enum at schema path: {}",
+                    KEY,
+                    Pointer(path.to_vec())
+                );
+            }
+        }
+        (Some(Value::Object(_)), _) => bail!(
+            "{} objects are only for enum schemas at schema path: {}",
+            KEY,
+            Pointer(path.to_vec())
+        ),
+        (Some(weight), _) if !weighable => bail!(
+            "{} {} is only for discriminator variants and optional properties at schema path: {}",
+            KEY,
+            weight,
+            Pointer(path.to_vec())
+        ),
+        (Some(weight), _) => {
+            if !weight.as_f64().is_some_and(|w| w >= 0.0 && w.is_finite()) {
+                bail!(
+                    "{} must be a finite, non-negative number, not {}, at schema path: {}",
+                    KEY,
+                    weight,
+                    Pointer(path.to_vec())
+                );
+            }
+        }
+    }
+
+    if let Form::Discriminator(_, mapping) = schema.form() {
+        let mut tag_vals: Vec<_> = mapping.keys().collect();
+        tag_vals.sort();
+        if let Some(weights) = of_variants(mapping, &tag_vals) {
+            if weights.iter().all(|w| *w == 0.0) {
+                bail!(
+                    "{} rules out every variant of the discriminator at schema path: {}",
+                    KEY,
+                    Pointer(path.to_vec())
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get(schema: &Schema) -> Option<&Value> {
+    schema.extra().get("metadata")?.get(KEY)
+}
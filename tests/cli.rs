@@ -0,0 +1,916 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{self, Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Runs jddf-fuzz with these arguments, giving it this schema on stdin.
+fn run(args: &[&str], schema: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jddf-fuzz"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // jddf-fuzz may exit before reading the schema, such as when its options
+    // are rejected, and the pipe is then broken.
+    let _ = child.stdin.take().unwrap().write_all(schema.as_bytes());
+
+    child.wait_with_output().unwrap()
+}
+
+/// Makes an empty directory for a test to write files in.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("jddf-fuzz-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn no_optional_flags() {
+    let output = run(&["-n", "3"], r#"{"type":"uint8"}"#);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let docs: Vec<_> = stdout.lines().collect();
+    assert_eq!(docs.len(), 3);
+    for doc in docs {
+        assert!(doc.parse::<u8>().is_ok(), "not a uint8: {}", doc);
+    }
+}
+
+#[test]
+fn self_referential_definitions() {
+    let output = run(
+        &["-n", "1000"],
+        r#"{"definitions":{"a":{"elements":{"ref":"a"}}},"ref":"a"}"#,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().lines().count(),
+        1000
+    );
+}
+
+/// A discriminator whose first variant only ever refers back to it.
+const RECURSIVE_DISCRIMINATOR: &str = r#"{"definitions":{"t":{"discriminator":{"tag":"k","mapping":{"a":{"properties":{"x":{"ref":"t"}}},"b":{"properties":{}}}}}},"ref":"t"}"#;
+
+#[test]
+fn self_referential_discriminators() {
+    let output = run(&["-n", "500"], RECURSIVE_DISCRIMINATOR);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().lines().count(),
+        500
+    );
+}
+
+#[test]
+fn int_floats_conflict_with_verify() {
+    let output = run(
+        &["-n", "1", "--int-floats", "--verify"],
+        r#"{"type":"int8"}"#,
+    );
+    assert!(!output.status.success());
+
+    let dir = scratch("int-floats");
+    let config = dir.join("config.json");
+    fs::write(&config, r#"{"intFloats":0.5,"verify":true}"#).unwrap();
+    let output = run(
+        &["-n", "1", "--config", config.to_str().unwrap()],
+        r#"{"type":"int8"}"#,
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("intFloats"), "{}", stderr);
+}
+
+/// Runs jddf-fuzz, and gives what it wrote to stdout, failing if it failed.
+fn stdout(args: &[&str], schema: &str) -> String {
+    let output = run(args, schema);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Parses each line of output as a JSON document.
+fn documents(stdout: &str) -> Vec<serde_json::Value> {
+    stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn int_as_string() {
+    let docs = documents(&stdout(
+        &["-n", "50", "--int-as-string", "/properties/id=3..5"],
+        r#"{"properties":{"id":{"type":"string"},"name":{"type":"string"}}}"#,
+    ));
+
+    for doc in docs {
+        let id = doc["id"].as_str().unwrap();
+        assert!((3..=5).contains(&id.len()), "{}", doc);
+        assert!(id.bytes().all(|b| b.is_ascii_digit()), "{}", doc);
+    }
+}
+
+#[test]
+fn dict() {
+    let dict = stdout(
+        &["dict", "-"],
+        r#"{"properties":{"kind":{"enum":["on","off"]},"event":{"discriminator":{"tag":"type","mapping":{"click":{"properties":{}}}}}}}"#,
+    );
+
+    // Each entry is a JSON string, quoted again as AFL expects.
+    assert_eq!(
+        dict.lines().collect::<Vec<_>>(),
+        [
+            r#""\"click\"""#,
+            r#""\"event\"""#,
+            r#""\"kind\"""#,
+            r#""\"off\"""#,
+            r#""\"on\"""#,
+            r#""\"type\"""#,
+        ]
+    );
+}
+
+#[test]
+fn freeze() {
+    let docs = documents(&stdout(
+        &[
+            "-n",
+            "3",
+            "--freeze",
+            "/properties/id=seq",
+            "--freeze",
+            "/properties/createdAt=min",
+            "--freeze",
+            r#"/properties/role="admin""#,
+        ],
+        r#"{"properties":{"id":{"type":"uint32"},"createdAt":{"type":"timestamp"},"role":{"enum":["admin","user"]},"name":{"type":"string"}}}"#,
+    ));
+
+    assert_eq!(docs.len(), 3);
+    for (i, doc) in docs.iter().enumerate() {
+        assert_eq!(doc["id"], i, "{}", doc);
+        assert_eq!(doc["createdAt"], "1970-01-01T00:00:00+00:00", "{}", doc);
+        assert_eq!(doc["role"], "admin", "{}", doc);
+        assert!(doc["name"].is_string(), "{}", doc);
+    }
+
+    // A frozen value must satisfy the schema at its path.
+    let output = run(
+        &["-n", "1", "--freeze", "/properties/id=-1"],
+        r#"{"properties":{"id":{"type":"uint32"}}}"#,
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("does not satisfy the schema"), "{}", stderr);
+}
+
+#[test]
+fn mutate() {
+    let dir = scratch("mutate");
+    let schema = dir.join("schema.json");
+    fs::write(
+        &schema,
+        r#"{"properties":{"items":{"elements":{"type":"uint8"}}},"optionalProperties":{"note":{"type":"string"}}}"#,
+    )
+    .unwrap();
+
+    let args = [
+        "mutate",
+        schema.to_str().unwrap(),
+        "-",
+        "--mutations",
+        "flip-optional",
+        "--seed",
+        "7",
+    ];
+    // Several documents are read as a JSON text sequence.
+    let seeds = "\u{1e}{\"items\":[1,2,3]}\n\u{1e}{\"items\":[],\"note\":\"hi\"}\n";
+    let mutated = stdout(&args, seeds);
+    assert_eq!(mutated, stdout(&args, seeds));
+
+    // Only the optional property is flipped, and the rest left alone.
+    let docs = documents(&mutated);
+    assert_eq!(docs.len(), 2);
+    assert_eq!(docs[0]["items"], serde_json::json!([1, 2, 3]));
+    assert!(docs[0]["note"].is_string(), "{}", docs[0]);
+    assert_eq!(docs[1], serde_json::json!({ "items": [] }));
+
+    let output = run(
+        &[
+            "mutate",
+            schema.to_str().unwrap(),
+            "-",
+            "--mutations",
+            "bogus",
+        ],
+        seeds,
+    );
+    assert!(!output.status.success());
+}
+
+#[test]
+fn merge() {
+    let dir = scratch("merge");
+    for (file, contents) in &[
+        ("a/1.json", r#"{"a":1,"b":2}"#),
+        ("a/2.json", r#"{"x":"invalid"}"#),
+        ("b/3.json", r#"{ "b": 2, "a": 1 }"#),
+        ("b/4.json", "unparseable"),
+    ] {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    let schema = dir.join("schema.json");
+    fs::write(
+        &schema,
+        r#"{"properties":{"a":{"type":"uint8"},"b":{"type":"uint8"}}}"#,
+    )
+    .unwrap();
+
+    let out = dir.join("out");
+    let report: serde_json::Value = serde_json::from_str(&stdout(
+        &[
+            "merge",
+            out.to_str().unwrap(),
+            dir.join("a").to_str().unwrap(),
+            dir.join("b").to_str().unwrap(),
+            "--schema",
+            schema.to_str().unwrap(),
+        ],
+        "",
+    ))
+    .unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({ "merged": 1, "duplicates": 1, "invalid": 1, "unparseable": 1 })
+    );
+
+    // The documents which differ only in property order and whitespace are
+    // merged into one file, which the manifest says came from both.
+    let mut files: Vec<_> = fs::read_dir(&out)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert_eq!(files[1], "manifest.json");
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(out.join("manifest.json")).unwrap()).unwrap();
+    let merged = &manifest["files"][0];
+    assert_eq!(merged["file"], files[0]);
+    assert_eq!(
+        merged["sources"].as_array().unwrap().len(),
+        2,
+        "{}",
+        manifest
+    );
+}
+
+#[test]
+fn soak() {
+    let dir = scratch("soak");
+    let out = dir.join("out");
+    let stats = dir.join("stats.json");
+    let args = ["-n", "100", "--seed", "1"];
+    let schema = r#"{"type":"uint32"}"#;
+    let expected = stdout(&args, schema);
+
+    let soak = [
+        "--soak",
+        out.to_str().unwrap(),
+        "--rotate-bytes",
+        "200",
+        "--stats",
+        stats.to_str().unwrap(),
+    ];
+    assert_eq!(stdout(&[&args[..], &soak].concat(), schema), "");
+
+    // Each file holds at least as many bytes as given before the next is
+    // begun, and together they hold what would have been written to stdout.
+    let read_files = || {
+        let mut files: Vec<_> = fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    };
+
+    let files = read_files();
+    assert!(files.len() > 1, "{:?}", files);
+    assert_eq!(files[0].file_name().unwrap(), "000000.json");
+
+    let mut written = String::new();
+    for (i, file) in files.iter().enumerate() {
+        let contents = fs::read_to_string(file).unwrap();
+        if i + 1 < files.len() {
+            assert!(contents.len() >= 200, "{}: {}", file.display(), contents);
+        }
+
+        written.push_str(&contents);
+    }
+    assert_eq!(written, expected);
+
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats).unwrap()).unwrap();
+    assert_eq!(stats["documents"], 100);
+    assert_eq!(stats["files"], files.len());
+
+    // Restarting continues numbering where the last run left off.
+    stdout(&[&args[..], &soak].concat(), schema);
+    let restarted = read_files();
+    assert_eq!(restarted.len(), files.len() * 2);
+    assert_eq!(restarted[..files.len()], files[..]);
+}
+
+#[test]
+fn codegen_fixtures() {
+    let schema = r#"{"properties":{"ev":{"discriminator":{"tag":"t","mapping":{"a":{"properties":{}},"b":{"properties":{"n":{"type":"uint8"}}}}}}},"optionalProperties":{"x":{"type":"string"}}}"#;
+    let args = ["codegen-fixtures", "--seed", "1", "-"];
+    let source = stdout(&args, schema);
+    assert_eq!(source, stdout(&args, schema));
+
+    // Each constant is a raw string of JSON.
+    let fixtures: Vec<(&str, serde_json::Value)> = source
+        .split("pub const ")
+        .skip(1)
+        .map(|item| {
+            let (name, rest) = item.split_once(": &str = r#\"").unwrap();
+            let (json, _) = rest.split_once("\"#;").unwrap();
+            (name, serde_json::from_str(json).unwrap())
+        })
+        .collect();
+
+    let names: Vec<_> = fixtures.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["MINIMAL", "MAXIMAL", "BRANCH_EV_A", "BRANCH_EV_B"]);
+    assert_eq!(fixtures[0].1, serde_json::json!({ "ev": { "t": "a" } }));
+    assert!(fixtures[1].1["x"].is_string(), "{}", fixtures[1].1);
+    assert_eq!(fixtures[2].1["ev"]["t"], "a");
+    assert_eq!(fixtures[3].1["ev"]["t"], "b");
+    assert!(fixtures[3].1["ev"]["n"].is_u64(), "{}", fixtures[3].1);
+}
+
+#[test]
+fn pointers() {
+    let schema = r#"{"properties":{"items":{"elements":{"properties":{"id":{"type":"uint8"}}}},"meta":{"values":{"type":"string"}}},"optionalProperties":{"note":{"type":"string"}}}"#;
+    let listed = stdout(&["pointers", "-"], schema);
+    let patterns: Vec<_> = listed.lines().collect();
+    assert_eq!(
+        patterns,
+        [
+            "",
+            "/items",
+            "/items/*",
+            "/items/*/id",
+            "/meta",
+            "/meta/*",
+            "/note"
+        ]
+    );
+
+    assert_eq!(
+        stdout(&["pointers", "--max-len", "1", "-"], schema),
+        "\n/items\n/meta\n/note\n"
+    );
+
+    // Sampled pointers fill in each * with an array index below eight, or a
+    // random key.
+    let args = ["pointers", "--sample", "50", "--seed", "3", "-"];
+    let sampled = stdout(&args, schema);
+    assert_eq!(sampled, stdout(&args, schema));
+    assert_eq!(sampled.lines().count(), 50);
+    for pointer in sampled.lines() {
+        let tokens: Vec<_> = pointer.split('/').collect();
+        let matched = patterns.iter().any(|pattern| {
+            let pattern: Vec<_> = pattern.split('/').collect();
+            pattern.len() == tokens.len()
+                && pattern
+                    .iter()
+                    .zip(&tokens)
+                    .enumerate()
+                    .all(|(i, (p, t))| match *p {
+                        "*" if pattern[i - 1] == "items" => t.parse::<u8>().unwrap() < 8,
+                        "*" => true,
+                        p => p == *t,
+                    })
+        });
+        assert!(matched, "{:?}", pointer);
+    }
+}
+
+#[test]
+fn transform() {
+    let dir = scratch("transform");
+    let input = dir.join("user.json");
+    let output = dir.join("contact.json");
+    let mapping = dir.join("mapping.json");
+    fs::write(
+        &input,
+        r#"{"properties":{"name":{"type":"string"}},"optionalProperties":{"email":{"type":"string"}}}"#,
+    )
+    .unwrap();
+    fs::write(
+        &output,
+        r#"{"properties":{"fullName":{"type":"string"},"version":{"type":"uint8"},"contact":{"properties":{"email":{}}}}}"#,
+    )
+    .unwrap();
+    fs::write(
+        &mapping,
+        r#"{"/fullName":{"from":"/name"},"/contact/email":{"from":"/email","default":null},"/version":{"const":2}}"#,
+    )
+    .unwrap();
+
+    let args = [
+        "transform",
+        "-n",
+        "20",
+        "--seed",
+        "7",
+        input.to_str().unwrap(),
+        output.to_str().unwrap(),
+        mapping.to_str().unwrap(),
+    ];
+    let pairs = documents(&stdout(&args, ""));
+    assert_eq!(pairs.len(), 20);
+    for pair in &pairs {
+        let (input, expected) = (&pair["input"], &pair["expected"]);
+        assert_eq!(expected["fullName"], input["name"], "{}", pair);
+        assert_eq!(expected["version"], 2, "{}", pair);
+        match input.get("email") {
+            Some(email) => assert_eq!(&expected["contact"]["email"], email, "{}", pair),
+            None => assert!(expected["contact"]["email"].is_null(), "{}", pair),
+        }
+    }
+
+    // Outputs which don't satisfy the output schema are an error.
+    fs::write(&mapping, r#"{"/fullName":{"const":1}}"#).unwrap();
+    let failed = run(&args, "");
+    let stderr = String::from_utf8_lossy(&failed.stderr);
+    assert!(!failed.status.success());
+    assert!(
+        stderr.contains("does not satisfy the output schema"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn coverage() {
+    let output = run(
+        &["-n", "3", "--coverage"],
+        r#"{"properties":{"role":{"enum":["admin","guest","user"]},"tags":{"elements":{"type":"uint8"}}},"optionalProperties":{"nick":{"type":"string"}}}"#,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Every choice is taken in turn, so three documents are enough to take
+    // them all.
+    let docs = documents(&String::from_utf8(output.stdout).unwrap());
+    let roles: Vec<_> = docs.iter().map(|doc| doc["role"].clone()).collect();
+    assert_eq!(roles, ["admin", "guest", "user"]);
+    assert!(docs.iter().any(|doc| doc.get("nick").is_some()));
+    assert!(docs.iter().any(|doc| doc.get("nick").is_none()));
+    assert!(docs.iter().any(|doc| doc["tags"] == serde_json::json!([])));
+    assert!(docs.iter().any(|doc| doc["tags"] != serde_json::json!([])));
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report["covered"], 3, "{}", report);
+    assert_eq!(report["total"], 3, "{}", report);
+    assert_eq!(
+        report["choices"][1]["taken"],
+        serde_json::json!({ "admin": 1, "guest": 1, "user": 1 })
+    );
+}
+
+#[test]
+fn format() {
+    let schema = r#"{"properties":{"a":{"type":"uint8"}}}"#;
+    let args = ["-n", "2", "--seed", "1"];
+    let ndjson = stdout(&args, schema);
+    let docs = documents(&ndjson);
+    assert_eq!(
+        stdout(&[&args[..], &["--format", "ndjson"]].concat(), schema),
+        ndjson
+    );
+
+    let array = stdout(&[&args[..], &["--format", "array"]].concat(), schema);
+    let array: serde_json::Value = serde_json::from_str(&array).unwrap();
+    assert_eq!(array, serde_json::Value::Array(docs.clone()));
+
+    let pretty = stdout(&[&args[..], &["--format", "pretty"]].concat(), schema);
+    assert_eq!(
+        pretty,
+        format!(
+            "{{\n  \"a\": {}\n}}\n\n{{\n  \"a\": {}\n}}\n",
+            docs[0]["a"], docs[1]["a"]
+        )
+    );
+
+    let query = stdout(&[&args[..], &["--format", "query-string"]].concat(), schema);
+    assert_eq!(query, format!("?a={}\n?a={}\n", docs[0]["a"], docs[1]["a"]));
+}
+
+#[test]
+fn sinks() {
+    let dir = scratch("sinks");
+    let all = dir.join("all.jsonl");
+    let sampled = dir.join("sampled.jsonl");
+    let schema = r#"{"type":"uint8"}"#;
+    let args = ["-n", "200", "--seed", "1"];
+    let expected = stdout(&args, schema);
+
+    // Sinks get a line of JSON for each document, whatever the output format.
+    let all_sink = all.to_str().unwrap().to_owned();
+    let sampled_sink = format!("{},sample=0.1", sampled.display());
+    let array = stdout(
+        &[
+            &args[..],
+            &[
+                "--format",
+                "array",
+                "--sink",
+                &all_sink,
+                "--sink",
+                &sampled_sink,
+            ],
+        ]
+        .concat(),
+        schema,
+    );
+    assert!(array.starts_with('['), "{}", array);
+    assert_eq!(fs::read_to_string(&all).unwrap(), expected);
+
+    let sampled = fs::read_to_string(&sampled).unwrap();
+    let count = sampled.lines().count();
+    assert!(count > 0 && count < 100, "{} of 200 sampled", count);
+    let mut expected_lines = expected.lines();
+    for line in sampled.lines() {
+        assert!(expected_lines.any(|expected| expected == line), "{}", line);
+    }
+
+    // A sink which fails aborts the run, unless it's to be skipped or
+    // disabled instead.
+    let failing = "http://127.0.0.1:1/";
+    let output = run(&["-n", "5", "--sink", failing], schema);
+    assert!(!output.status.success());
+
+    let disabled = format!("{},on-error=disable", failing);
+    let output = run(&["-n", "5", "--sink", &disabled], schema);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 5);
+    assert!(stderr.contains("was disabled after failing"), "{}", stderr);
+}
+
+#[test]
+fn strategy() {
+    let schema = r#"{"properties":{"s":{"type":"string"},"n":{"elements":{"type":"uint8"}}},"optionalProperties":{"o":{"type":"boolean"}}}"#;
+    let sizes = ["--container-size", "1..3", "--string-len", "2..4"];
+    for (strategy, len, string_len) in &[("minimal", 1, 2), ("maximal", 3, 4)] {
+        let args = [&["-n", "20", "--strategy", strategy][..], &sizes].concat();
+        for doc in documents(&stdout(&args, schema)) {
+            assert_eq!(doc["n"].as_array().unwrap().len(), *len, "{}", doc);
+            assert_eq!(
+                doc["s"].as_str().unwrap().chars().count(),
+                *string_len,
+                "{}",
+                doc
+            );
+            assert_eq!(doc.get("o").is_some(), *strategy == "maximal", "{}", doc);
+        }
+    }
+
+    // Options which leave things out entirely still do.
+    let args = [
+        &["-n", "20", "--strategy", "maximal", "--optional-prob", "0"][..],
+        &sizes,
+    ]
+    .concat();
+    for doc in documents(&stdout(&args, schema)) {
+        assert!(doc.get("o").is_none(), "{}", doc);
+    }
+}
+
+/// A request received by `serve_posts`.
+struct Request {
+    head: String,
+    body: String,
+}
+
+/// Listens on a local port, answering each POST request with a 201, or a 422
+/// if its body is a number above 127, and keeping every request it gets.
+fn serve_posts() -> (String, Arc<Mutex<Vec<Request>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/ingest", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let received = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let received = Arc::clone(&received);
+            thread::spawn(move || {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                // Connections may be kept alive for further requests.
+                loop {
+                    let mut head = String::new();
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap() == 0 {
+                            return;
+                        }
+
+                        if line == "\r\n" {
+                            break;
+                        }
+
+                        head.push_str(&line);
+                    }
+
+                    let len: usize = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            if name.eq_ignore_ascii_case("content-length") {
+                                value.trim().parse().ok()
+                            } else {
+                                None
+                            }
+                        })
+                        .unwrap();
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    let body = String::from_utf8(body).unwrap();
+
+                    let status = match body.parse::<u8>() {
+                        Ok(n) if n > 127 => "422 Unprocessable Entity",
+                        _ => "201 Created",
+                    };
+                    received.lock().unwrap().push(Request { head, body });
+                    write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                }
+            });
+        }
+    });
+
+    (url, requests)
+}
+
+#[test]
+fn post() {
+    let (url, requests) = serve_posts();
+    let schema = r#"{"type":"uint8"}"#;
+    let args = ["-n", "50", "--seed", "1"];
+    let expected: Vec<u8> = stdout(&args, schema)
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+
+    let post = [
+        "--post",
+        &url,
+        "--header",
+        "Authorization: Bearer test",
+        "--concurrency",
+        "4",
+    ];
+    let output = run(&[&args[..], &post].concat(), schema);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(output.stdout, b"");
+
+    // Each document is sent once, in a request of its own, with the header.
+    let requests = requests.lock().unwrap();
+    let mut sent: Vec<u8> = requests
+        .iter()
+        .map(|req| req.body.parse().unwrap())
+        .collect();
+    let mut expected_sent = expected.clone();
+    sent.sort();
+    expected_sent.sort();
+    assert_eq!(sent, expected_sent);
+    for req in requests.iter() {
+        assert!(
+            req.head.starts_with("POST /ingest HTTP/1.1\r\n"),
+            "{}",
+            req.head
+        );
+        assert!(
+            req.head.contains("Authorization: Bearer test\r\n"),
+            "{}",
+            req.head
+        );
+        assert!(req.head.contains("application/json"), "{}", req.head);
+    }
+
+    let rejected = expected.iter().filter(|n| **n > 127).count();
+    let report: serde_json::Value = serde_json::from_str(stderr.trim()).unwrap();
+    assert_eq!(report["sent"], 50, "{}", report);
+    assert_eq!(report["errors"], 0, "{}", report);
+    assert_eq!(report["statuses"]["201"], 50 - rejected, "{}", report);
+    assert_eq!(report["statuses"]["422"], rejected, "{}", report);
+
+    // Headers which could smuggle in other headers are rejected.
+    let output = run(
+        &["-n", "1", "--post", &url, "--header", "X-A: b\r\nX-C: d"],
+        schema,
+    );
+    assert!(!output.status.success());
+}
+
+/// Sends a request to a server, and gives its response.
+fn request(addr: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// A running `jddf-fuzz serve`, stopped once the test is done with it, even if
+/// it fails.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn serve() {
+    let dir = scratch("serve");
+    let schema = dir.join("schema.json");
+    fs::write(&schema, r#"{"properties":{"id":{"type":"uint32"}}}"#).unwrap();
+
+    let mut child = Server(
+        Command::new(env!("CARGO_BIN_EXE_jddf-fuzz"))
+            .args(["serve", "--listen", "127.0.0.1:0"])
+            .arg(&schema)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+
+    let mut listening = String::new();
+    BufReader::new(child.0.stderr.take().unwrap())
+        .read_line(&mut listening)
+        .unwrap();
+    let addr = listening
+        .trim()
+        .strip_prefix("listening on http://")
+        .unwrap_or_else(|| panic!("{}", listening))
+        .to_owned();
+
+    let get = |target: &str| {
+        let response = request(&addr, &format!("GET {} HTTP/1.1\r\n\r\n", target));
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_owned(), body.to_owned())
+    };
+
+    // A request with a seed gets the first document a run with that seed
+    // would write, whatever the path, and the same one each time.
+    let first = stdout(&["-n", "1", "--seed", "7", schema.to_str().unwrap()], "");
+    let (status, body) = get("/users?seed=7");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(body, first.trim_end());
+    assert_eq!(get("/?seed=%37").1, body);
+
+    let (status, body) = get("/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let doc: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(doc["id"].is_u64(), "{}", doc);
+
+    assert_eq!(get("/?seed=x").0, "HTTP/1.1 400 Bad Request");
+    let response = request(&addr, "POST / HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+    assert!(
+        response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"),
+        "{}",
+        response
+    );
+}
+
+#[test]
+fn freeze_recursive_minimum() {
+    let docs = documents(&stdout(
+        &["-n", "20", "--freeze", "/definitions/t=min"],
+        RECURSIVE_DISCRIMINATOR,
+    ));
+
+    assert_eq!(docs.len(), 20);
+    for doc in docs {
+        assert_eq!(doc, serde_json::json!({ "k": "b" }));
+    }
+}
+
+#[test]
+fn progress_json_descriptors() {
+    let dir = scratch("progress_json_descriptors");
+    let dir = dir.to_str().unwrap();
+
+    for (fd, message) in &[
+        ("fd:1", "file descriptor is not free for progress: 1"),
+        ("fd:97", "file descriptor is not open: 97"),
+    ] {
+        let output = run(&["--soak", dir, "-n", "3", "--progress-json", fd], "{}");
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+#[test]
+fn toml_config() {
+    let dir = scratch("toml-config");
+    let json = dir.join("config.json");
+    fs::write(
+        &json,
+        r#"{
+            "adversarial": true,
+            "maxDepth": 4,
+            "onDepthLimit": "null",
+            "stringEdgeCases": 0,
+            "intAsString": { "/properties/id": { "min": 10, "max": 20 } },
+            "freeze": {
+                "/properties/id": "sequence",
+                "/properties/kind": { "value": "user" }
+            }
+        }"#,
+    )
+    .unwrap();
+    let toml = dir.join("config.toml");
+    fs::write(
+        &toml,
+        r#"
+            adversarial = true
+            maxDepth = 4
+            onDepthLimit = "null"
+            stringEdgeCases = 0
+
+            [intAsString."/properties/id"]
+            min = 10
+            max = 20
+
+            [freeze]
+            "/properties/id" = "sequence"
+            "/properties/kind" = { value = "user" }
+        "#,
+    )
+    .unwrap();
+
+    let schema = r#"{"properties":{"id":{"type":"string"},"kind":{"type":"string"}}}"#;
+    let print_config = |path: &PathBuf| {
+        let output = run(
+            &[
+                "-n",
+                "1",
+                "--print-config",
+                "--config",
+                path.to_str().unwrap(),
+            ],
+            schema,
+        );
+        assert!(output.status.success(), "{:?}", output);
+        output.stderr
+    };
+    assert_eq!(print_config(&toml), print_config(&json));
+
+    fs::write(&toml, "maxDepth = \"four\"").unwrap();
+    let output = run(&["-n", "1", "--config", toml.to_str().unwrap()], schema);
+    assert!(!output.status.success());
+}